@@ -0,0 +1,38 @@
+use clap::ValueEnum;
+use dash7::dialect::Dialect;
+
+/// Protocol dialect spoken by the other side
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DialectArg {
+    /// DASH7 Alliance Protocol v1.2
+    Spec,
+    /// Sub-IoT v0.1
+    Subiot,
+    /// Sub-IoT v0.0
+    #[clap(name = "subiot-v0-0")]
+    SubiotV0_0,
+    /// Wizzilab v5.3
+    Wizzilab,
+}
+
+impl Default for DialectArg {
+    fn default() -> Self {
+        match Dialect::default() {
+            Dialect::SpecV1_2 => Self::Spec,
+            Dialect::SubIoTV0_0 => Self::SubiotV0_0,
+            Dialect::SubIoTV0_1 => Self::Subiot,
+            Dialect::WizzilabV5_3 => Self::Wizzilab,
+        }
+    }
+}
+
+impl From<DialectArg> for Dialect {
+    fn from(dialect: DialectArg) -> Self {
+        match dialect {
+            DialectArg::Spec => Dialect::SpecV1_2,
+            DialectArg::Subiot => Dialect::SubIoTV0_1,
+            DialectArg::SubiotV0_0 => Dialect::SubIoTV0_0,
+            DialectArg::Wizzilab => Dialect::WizzilabV5_3,
+        }
+    }
+}
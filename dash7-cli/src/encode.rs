@@ -0,0 +1,626 @@
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use dash7::{
+    app::{
+        action::{Action, OpCode},
+        command::Command,
+        interface::{Dash7InterfaceConfiguration, InterfaceConfiguration},
+        operation::{
+            ActionHeader, ActionQuery, ActionStatus, Chunk, ChunkStep, CopyFile, FileData, FileId,
+            FileOffset, Forward, IndirectForward, Logic, LogicOp, Nop, ReadFileData, RequestTag,
+            RequestTagHeader, ResponseTag, ResponseTagHeader, Status, StatusCode,
+        },
+        query::{
+            ArithmeticComparisonType, ArithmeticQueryParams, ComparisonWithValue, NonVoid, Query,
+        },
+    },
+    dialect::Dialect,
+    file::File,
+    link::AccessClass,
+    network::{Address, Addressee, NlsState},
+    session::{ResponseMode, RetryMode},
+    transport::GroupCondition,
+};
+
+use crate::parse::Parsed;
+
+/// Build an ALP command from a textual description and print it as hex
+///
+/// Actions are separated by `;` or newlines, arguments by whitespace. Numbers are decimal or
+/// `0x` prefixed hex, data is always hex. The `group` and `response` flags can be appended to
+/// any action that has an action header.
+///
+/// Actions:
+///   nop
+///   read <file> <offset> <length>
+///   read_properties | exist | delete | restore | flush | execute <file>
+///   write | write_flush | return <file> <offset> <data>
+///   query | break_query | verify_checksum nonvoid <file> <offset> <length>
+///   query | break_query | verify_checksum compare <file> <offset> <eq|ne|lt|le|gt|ge> <value> [mask=<data>] [signed=<bool>]
+///   copy <source file> <destination file>
+///   status <action id> <status code>
+///   request_tag <id> [eop=<bool>]
+///   response_tag <id> [eop] [error]
+///   chunk start | continue | end | startend
+///   logic or | xor | nor | nand
+///   forward host | serial
+///   forward dash7 noid | nbid=<n> | uid=<hex> | vid=<hex> [access_class=<n>] [response_mode=<mode>] [retry_mode=<mode>]
+///     [stop_on_error=<bool>] [record=<bool>] [dormant_timeout=<s>] [execution_delay=<ms>]
+///     [group_condition=any|ne|eq|gt] [use_vid=<bool>]
+///   indirect_forward
+///
+/// Example: `request_tag 1; read 0x00 0 8 response; write 0x40 0 01020304 group`
+///
+/// Request tags ask for the end of packet flag in the last response, unless `eop=false`.
+///
+/// `execution_delay` is not supported by Sub-IoT, `group_condition` and `use_vid` only by Wizzilab.
+///
+/// A JSON object is accepted as well: either a serialized command, or the output of
+/// `dash7 parse --format json`.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct EncodeArgs {
+    /// Command description. Reads from stdin when omitted or `-`
+    #[arg()]
+    description: Option<String>,
+}
+
+/// Tokens of a single action
+struct Tokens<'a> {
+    name: &'a str,
+    args: Vec<&'a str>,
+    position: usize,
+    group: bool,
+    response: bool,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(action: &'a str) -> Option<Self> {
+        let mut words = action.split_whitespace();
+        let name = words.next()?;
+
+        let mut tokens = Self {
+            name,
+            args: vec![],
+            position: 0,
+            group: false,
+            response: false,
+        };
+        for word in words {
+            match word {
+                "group" => tokens.group = true,
+                "response" => tokens.response = true,
+                _ => tokens.args.push(word),
+            }
+        }
+        Some(tokens)
+    }
+
+    fn header(&self) -> ActionHeader {
+        ActionHeader::new(self.group, self.response)
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a str> {
+        let value = self
+            .args
+            .get(self.position)
+            .ok_or_else(|| anyhow!("{}: missing {}", self.name, what))?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn next_u32(&mut self, what: &str) -> Result<u32> {
+        let value = self.next(what)?;
+        parse_number(value).with_context(|| format!("{}: invalid {} '{}'", self.name, what, value))
+    }
+
+    fn next_u8(&mut self, what: &str) -> Result<u8> {
+        let value = self.next_u32(what)?;
+        value
+            .try_into()
+            .map_err(|_| anyhow!("{}: {} {} out of range", self.name, what, value))
+    }
+
+    fn next_hex(&mut self, what: &str) -> Result<Vec<u8>> {
+        let value = self.next(what)?;
+        parse_hex(value).with_context(|| format!("{}: invalid {} '{}'", self.name, what, value))
+    }
+
+    /// Remaining `key=value` options
+    fn options(&mut self) -> Result<Vec<(&'a str, &'a str)>> {
+        let options = self.args[self.position..]
+            .iter()
+            .map(|option| {
+                option
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("{}: expected key=value, got '{}'", self.name, option))
+            })
+            .collect();
+        self.position = self.args.len();
+        options
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.position < self.args.len() {
+            bail!(
+                "{}: unexpected arguments {:?}",
+                self.name,
+                &self.args[self.position..]
+            );
+        }
+        Ok(())
+    }
+}
+
+fn parse_number(value: &str) -> Result<u32> {
+    Ok(match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16)?,
+        None => value.parse()?,
+    })
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(value.strip_prefix("0x").unwrap_or(value))?)
+}
+
+fn file_id_action(tokens: &mut Tokens, opcode: OpCode) -> Result<FileId> {
+    Ok(FileId {
+        header: tokens.header(),
+        opcode,
+        file_id: tokens.next_u8("file id")?,
+    })
+}
+
+fn file_data_action(tokens: &mut Tokens, opcode: OpCode) -> Result<FileData> {
+    let file_id = tokens.next_u8("file id")?;
+    let offset = tokens.next_u32("offset")?;
+    let data = tokens.next_hex("data")?;
+
+    Ok(FileData::new(
+        tokens.header(),
+        FileOffset {
+            file_id,
+            offset: offset.into(),
+        },
+        File::Other(data),
+        opcode,
+    ))
+}
+
+fn query(tokens: &mut Tokens, opcode: OpCode) -> Result<ActionQuery> {
+    let query = match tokens.next("query type")? {
+        "nonvoid" => Query::NonVoid(NonVoid {
+            file: FileOffset {
+                file_id: tokens.next_u8("file id")?,
+                offset: tokens.next_u32("offset")?.into(),
+            },
+            length: tokens.next_u32("length")?.into(),
+        }),
+        "compare" => {
+            let file = FileOffset {
+                file_id: tokens.next_u8("file id")?,
+                offset: tokens.next_u32("offset")?.into(),
+            };
+            let comparison_type = match tokens.next("comparison")? {
+                "ne" => ArithmeticComparisonType::Inequal,
+                "eq" => ArithmeticComparisonType::Equal,
+                "lt" => ArithmeticComparisonType::LessThan,
+                "le" => ArithmeticComparisonType::LessThanOrEqual,
+                "gt" => ArithmeticComparisonType::GreaterThan,
+                "ge" => ArithmeticComparisonType::GreaterThanOrEqual,
+                other => bail!("{}: unknown comparison '{}'", tokens.name, other),
+            };
+            let value = tokens.next_hex("value")?;
+
+            let mut signed = false;
+            let mut mask = vec![];
+            for (key, option) in tokens.options()? {
+                match key {
+                    "signed" => signed = option.parse()?,
+                    "mask" => mask = parse_hex(option)?,
+                    _ => bail!("{}: unknown option '{}'", tokens.name, key),
+                }
+            }
+
+            Query::ComparisonWithValue(ComparisonWithValue::new(
+                ArithmeticQueryParams {
+                    signed,
+                    comparison_type,
+                },
+                mask,
+                value,
+                file,
+            ))
+        }
+        other => bail!("{}: unknown query type '{}'", tokens.name, other),
+    };
+
+    Ok(ActionQuery {
+        header: tokens.header(),
+        opcode,
+        query,
+    })
+}
+
+fn address(value: &str) -> Result<Address> {
+    let (kind, id) = value.split_once('=').unwrap_or((value, ""));
+    Ok(match kind {
+        "noid" => Address::NoId,
+        "nbid" => Address::NbId(parse_number(id)?.into()),
+        "uid" => Address::UId(u64::from_str_radix(id.trim_start_matches("0x"), 16)?),
+        "vid" => Address::VId(u16::from_str_radix(id.trim_start_matches("0x"), 16)?),
+        _ => bail!("unknown address '{}'", value),
+    })
+}
+
+fn dash7_configuration(
+    tokens: &mut Tokens,
+    dialect: Dialect,
+) -> Result<Dash7InterfaceConfiguration> {
+    let address = address(tokens.next("address")?)?;
+
+    let mut configuration = Dash7InterfaceConfiguration::default();
+    let mut access_class = AccessClass::default();
+    let mut group_condition = GroupCondition::Any;
+    let mut use_vid = false;
+
+    for (key, value) in tokens.options()? {
+        match key {
+            "access_class" => {
+                let value = parse_number(value)?;
+                access_class = AccessClass::new(((value >> 4) & 0x0F) as u8, (value & 0x0F) as u8);
+            }
+            "response_mode" => {
+                configuration.qos.response_mode = match value {
+                    "no" => ResponseMode::No,
+                    "all" => ResponseMode::All,
+                    "any" => ResponseMode::Any,
+                    "norepeat" => ResponseMode::NoRepeat,
+                    "onerror" => ResponseMode::OnError,
+                    "preferred" => ResponseMode::Preferred,
+                    _ => bail!("unknown response mode '{}'", value),
+                }
+            }
+            "retry_mode" => {
+                configuration.qos.retry_mode = match value {
+                    "no" => RetryMode::No,
+                    "oneshot" => RetryMode::OneshotRetry,
+                    "fifofast" => RetryMode::FifoFast,
+                    "fifoslow" => RetryMode::FifoSlow,
+                    "singlefast" => RetryMode::SingleFast,
+                    "singleslow" => RetryMode::SingleSlow,
+                    "oneshotsticky" => RetryMode::OneshotSticky,
+                    _ => bail!("unknown retry mode '{}'", value),
+                }
+            }
+            "stop_on_error" => configuration.qos.stop_on_error = value.parse()?,
+            "record" => configuration.qos.record = value.parse()?,
+            "dormant_timeout" => {
+                configuration.dormant_session_timeout = parse_number(value)?.into()
+            }
+            "execution_delay" if dialect.has_execution_delay_timeout() => {
+                configuration.execution_delay_timeout = parse_number(value)?.into()
+            }
+            "group_condition" if dialect.is_wizzilab() => {
+                group_condition = match value {
+                    "any" => GroupCondition::Any,
+                    "ne" => GroupCondition::NotEqual,
+                    "eq" => GroupCondition::Equal,
+                    "gt" => GroupCondition::GreaterThan,
+                    _ => bail!("unknown group condition '{}'", value),
+                }
+            }
+            "use_vid" if dialect.is_wizzilab() => use_vid = value.parse()?,
+            "execution_delay" | "group_condition" | "use_vid" => bail!(
+                "{}: option '{}' is not supported by {:?}",
+                tokens.name,
+                key,
+                dialect
+            ),
+            _ => bail!("{}: unknown option '{}'", tokens.name, key),
+        }
+    }
+
+    configuration.addressee = Addressee::new(address, NlsState::None, access_class);
+    configuration.addressee.group_condition = group_condition;
+    configuration.addressee.use_vid = use_vid;
+    Ok(configuration)
+}
+
+fn action(description: &str, dialect: Dialect) -> Result<Option<Action>> {
+    let Some(mut tokens) = Tokens::new(description) else {
+        return Ok(None);
+    };
+
+    let action = match tokens.name {
+        "nop" => Action::Nop(Nop {
+            header: tokens.header(),
+            opcode: OpCode::NOP,
+        }),
+        "read" => Action::ReadFileData(ReadFileData {
+            header: tokens.header(),
+            opcode: OpCode::READ_FILE_DATA,
+            offset: FileOffset {
+                file_id: tokens.next_u8("file id")?,
+                offset: tokens.next_u32("offset")?.into(),
+            },
+            length: tokens.next_u32("length")?.into(),
+        }),
+        "read_properties" => {
+            Action::ReadFileProperties(file_id_action(&mut tokens, OpCode::READ_FILE_PROPERTIES)?)
+        }
+        "write" => Action::WriteFileData(file_data_action(&mut tokens, OpCode::WRITE_FILE_DATA)?),
+        "write_flush" => Action::WriteFileDataFlush(file_data_action(
+            &mut tokens,
+            OpCode::WRITE_FILE_DATA_FLUSH,
+        )?),
+        "return" => {
+            Action::ReturnFileData(file_data_action(&mut tokens, OpCode::RETURN_FILE_DATA)?)
+        }
+        "query" => Action::ActionQuery(query(&mut tokens, OpCode::ACTION_QUERY)?),
+        "break_query" => Action::BreakQuery(query(&mut tokens, OpCode::BREAK_QUERY)?),
+        "verify_checksum" => Action::VerifyChecksum(query(&mut tokens, OpCode::VERIFY_CHECKSUM)?),
+        "exist" => Action::ExistFile(file_id_action(&mut tokens, OpCode::EXIST_FILE)?),
+        "delete" => Action::DeleteFile(file_id_action(&mut tokens, OpCode::DELETE_FILE)?),
+        "restore" => Action::RestoreFile(file_id_action(&mut tokens, OpCode::RESTORE_FILE)?),
+        "flush" => Action::FlushFile(file_id_action(&mut tokens, OpCode::FLUSH_FILE)?),
+        "execute" => Action::ExecuteFile(file_id_action(&mut tokens, OpCode::EXECUTE_FILE)?),
+        "copy" => Action::CopyFile(CopyFile {
+            header: tokens.header(),
+            opcode: OpCode::COPY_FILE,
+            src_file_id: tokens.next_u8("source file id")?,
+            dst_file_id: tokens.next_u8("destination file id")?,
+        }),
+        "status" => Action::Status(
+            Status::Action(ActionStatus {
+                action_id: tokens.next_u8("action id")?,
+                status: StatusCode(tokens.next_u8("status code")?),
+            })
+            .into(),
+        ),
+        "request_tag" => {
+            let id = tokens.next_u8("tag id")?;
+            let mut header = RequestTagHeader {
+                end_of_packet: true,
+            };
+            for (key, value) in tokens.options()? {
+                match key {
+                    "eop" => header.end_of_packet = value.parse()?,
+                    _ => bail!("{}: unknown option '{}'", tokens.name, key),
+                }
+            }
+            Action::RequestTag(RequestTag {
+                header,
+                opcode: OpCode::REQUEST_TAG,
+                id,
+            })
+        }
+        "response_tag" => {
+            let id = tokens.next_u8("tag id")?;
+            let mut header = ResponseTagHeader::default();
+            while let Ok(flag) = tokens.next("flag") {
+                match flag {
+                    "eop" => header.end_of_packet = true,
+                    "error" => header.error = true,
+                    _ => bail!("{}: unknown flag '{}'", tokens.name, flag),
+                }
+            }
+            Action::ResponseTag(ResponseTag {
+                header,
+                opcode: OpCode::RESPONSE_TAG,
+                id,
+            })
+        }
+        "chunk" => Action::Chunk(Chunk {
+            step: match tokens.next("chunk step")? {
+                "continue" => ChunkStep::Continue,
+                "start" => ChunkStep::Start,
+                "end" => ChunkStep::End,
+                "startend" => ChunkStep::StartEnd,
+                other => bail!("{}: unknown chunk step '{}'", tokens.name, other),
+            },
+            opcode: OpCode::CHUNK,
+        }),
+        "logic" => Action::Logic(Logic {
+            logic: match tokens.next("logic operation")? {
+                "or" => LogicOp::Or,
+                "xor" => LogicOp::Xor,
+                "nor" => LogicOp::Nor,
+                "nand" => LogicOp::Nand,
+                other => bail!("{}: unknown logic operation '{}'", tokens.name, other),
+            },
+            opcode: OpCode::LOGIC,
+        }),
+        "forward" => {
+            let configuration = match tokens.next("interface")? {
+                "host" => InterfaceConfiguration::Host,
+                "serial" => InterfaceConfiguration::Serial,
+                "dash7" | "d7" => {
+                    InterfaceConfiguration::Dash7(dash7_configuration(&mut tokens, dialect)?)
+                }
+                other => bail!("{}: unknown interface '{}'", tokens.name, other),
+            };
+            Action::Forward(Forward::new(tokens.response, configuration))
+        }
+        "indirect_forward" => Action::IndirectForward(IndirectForward::new(tokens.response, None)),
+        other => bail!("unknown action '{}'", other),
+    };
+
+    tokens.finish()?;
+    Ok(Some(action))
+}
+
+/// Builds a command from its textual description
+pub fn command(description: &str, dialect: Dialect) -> Result<Command> {
+    let mut actions = vec![];
+    for part in description.split([';', '\n']) {
+        if let Some(action) = action(part, dialect)? {
+            actions.push(action);
+        }
+    }
+    if actions.is_empty() {
+        bail!("No actions given");
+    }
+    Ok(Command::new(actions))
+}
+
+fn run(args: EncodeArgs, dialect: Dialect) -> Result<String> {
+    let description = match args.description {
+        Some(description) if description != "-" => description,
+        _ => {
+            let mut description = String::new();
+            std::io::stdin().read_to_string(&mut description)?;
+            description
+        }
+    };
+
+    let bytes = if description.trim_start().starts_with('{') {
+        json(&description, dialect)?
+    } else {
+        command(&description, dialect)?
+            .to_bytes_with_dialect(dialect)
+            .map_err(|err| anyhow!("Could not encode command: {}", err))?
+    };
+    Ok(hex::encode_upper(bytes))
+}
+
+/// Encodes a JSON command, or the JSON output of the parse subcommand
+fn json(description: &str, dialect: Dialect) -> Result<Vec<u8>> {
+    let value: serde_json::Value = serde_json::from_str(description).context("Invalid JSON")?;
+
+    if value.get("type").is_some() {
+        let parsed: Parsed = serde_json::from_value(value).context("Invalid parse output")?;
+        return parsed.to_bytes(dialect);
+    }
+
+    let command: Command = serde_json::from_value(value).context("Invalid command")?;
+    command
+        .to_bytes_with_dialect(dialect)
+        .map_err(|err| anyhow!("Could not encode command: {}", err))
+}
+
+pub fn main(args: EncodeArgs, dialect: Dialect) {
+    match run(args, dialect) {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("{:#}", err);
+            quit::with_code(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DIALECT: Dialect = Dialect::SpecV1_2;
+
+    fn command(description: &str) -> Result<Command> {
+        super::command(description, DIALECT)
+    }
+
+    fn encode(description: &str) -> String {
+        hex::encode_upper(command(description).unwrap().to_bytes().unwrap())
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(
+            encode("request_tag 66; read 0 0 8 response; read 4 2 3 group; nop group response"),
+            "B4424100000881040203C0"
+        );
+    }
+
+    #[test]
+    fn test_request_tag() {
+        assert_eq!(encode("request_tag 66"), "B442");
+        assert_eq!(encode("request_tag 66 eop=true"), "B442");
+        assert_eq!(encode("request_tag 66 eop=false"), "3442");
+        assert!(command("request_tag 66 eop").is_err());
+        assert!(command("request_tag 66 error=true").is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        assert_eq!(encode("write 0xF9 5 010203 group"), "84F90503010203");
+    }
+
+    #[test]
+    fn test_forward() {
+        assert_eq!(encode("forward serial\nindirect_forward"), "320133");
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            encode("query compare 4 5 eq 090909; break_query nonvoid 5 6 4"),
+            "08410309090904050900040506"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let description = "request_tag 3; response_tag 3 eop error; status 1 0xF6; chunk start; \
+                           logic xor; copy 1 2; exist 0x40 response; return 0x40 0 AABB";
+        let command = command(description).unwrap();
+        let bytes = command.to_bytes().unwrap();
+        assert_eq!(Command::try_from(bytes.as_slice()).unwrap(), command);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(command("").is_err());
+        assert!(command("read 0 0").is_err());
+        assert!(command("read 0 0 8 9").is_err());
+        assert!(command("read 0x100 0 8").is_err());
+        assert!(command("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let command = command("request_tag 3; read 0 0 8 response").unwrap();
+        let bytes = command.to_bytes().unwrap();
+
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(super::json(&json, DIALECT).unwrap(), bytes);
+
+        let parsed = format!(r#"{{"type":"alp","data":{},"trailing":"FF"}}"#, json);
+        let mut expected = bytes.clone();
+        expected.push(0xFF);
+        assert_eq!(super::json(&parsed, DIALECT).unwrap(), expected);
+
+        assert!(super::json(r#"{"actions":[{"Frobnicate":{}}]}"#, DIALECT).is_err());
+    }
+
+    #[test]
+    fn test_dialect() {
+        let description =
+            "forward dash7 vid=ABCD access_class=0x01 execution_delay=4 group_condition=eq use_vid=true";
+
+        let command = super::command(description, Dialect::WizzilabV5_3).unwrap();
+        assert_eq!(
+            hex::encode_upper(
+                command
+                    .to_bytes_with_dialect(Dialect::WizzilabV5_3)
+                    .unwrap()
+            ),
+            "32D7000004B801ABCD"
+        );
+
+        assert!(super::command(description, Dialect::SpecV1_2).is_err());
+        assert!(
+            super::command("forward dash7 noid execution_delay=4", Dialect::SubIoTV0_1).is_err()
+        );
+        assert_eq!(
+            hex::encode_upper(
+                super::command("forward dash7 noid", Dialect::SubIoTV0_1)
+                    .unwrap()
+                    .to_bytes_with_dialect(Dialect::SubIoTV0_1)
+                    .unwrap()
+            ),
+            "32D700001000"
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
 
+mod dialect;
+mod encode;
+mod modem;
 mod parse;
+mod schema;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -14,12 +18,23 @@ struct Cli {
     /// Control command verbosity
     #[command(flatten)]
     verbose: Verbosity,
+
+    /// Protocol dialect. Defaults to the one selected at build time
+    #[arg(value_enum, long = "dialect", global = true, default_value_t)]
+    dialect: dialect::DialectArg,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Parse a hex string
     Parse(parse::ParseArgs),
+
+    // a doc comment would replace the long help of `EncodeArgs`, which describes the actions
+    #[command(about = "Build an ALP command from a textual description and print it as hex")]
+    Encode(encode::EncodeArgs),
+
+    /// Talk to a modem connected over a serial port
+    Modem(modem::ModemArgs),
 }
 
 #[quit::main]
@@ -30,7 +45,10 @@ pub fn main() {
         .filter_level(cli.verbose.log_level_filter())
         .init();
 
+    let dialect = cli.dialect.into();
     match cli.command {
-        Commands::Parse(args) => parse::main(args),
+        Commands::Parse(args) => parse::main(args, dialect),
+        Commands::Encode(args) => encode::main(args, dialect),
+        Commands::Modem(args) => modem::main(args, dialect),
     }
 }
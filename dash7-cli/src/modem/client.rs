@@ -0,0 +1,500 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use dash7::{
+    app::{
+        command::Command,
+        tag::{Received, TagTracker},
+    },
+    dialect::Dialect,
+    serial::{MessageType, SerialFrame, SerialMessage},
+};
+use futures::{SinkExt, Stream, StreamExt};
+use serialport::SerialPortBuilderExt;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, oneshot, Mutex as AsyncMutex},
+    task::JoinHandle,
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use super::codec::SerialCodec;
+
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Stream of commands received from the modem
+///
+/// When it belongs to a request, dropping it finishes the request, so its tag can be used again.
+pub struct Commands {
+    receiver: mpsc::UnboundedReceiver<Command>,
+    request: Option<(u8, Arc<Mutex<Shared>>)>,
+}
+
+impl Commands {
+    fn new(receiver: mpsc::UnboundedReceiver<Command>) -> Self {
+        Self {
+            receiver,
+            request: None,
+        }
+    }
+}
+
+impl Stream for Commands {
+    type Item = Command;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl fmt::Debug for Commands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Commands")
+            .field("request", &self.request.as_ref().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl Drop for Commands {
+    fn drop(&mut self) {
+        let Some((id, shared)) = &self.request else {
+            return;
+        };
+        self.receiver.close();
+        let Ok(mut shared) = shared.lock() else {
+            return;
+        };
+        // once finished, the tag may already belong to a new request, which is still open
+        if shared.pending.get(id).is_some_and(|tx| tx.is_closed()) {
+            shared.tracker.finish(*id);
+            shared.pending.remove(id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Matches responses to the requests that are waiting for them
+    tracker: TagTracker,
+
+    /// Receivers of the responses to pending requests, keyed on their request tag
+    pending: HashMap<u8, mpsc::UnboundedSender<Command>>,
+
+    /// Pings that are waiting for a response, keyed on their payload
+    pings: HashMap<Vec<u8>, oneshot::Sender<()>>,
+}
+
+/// Async client for a DASH7 modem connected over a serial line
+///
+/// Outgoing commands are wrapped in serial frames. Incoming commands carrying the response tag
+/// of a pending request are routed to that request, everything else ends up in the
+/// unsolicited command stream.
+pub struct Modem {
+    dialect: Dialect,
+    writer: AsyncMutex<FramedWrite<BoxedWriter, SerialCodec>>,
+    shared: Arc<Mutex<Shared>>,
+    counter: AtomicU8,
+    ping_counter: AtomicU8,
+    unsolicited: Option<Commands>,
+    reader: JoinHandle<()>,
+}
+
+impl Modem {
+    /// Opens the serial device at `path`
+    pub fn open(path: &str, baud_rate: u32, dialect: Dialect) -> Result<Self> {
+        let port = serialport::new(path, baud_rate)
+            .open_native_async()
+            .with_context(|| format!("Could not open serial port {}", path))?;
+        Ok(Self::new(port, dialect))
+    }
+
+    /// Talks to a modem over any byte stream, eg. a pseudo-terminal or an in-memory duplex
+    pub fn new<T>(io: T, dialect: Dialect) -> Self
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read_half, write_half) = tokio::io::split(io);
+        let writer: BoxedWriter = Box::new(write_half);
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (unsolicited_tx, unsolicited_rx) = mpsc::unbounded_channel();
+
+        let reader = tokio::spawn(Self::read_loop(
+            FramedRead::new(read_half, SerialCodec::new(dialect)),
+            shared.clone(),
+            unsolicited_tx,
+        ));
+
+        Self {
+            dialect,
+            writer: AsyncMutex::new(FramedWrite::new(writer, SerialCodec::new(dialect))),
+            shared,
+            counter: AtomicU8::new(0),
+            ping_counter: AtomicU8::new(0),
+            unsolicited: Some(Commands::new(unsolicited_rx)),
+            reader,
+        }
+    }
+
+    async fn read_loop<R>(
+        mut frames: FramedRead<R, SerialCodec>,
+        shared: Arc<Mutex<Shared>>,
+        unsolicited: mpsc::UnboundedSender<Command>,
+    ) where
+        R: AsyncRead + Unpin,
+    {
+        while let Some(frame) = frames.next().await {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(err) => {
+                    log::error!("Could not read from modem: {}", err);
+                    break;
+                }
+            };
+
+            match frame.message {
+                SerialMessage::Alp(command) => {
+                    let mut shared = shared.lock().unwrap();
+                    match shared.tracker.receive(command) {
+                        Received::Response(response) => {
+                            let id = response.id;
+                            let sent = shared
+                                .pending
+                                .get(&id)
+                                .is_some_and(|tx| tx.send(response.command).is_ok());
+                            if !sent || response.last {
+                                shared.tracker.finish(id);
+                                shared.pending.remove(&id);
+                            }
+                        }
+                        Received::Unsolicited(command) => {
+                            let _ = unsolicited.send(command);
+                        }
+                    }
+                }
+                SerialMessage::PingResponse(data) => {
+                    if let Some(tx) = shared.lock().unwrap().pings.remove(&data) {
+                        let _ = tx.send(());
+                    }
+                }
+                SerialMessage::Logging(message) => log::info!("modem: {}", message),
+                message => log::debug!("Ignoring {:?} message: {:?}", frame.message_type, message),
+            }
+        }
+
+        // The connection is gone: finish all outstanding requests
+        let mut shared = shared.lock().unwrap();
+        shared.tracker = TagTracker::new();
+        shared.pending.clear();
+        shared.pings.clear();
+    }
+
+    async fn send_frame(&self, message_type: MessageType, message: SerialMessage) -> Result<()> {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        let frame = SerialFrame::new(counter, message_type, message);
+        self.writer.lock().await.send(frame).await?;
+        Ok(())
+    }
+
+    /// Sends a command without waiting for any response
+    pub async fn send(&self, command: Command) -> Result<()> {
+        self.send_frame(MessageType::alp(self.dialect), SerialMessage::Alp(command))
+            .await
+    }
+
+    /// Sends a command as a request and returns the stream of its responses
+    ///
+    /// A request tag is allocated and prepended when the command does not have one yet.
+    /// The stream ends after the last response (`end_of_packet`) was received. Dropping it
+    /// earlier stops waiting for responses.
+    pub async fn request(&self, mut command: Command) -> Result<Commands> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let id = {
+            let mut shared = self.shared.lock().unwrap();
+            let id = shared.tracker.tag(&mut command)?;
+            shared.pending.insert(id, tx);
+            id
+        };
+
+        let responses = Commands {
+            receiver: rx,
+            request: Some((id, self.shared.clone())),
+        };
+        self.send(command).await?;
+        Ok(responses)
+    }
+
+    /// Sends a request and collects all of its responses
+    pub async fn execute(&self, command: Command, timeout: Duration) -> Result<Vec<Command>> {
+        let responses = self.request(command).await?;
+        tokio::time::timeout(timeout, responses.collect())
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for responses"))
+    }
+
+    /// Checks whether the modem is alive
+    pub async fn ping(&self, timeout: Duration) -> Result<Duration> {
+        let payload = vec![self.ping_counter.fetch_add(1, Ordering::Relaxed)];
+
+        let (tx, rx) = oneshot::channel();
+        self.shared
+            .lock()
+            .unwrap()
+            .pings
+            .insert(payload.clone(), tx);
+
+        let start = tokio::time::Instant::now();
+        let result = async {
+            self.send_frame(
+                MessageType::ping_request(self.dialect),
+                SerialMessage::PingRequest(payload.clone()),
+            )
+            .await?;
+            tokio::time::timeout(timeout, rx)
+                .await
+                .map_err(|_| anyhow!("Timed out waiting for ping response"))?
+                .map_err(|_| anyhow!("Connection to the modem was closed"))
+        }
+        .await;
+
+        if result.is_err() {
+            self.shared.lock().unwrap().pings.remove(&payload);
+        }
+        result.map(|_| start.elapsed())
+    }
+
+    /// Takes the stream of commands that are not a response to any request
+    ///
+    /// This can only be taken once.
+    pub fn unsolicited(&mut self) -> Option<Commands> {
+        self.unsolicited.take()
+    }
+}
+
+impl Drop for Modem {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dash7::app::{
+        action::{Action, OpCode},
+        operation::{
+            ActionHeader, FileData, FileOffset, ReadFileData, RequestTag, RequestTagHeader,
+            ResponseTag, ResponseTagHeader,
+        },
+    };
+    use dash7::file::File;
+    use futures::SinkExt;
+    use tokio::io::DuplexStream;
+    use tokio_util::codec::Framed;
+
+    use super::*;
+
+    fn read_file(file_id: u8) -> Command {
+        Command::new(vec![Action::ReadFileData(ReadFileData {
+            header: ActionHeader::new(false, true),
+            opcode: OpCode::READ_FILE_DATA,
+            offset: FileOffset::no_offset(file_id),
+            length: 4u32.into(),
+        })])
+    }
+
+    fn response(id: u8, end_of_packet: bool, data: Vec<u8>) -> Command {
+        let mut actions = vec![Action::ResponseTag(ResponseTag {
+            header: ResponseTagHeader {
+                end_of_packet,
+                error: false,
+            },
+            opcode: OpCode::RESPONSE_TAG,
+            id,
+        })];
+        if !data.is_empty() {
+            actions.push(Action::ReturnFileData(FileData::new(
+                ActionHeader::default(),
+                FileOffset::no_offset(0x40),
+                File::Other(data),
+                OpCode::RETURN_FILE_DATA,
+            )));
+        }
+        Command::new(actions)
+    }
+
+    const DIALECT: Dialect = Dialect::SubIoTV0_1;
+
+    fn device(stream: DuplexStream, dialect: Dialect) -> Framed<DuplexStream, SerialCodec> {
+        Framed::new(stream, SerialCodec::new(dialect))
+    }
+
+    #[tokio::test]
+    async fn test_request_response() {
+        let (host, device_stream) = tokio::io::duplex(1024);
+        let mut modem = Modem::new(host, DIALECT);
+        let mut unsolicited = modem.unsolicited().unwrap();
+        let mut device = device(device_stream, DIALECT);
+
+        let device_task = tokio::spawn(async move {
+            let request = device.next().await.unwrap().unwrap();
+            let command = request.command().unwrap().clone();
+            let id = command.request_id().expect("request tag should be added");
+            assert_eq!(&command.actions[1..], &read_file(0x40).actions[..]);
+
+            // unsolicited traffic in between the responses
+            device
+                .send(SerialFrame::alp(
+                    0,
+                    response(id.wrapping_add(1), true, vec![9]),
+                    DIALECT,
+                ))
+                .await
+                .unwrap();
+            device
+                .send(SerialFrame::alp(
+                    1,
+                    response(id, false, vec![1, 2, 3, 4]),
+                    DIALECT,
+                ))
+                .await
+                .unwrap();
+            device
+                .send(SerialFrame::alp(2, response(id, true, vec![]), DIALECT))
+                .await
+                .unwrap();
+            id
+        });
+
+        let responses = modem
+            .execute(read_file(0x40), Duration::from_secs(5))
+            .await
+            .unwrap();
+        let id = device_task.await.unwrap();
+
+        assert_eq!(
+            responses,
+            vec![
+                response(id, false, vec![1, 2, 3, 4]),
+                response(id, true, vec![])
+            ]
+        );
+        assert_eq!(
+            unsolicited.next().await,
+            Some(response(id.wrapping_add(1), true, vec![9]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_keeps_tag() {
+        let (host, device_stream) = tokio::io::duplex(1024);
+        let modem = Modem::new(host, DIALECT);
+        let mut device = device(device_stream, DIALECT);
+
+        let mut command = read_file(0);
+        command.actions.insert(
+            0,
+            Action::RequestTag(RequestTag {
+                header: RequestTagHeader {
+                    end_of_packet: true,
+                },
+                opcode: OpCode::REQUEST_TAG,
+                id: 42,
+            }),
+        );
+
+        let _responses = modem.request(command.clone()).await.unwrap();
+        let request = device.next().await.unwrap().unwrap();
+        assert_eq!(request.command(), Some(&command));
+
+        // the same tag cannot be used twice while pending
+        assert!(modem.request(command).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_finishes_request() {
+        let (host, device_stream) = tokio::io::duplex(1024);
+        let modem = Modem::new(host, DIALECT);
+        let _device = device(device_stream, DIALECT);
+
+        let mut command = read_file(0);
+        command.actions.insert(
+            0,
+            Action::RequestTag(RequestTag {
+                header: RequestTagHeader {
+                    end_of_packet: true,
+                },
+                opcode: OpCode::REQUEST_TAG,
+                id: 42,
+            }),
+        );
+
+        assert!(modem
+            .execute(command.clone(), Duration::from_millis(10))
+            .await
+            .is_err());
+        assert!(modem.shared.lock().unwrap().pending.is_empty());
+
+        // the tag is free again, also after dropping the responses of a request
+        let responses = modem.request(command.clone()).await.unwrap();
+        drop(responses);
+        assert!(modem.request(command).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ping_timeout() {
+        let (host, device_stream) = tokio::io::duplex(1024);
+        let modem = Modem::new(host, DIALECT);
+        let _device = device(device_stream, DIALECT);
+
+        assert!(modem.ping(Duration::from_millis(10)).await.is_err());
+        assert!(modem.shared.lock().unwrap().pings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        for dialect in [Dialect::SubIoTV0_1, Dialect::WizzilabV5_3] {
+            let (host, device_stream) = tokio::io::duplex(1024);
+            let modem = Modem::new(host, dialect);
+            let mut device = device(device_stream, dialect);
+
+            tokio::spawn(async move {
+                let request = device.next().await.unwrap().unwrap();
+                let SerialMessage::PingRequest(data) = request.message else {
+                    panic!("expected a ping request");
+                };
+                device
+                    .send(SerialFrame::new(
+                        0,
+                        MessageType::ping_response(dialect),
+                        SerialMessage::PingResponse(data),
+                    ))
+                    .await
+                    .unwrap();
+            });
+
+            modem.ping(Duration::from_secs(5)).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_ends_requests() {
+        let (host, device_stream) = tokio::io::duplex(1024);
+        let modem = Modem::new(host, DIALECT);
+
+        let responses = modem.request(read_file(0)).await.unwrap();
+        drop(device_stream);
+
+        assert!(responses.collect::<Vec<_>>().await.is_empty());
+    }
+}
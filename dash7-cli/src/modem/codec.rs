@@ -0,0 +1,152 @@
+use std::io;
+
+use dash7::{dialect::Dialect, serial::SerialFrame};
+use tokio_util::{
+    bytes::{Buf, BufMut, BytesMut},
+    codec::{Decoder, Encoder},
+};
+
+/// Splits a serial byte stream into [`SerialFrame`]s
+///
+/// Garbage in between frames, and frames that fail to decode (eg. a CRC mismatch), are
+/// skipped. The decoder resynchronises on the next sync bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SerialCodec {
+    dialect: Dialect,
+}
+
+impl SerialCodec {
+    pub fn new(dialect: Dialect) -> Self {
+        Self { dialect }
+    }
+
+    fn find_sync(&self, data: &[u8]) -> Option<usize> {
+        let sync = SerialFrame::sync(self.dialect);
+        data.windows(sync.len()).position(|window| window == sync)
+    }
+}
+
+impl Decoder for SerialCodec {
+    type Item = SerialFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            match self.find_sync(src) {
+                Some(start) => src.advance(start),
+                None => {
+                    // keep the last byte around, it might be the start of the next sync
+                    let keep = src.len().min(SerialFrame::sync(self.dialect).len() - 1);
+                    src.advance(src.len() - keep);
+                    return Ok(None);
+                }
+            }
+
+            let Some(size) = SerialFrame::frame_size(src, self.dialect) else {
+                return Ok(None);
+            };
+
+            if src.len() < size {
+                src.reserve(size - src.len());
+                return Ok(None);
+            }
+
+            match SerialFrame::from_bytes_with_dialect((&src[..size], 0), self.dialect) {
+                Ok((_, frame)) => {
+                    src.advance(size);
+                    return Ok(Some(frame));
+                }
+                Err(err) => {
+                    log::warn!("Dropping invalid serial frame: {}", err);
+                    src.advance(1);
+                }
+            }
+        }
+    }
+}
+
+impl Encoder<SerialFrame> for SerialCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: SerialFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let data = item
+            .to_bytes_with_dialect(self.dialect)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        dst.put_slice(&data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dash7::serial::{MessageType, SerialMessage};
+
+    use super::*;
+
+    fn ping(counter: u8, dialect: Dialect) -> SerialFrame {
+        SerialFrame::new(
+            counter,
+            MessageType::ping_request(dialect),
+            SerialMessage::PingRequest(vec![counter]),
+        )
+    }
+
+    fn bytes(frame: SerialFrame, dialect: Dialect) -> Vec<u8> {
+        frame.to_bytes_with_dialect(dialect).unwrap()
+    }
+
+    #[test]
+    fn test_decode_partial() {
+        for dialect in Dialect::ALL {
+            let data = bytes(ping(1, dialect), dialect);
+            let mut codec = SerialCodec::new(dialect);
+
+            let mut buf = BytesMut::from(&data[..3]);
+            assert_eq!(codec.decode(&mut buf).unwrap(), None);
+
+            buf.extend_from_slice(&data[3..]);
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(ping(1, dialect)));
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_decode_resync() {
+        for dialect in Dialect::ALL {
+            let mut buf = BytesMut::new();
+            buf.extend_from_slice(&[0x12, 0x34, SerialFrame::sync(dialect)[0]]);
+            buf.extend_from_slice(&bytes(ping(2, dialect), dialect));
+
+            let mut codec = SerialCodec::new(dialect);
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(ping(2, dialect)));
+            assert!(buf.is_empty());
+        }
+    }
+
+    // Wizzilab frames carry no CRC
+    #[test]
+    fn test_decode_skips_corrupted_frame() {
+        let dialect = Dialect::SubIoTV0_1;
+        let mut corrupted = bytes(ping(1, dialect), dialect);
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+
+        let mut buf = BytesMut::from(corrupted.as_slice());
+        buf.extend_from_slice(&bytes(ping(2, dialect), dialect));
+
+        let mut codec = SerialCodec::new(dialect);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(ping(2, dialect)));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_encode() {
+        for dialect in Dialect::ALL {
+            let mut buf = BytesMut::new();
+            SerialCodec::new(dialect)
+                .encode(ping(3, dialect), &mut buf)
+                .unwrap();
+            assert_eq!(&buf[..], bytes(ping(3, dialect), dialect).as_slice());
+        }
+    }
+}
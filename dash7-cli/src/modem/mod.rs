@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use dash7::{app::command::Command, dialect::Dialect};
+use futures::StreamExt;
+
+mod client;
+mod codec;
+
+use client::Modem;
+
+#[derive(Debug, Args)]
+pub struct ModemArgs {
+    /// Serial device the modem is connected to
+    #[arg(short = 'p', long = "port")]
+    port: String,
+
+    #[arg(short = 'b', long = "baud", default_value_t = 115200)]
+    baud_rate: u32,
+
+    #[clap(subcommand)]
+    command: ModemCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum ModemCommands {
+    /// Print every command received from the modem
+    Listen,
+
+    /// Send an ALP command, given as a hex string, and print the responses
+    Send(SendArgs),
+
+    /// Check whether the modem responds
+    Ping(TimeoutArgs),
+}
+
+#[derive(Debug, Args)]
+struct TimeoutArgs {
+    /// Time to wait for a response, in seconds
+    #[arg(short = 't', long = "timeout", default_value_t = 10)]
+    timeout: u64,
+}
+
+#[derive(Debug, Args)]
+struct SendArgs {
+    #[command(flatten)]
+    timeout: TimeoutArgs,
+
+    /// Do not wait for responses
+    #[arg(short = 'n', long = "no-response")]
+    no_response: bool,
+
+    #[arg()]
+    hex: String,
+}
+
+fn remove_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+async fn listen(mut modem: Modem) -> Result<()> {
+    let mut commands = modem
+        .unsolicited()
+        .context("Unsolicited commands already taken")?;
+    while let Some(command) = commands.next().await {
+        println!("{}", command);
+    }
+    Ok(())
+}
+
+async fn send(modem: Modem, args: SendArgs, dialect: Dialect) -> Result<()> {
+    let input = hex::decode(remove_whitespace(&args.hex)).context("Could not parse input hex")?;
+    let (rest, command) = Command::from_bytes_with_dialect((input.as_slice(), 0), dialect)
+        .context("Could not parse command")?;
+    if !rest.0.is_empty() {
+        bail!("Could not parse command: too much data");
+    }
+
+    if args.no_response {
+        return modem.send(command).await;
+    }
+
+    let responses = modem
+        .execute(command, Duration::from_secs(args.timeout.timeout))
+        .await?;
+    for response in responses.iter() {
+        println!("{}", response);
+    }
+    Ok(())
+}
+
+async fn ping(modem: Modem, args: TimeoutArgs) -> Result<()> {
+    let elapsed = modem.ping(Duration::from_secs(args.timeout)).await?;
+    println!("Modem responded in {:?}", elapsed);
+    Ok(())
+}
+
+async fn run(args: ModemArgs, dialect: Dialect) -> Result<()> {
+    let modem = Modem::open(&args.port, args.baud_rate, dialect)?;
+
+    match args.command {
+        ModemCommands::Listen => listen(modem).await,
+        ModemCommands::Send(send_args) => send(modem, send_args, dialect).await,
+        ModemCommands::Ping(timeout_args) => ping(modem, timeout_args).await,
+    }
+}
+
+pub fn main(args: ModemArgs, dialect: Dialect) {
+    let runtime = tokio::runtime::Runtime::new().expect("Could not start async runtime");
+
+    if let Err(err) = runtime.block_on(run(args, dialect)) {
+        eprintln!("{:#}", err);
+        quit::with_code(1);
+    }
+}
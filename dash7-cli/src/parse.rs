@@ -1,10 +1,19 @@
+use std::{fmt, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
 use clap::{Args, ValueEnum};
 use dash7::{
     app::command::Command,
-    file::{File, FileId},
+    dialect::Dialect,
+    file::{File, FileRegistry},
     link::{BackgroundFrame, ForegroundFrame},
+    serial::{SerialFrame, SerialMessage},
+    transport::TransportDecoder,
 };
-use deku::DekuError;
+use deku::{writer::Writer, DekuContainerRead, DekuContainerWrite, DekuWriter};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::Schema;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum ParseType {
@@ -20,6 +29,17 @@ enum ParseType {
     Systemfile,
 }
 
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Single line debug representation
+    Debug,
+    /// Human readable, multi-line representation
+    #[default]
+    Pretty,
+    Json,
+    Yaml,
+}
+
 #[derive(Debug, Args)]
 pub struct ParseArgs {
     #[arg(value_enum, short = 't', long = "type")]
@@ -28,93 +48,488 @@ pub struct ParseArgs {
     #[arg(short = 'f')]
     file_id: Option<u8>,
 
+    #[arg(value_enum, long = "format", default_value_t)]
+    format: OutputFormat,
+
+    /// TOML or YAML file describing the layout of application files
+    #[arg(long = "schema")]
+    schema: Option<PathBuf>,
+
     #[arg()]
     hex: String,
 }
 
+/// A decoded payload, tagged with the type it was parsed as
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum Decoded {
+    Foreground(Vec<ForegroundFrame>),
+    Background(BackgroundFrame),
+    Alp(Command),
+    Serial(Vec<SerialFrame>),
+    Systemfile(File),
+}
+
+/// Output of the parse subcommand
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parsed {
+    #[serde(flatten)]
+    pub decoded: Decoded,
+
+    /// Bytes left over after decoding, as hex
+    #[serde(default)]
+    pub trailing: String,
+}
+
+impl Parsed {
+    fn new(decoded: Decoded, trailing: &[u8]) -> Self {
+        Self {
+            decoded,
+            trailing: hex::encode_upper(trailing),
+        }
+    }
+
+    /// Encodes the decoded structure again, followed by the trailing bytes
+    pub fn to_bytes(&self, dialect: Dialect) -> Result<Vec<u8>> {
+        let mut bytes = match &self.decoded {
+            Decoded::Foreground(frames) => {
+                let mut bytes = vec![];
+                let mut cursor = std::io::Cursor::new(&mut bytes);
+                let mut writer = Writer::new(&mut cursor);
+                for frame in frames.iter() {
+                    frame.to_writer(&mut writer, dialect)?;
+                }
+                writer.finalize()?;
+                bytes
+            }
+            Decoded::Background(frame) => frame.to_bytes()?,
+            Decoded::Alp(command) => command.to_bytes_with_dialect(dialect)?,
+            Decoded::Serial(frames) => {
+                let mut bytes = vec![];
+                for frame in frames.iter() {
+                    bytes.extend(frame.to_bytes_with_dialect(dialect)?);
+                }
+                bytes
+            }
+            Decoded::Systemfile(_) => bail!("Encoding system files is not supported"),
+        };
+        bytes.extend(hex::decode(&self.trailing).context("Invalid trailing bytes")?);
+        Ok(bytes)
+    }
+}
+
+impl fmt::Display for Parsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.decoded {
+            Decoded::Foreground(frames) => {
+                for (idx, frame) in frames.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{:#?}", frame)?;
+                }
+            }
+            Decoded::Background(frame) => write!(f, "{:#?}", frame)?,
+            Decoded::Alp(command) => write!(f, "{}", command)?,
+            Decoded::Serial(frames) => {
+                for (idx, frame) in frames.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(f)?;
+                    }
+                    match frame.command() {
+                        Some(command) => write!(
+                            f,
+                            "SerialFrame {{ counter: {}, message_type: {:?} }}\n{}",
+                            frame.counter, frame.message_type, command
+                        )?,
+                        None => write!(f, "{:#?}", frame)?,
+                    }
+                }
+            }
+            Decoded::Systemfile(file) => write!(f, "{:#?}", file)?,
+        }
+
+        if !self.trailing.is_empty() {
+            write!(f, "\nTrailing bytes: {}", self.trailing)?;
+        }
+        Ok(())
+    }
+}
+
 fn remove_whitespace(s: &str) -> String {
     s.chars().filter(|c| !c.is_whitespace()).collect()
 }
 
-fn parse_foreground_frame(input: &[u8]) -> Result<(), DekuError> {
-    let frame = ForegroundFrame::try_from(input)?;
-    println!("{:?}", frame);
-    Ok(())
+fn parse_foreground_frame(
+    input: &[u8],
+    dialect: Dialect,
+    registry: &FileRegistry,
+) -> Result<Parsed> {
+    // Consecutive frames are decoded as parts of the same dialogs, which tells requests from
+    // responses. Anything after the last frame that decodes is reported as trailing data.
+    let mut decoder = TransportDecoder::default();
+    let mut frames = vec![];
+    let mut rest = (input, 0);
+    loop {
+        match decoder.decode_foreground(rest, dialect) {
+            Ok((next, mut frame)) => {
+                frame
+                    .resolve_files(registry)
+                    .with_context(|| format!("In foreground frame {}", frames.len()))?;
+                frames.push(frame);
+                rest = next;
+            }
+            Err(err) if frames.is_empty() => return Err(err.into()),
+            Err(_) => break,
+        }
+        if rest.0.is_empty() {
+            break;
+        }
+    }
+
+    Ok(Parsed::new(Decoded::Foreground(frames), rest.0))
 }
 
-fn parse_background_frame(input: &[u8]) -> Result<(), DekuError> {
-    let frame = BackgroundFrame::try_from(input)?;
-    println!("{:?}", frame);
-    Ok(())
+fn parse_background_frame(input: &[u8], _: Dialect, _: &FileRegistry) -> Result<Parsed> {
+    let ((rest, _), frame) = BackgroundFrame::from_bytes((input, 0))?;
+    Ok(Parsed::new(Decoded::Background(frame), rest))
 }
 
-fn parse_alp_command(input: &[u8]) -> Result<(), DekuError> {
-    let command = Command::try_from(input)?;
-    println!("{}", command);
-    Ok(())
+fn parse_alp_command(input: &[u8], dialect: Dialect, registry: &FileRegistry) -> Result<Parsed> {
+    let ((rest, _), command) = Command::from_bytes_with_registry((input, 0), dialect, registry)?;
+    Ok(Parsed::new(Decoded::Alp(command), rest))
 }
 
-fn parse_serial(_input: &[u8]) -> Result<(), DekuError> {
-    Err(DekuError::Assertion("Not implemented".into()))
+fn parse_serial(input: &[u8], dialect: Dialect, registry: &FileRegistry) -> Result<Parsed> {
+    // A capture may contain several consecutive frames. Anything after the
+    // last frame that decodes is reported as trailing data.
+    let mut frames = vec![];
+    let mut rest = (input, 0);
+    loop {
+        match SerialFrame::from_bytes_with_dialect(rest, dialect) {
+            Ok((next, mut frame)) => {
+                if let SerialMessage::Alp(command) = &mut frame.message {
+                    command
+                        .resolve_files(registry)
+                        .with_context(|| format!("In serial frame {}", frame.counter))?;
+                }
+                frames.push(frame);
+                rest = next;
+            }
+            Err(err) if frames.is_empty() => return Err(err.into()),
+            Err(_) => break,
+        }
+        if rest.0.is_empty() {
+            break;
+        }
+    }
+
+    Ok(Parsed::new(Decoded::Serial(frames), rest.0))
 }
 
-fn parse_file(input: &[u8], file_id: FileId) -> Result<(), DekuError> {
-    let file = File::from_bytes((input, 0), file_id, 0)?;
-    println!("{:?}", file);
-    Ok(())
+fn parse_file(input: &[u8], file_id: u8, registry: &FileRegistry) -> Result<Parsed> {
+    let ((rest, _), file) =
+        File::from_bytes_with_registry((input, 0), file_id, input.len() as u32, registry)?;
+    Ok(Parsed::new(Decoded::Systemfile(file), rest))
 }
 
-fn parse_any_file(input: &[u8]) -> Result<(), DekuError> {
-    for file_id_raw in 0..=0x2Eu8 {
-        let file_id: FileId = file_id_raw.try_into()?;
-        if parse_file(input, file_id).is_ok() {
-            return Ok(());
+/// Picks the first candidate that consumes all input, or else the first one that decoded at all
+fn best_match(candidates: impl Iterator<Item = Result<Parsed>>) -> Option<Parsed> {
+    let mut fallback = None;
+    for parsed in candidates.flatten() {
+        if parsed.trailing.is_empty() {
+            return Some(parsed);
         }
+        fallback.get_or_insert(parsed);
     }
-    Ok(())
+    fallback
 }
 
-fn parse_any(input: &[u8]) -> Result<(), DekuError> {
-    if parse_foreground_frame(input).is_ok() {
-        return Ok(());
-    }
-    if parse_background_frame(input).is_ok() {
-        return Ok(());
-    }
-    if parse_alp_command(input).is_ok() {
-        return Ok(());
-    }
-    if parse_serial(input).is_ok() {
-        return Ok(());
-    }
-    if parse_any_file(input).is_ok() {
-        return Ok(());
-    }
+fn parse_any_file(input: &[u8], _: Dialect, registry: &FileRegistry) -> Result<Parsed> {
+    let candidates = (0..=0x2Eu8)
+        .chain(registry.file_ids())
+        .map(|file_id| parse_file(input, file_id, registry))
+        // unknown files decode as raw bytes, which does not tell anything
+        .filter(|parsed| {
+            !matches!(
+                parsed,
+                Ok(Parsed {
+                    decoded: Decoded::Systemfile(File::Other(_)),
+                    ..
+                })
+            )
+        });
+
+    best_match(candidates).context("Could not parse file")
+}
+
+type Parser = fn(&[u8], Dialect, &FileRegistry) -> Result<Parsed>;
+
+fn parse_any(input: &[u8], dialect: Dialect, registry: &FileRegistry) -> Result<Parsed> {
+    // Serial frames are checked first: sync bytes and CRC make false positives unlikely
+    let parsers: [Parser; 5] = [
+        parse_serial,
+        parse_foreground_frame,
+        parse_background_frame,
+        parse_alp_command,
+        parse_any_file,
+    ];
+
+    best_match(parsers.iter().map(|parse| parse(input, dialect, registry)))
+        .context("Could not parse input")
+}
 
-    Err(DekuError::Parse("Could not parse input".into()))
+fn print(parsed: &Parsed, format: OutputFormat) {
+    match format {
+        OutputFormat::Debug => println!("{:?}", parsed),
+        OutputFormat::Pretty => println!("{}", parsed),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(parsed).expect("Could not serialize output")
+        ),
+        OutputFormat::Yaml => print!(
+            "{}",
+            serde_yaml::to_string(parsed).expect("Could not serialize output")
+        ),
+    }
 }
 
-pub fn main(args: ParseArgs) {
-    let input_vec = hex::decode(remove_whitespace(&args.hex)).expect("Could not parse input hex");
+fn run(args: ParseArgs, dialect: Dialect, registry: &FileRegistry) -> Result<()> {
+    let input_vec =
+        hex::decode(remove_whitespace(&args.hex)).context("Could not parse input hex")?;
     let input = input_vec.as_slice();
 
-    match args.parse_type {
-        Some(ParseType::Foreground) => {
-            parse_foreground_frame(input).expect("Could not foreground frame")
+    let parsed = match args.parse_type {
+        Some(ParseType::Foreground) => parse_foreground_frame(input, dialect, registry)
+            .context("Could not parse foreground frame")?,
+        Some(ParseType::Background) => parse_background_frame(input, dialect, registry)
+            .context("Could not parse background frame")?,
+        Some(ParseType::Alp) => {
+            parse_alp_command(input, dialect, registry).context("Could not parse command")?
         }
-        Some(ParseType::Background) => {
-            parse_background_frame(input).expect("Could not background frame")
+        Some(ParseType::Serial) => {
+            parse_serial(input, dialect, registry).context("Could not parse serial")?
         }
-        Some(ParseType::Alp) => parse_alp_command(input).expect("Could not parse command"),
-        Some(ParseType::Serial) => parse_serial(input).expect("Could not parse serial"),
         Some(ParseType::Systemfile) => {
-            if let Some(file_id_raw) = args.file_id {
-                let file_id: FileId = file_id_raw.try_into().expect("File id invalid");
-                parse_file(input, file_id).expect("Could not parse file")
+            if let Some(file_id) = args.file_id {
+                parse_file(input, file_id, registry).context("Could not parse file")?
             } else {
-                parse_any_file(input).expect("Could not parse file")
+                parse_any_file(input, dialect, registry)?
             }
         }
-        None => parse_any(input).expect("Could not parse input"),
+        None => parse_any(input, dialect, registry)?,
+    };
+
+    print(&parsed, args.format);
+    Ok(())
+}
+
+pub fn main(args: ParseArgs, dialect: Dialect) {
+    let registry = match args.schema.as_deref().map(Schema::load).transpose() {
+        Ok(schema) => schema.map(|schema| schema.registry()).unwrap_or_default(),
+        Err(err) => {
+            eprintln!("{:#}", err);
+            quit::with_code(1);
+        }
+    };
+    if let Err(err) = run(args, dialect, &registry) {
+        eprintln!("{:#}", err);
+        quit::with_code(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dash7::{
+        app::action::Action,
+        link::AccessClass,
+        network::{self, Address, NlsState},
+        transport,
+        types::VarInt,
+    };
+
+    use super::*;
+
+    const DIALECT: Dialect = Dialect::SpecV1_2;
+
+    #[test]
+    fn test_alp_json() {
+        let parsed = parse_alp_command(
+            &hex::decode("01000008").unwrap(),
+            DIALECT,
+            &FileRegistry::new(),
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(json["type"], "alp");
+        assert_eq!(json["trailing"], "");
+        assert_eq!(json["data"]["actions"][0]["ReadFileData"]["length"], 8);
+
+        assert_eq!(serde_json::from_value::<Parsed>(json).unwrap(), parsed);
+    }
+
+    #[test]
+    fn test_serial_trailing() {
+        for dialect in Dialect::ALL {
+            let mut input = SerialFrame::alp(1, Command::default(), dialect)
+                .to_bytes_with_dialect(dialect)
+                .unwrap();
+            input.extend_from_slice(&[0xAB, 0xCD]);
+
+            let parsed = parse_serial(&input, dialect, &FileRegistry::new()).unwrap();
+            assert!(matches!(parsed.decoded, Decoded::Serial(ref frames) if frames.len() == 1));
+            assert_eq!(parsed.trailing, "ABCD");
+            assert_eq!(parsed.to_bytes(dialect).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_any_prefers_complete() {
+        let input = hex::decode("0100000801000104").unwrap();
+        let parsed = parse_any(&input, DIALECT, &FileRegistry::new()).unwrap();
+        assert!(matches!(parsed.decoded, Decoded::Alp(_)));
+        assert!(parsed.trailing.is_empty());
+    }
+
+    #[test]
+    fn test_yaml() {
+        let parsed = parse_alp_command(
+            &hex::decode("01000008").unwrap(),
+            DIALECT,
+            &FileRegistry::new(),
+        )
+        .unwrap();
+        let yaml = serde_yaml::to_string(&parsed).unwrap();
+        assert!(yaml.starts_with("type: alp\n"));
+        assert!(yaml.contains("!ReadFileData"));
+    }
+
+    #[test]
+    fn test_schema_output() {
+        let schema = Schema::from_yaml(
+            r#"
+files:
+  - file_id: 0x40
+    name: sensor
+    fields:
+      - { name: temperature, size: 2, signed: true, scale: 0.01 }
+      - { name: state, values: { 0: idle, 1: measuring } }
+"#,
+        )
+        .unwrap();
+        let parsed = parse_alp_command(
+            &hex::decode("20400003FF3801").unwrap(),
+            DIALECT,
+            &schema.registry(),
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(
+            json["data"]["actions"][0]["ReturnFileData"]["data"]["Custom"]["value"],
+            serde_json::json!({"temperature": -2.0, "state": "measuring"})
+        );
+
+        let yaml = serde_yaml::to_string(&parsed).unwrap();
+        assert!(yaml.contains("value:\n"));
+        assert!(yaml.contains("temperature: -2.0\n"));
+        assert!(yaml.contains("state: measuring\n"));
+    }
+
+    #[test]
+    fn test_foreground_schema() {
+        let schema =
+            Schema::from_yaml("files:\n  - {file_id: 0x40, name: sensor, fields: [{name: state}]}")
+                .unwrap();
+        let transport = transport::Frame {
+            dialog_id: 0x11,
+            transaction_id: 0x01,
+            command: Command::try_from(&hex::decode("2040000101").unwrap()[..]).unwrap(),
+            ..Default::default()
+        };
+        let network = network::Frame::new(
+            AccessClass::new(0, 1),
+            Address::NoId,
+            NlsState::None,
+            transport,
+        );
+        let input = ForegroundFrame::new(0x05, 0x10, Address::NoId, network)
+            .to_bytes()
+            .unwrap();
+
+        let parsed = parse_foreground_frame(&input, DIALECT, &schema.registry()).unwrap();
+        let Decoded::Foreground(frames) = &parsed.decoded else {
+            panic!("expected foreground frames");
+        };
+        let [frame] = frames.as_slice() else {
+            panic!("expected a single frame");
+        };
+        let Action::ReturnFileData(file_data) = &frame.frame().frame().command.actions[0] else {
+            panic!("expected file data");
+        };
+        assert!(matches!(file_data.data(), File::Custom(_)));
+        assert_eq!(parsed.to_bytes(DIALECT).unwrap(), input);
+    }
+
+    #[test]
+    fn test_foreground_dialog() {
+        let read = Command::builder().read_file(0x00, 0, 8).build().unwrap();
+        // requests with a Tc, the second one without being a dialog start
+        let mut input = vec![];
+        for (is_dialog_start, transaction_id) in [(true, 0x01), (false, 0x02)] {
+            let transport = transport::Frame {
+                control: transport::Control {
+                    is_dialog_start,
+                    is_ack_requested: true,
+                    ..Default::default()
+                },
+                dialog_id: 0x11,
+                transaction_id,
+                congestion_timeout: Some(VarInt::new(5, false).unwrap()),
+                command: read.clone(),
+                ..Default::default()
+            };
+            let network = network::Frame::new(
+                AccessClass::new(0, 1),
+                Address::NoId,
+                NlsState::None,
+                transport,
+            );
+            let frame = ForegroundFrame::new(0x05, 0x10, Address::NoId, network);
+            input.extend(frame.to_bytes().unwrap());
+        }
+
+        let parsed = parse_foreground_frame(&input, DIALECT, &FileRegistry::new()).unwrap();
+        let Decoded::Foreground(frames) = &parsed.decoded else {
+            panic!("expected foreground frames");
+        };
+        assert_eq!(frames.len(), 2);
+        assert!(parsed.trailing.is_empty());
+        assert_eq!(parsed.to_bytes(DIALECT).unwrap(), input);
+    }
+
+    #[test]
+    fn test_dialect() {
+        // wizzilab addressee: group condition and use_vid in the control byte
+        let input = hex::decode("32D70000009800").unwrap();
+
+        let parsed =
+            parse_alp_command(&input, Dialect::WizzilabV5_3, &FileRegistry::new()).unwrap();
+        assert!(parsed.trailing.is_empty());
+        assert_eq!(parsed.to_bytes(Dialect::WizzilabV5_3).unwrap(), input);
+
+        // the same control byte has the RFU bit set for the spec
+        assert!(parse_alp_command(&input, Dialect::SpecV1_2, &FileRegistry::new()).is_err());
+    }
+
+    #[test]
+    fn test_error_location() {
+        // write file data to file 0x40, without its length
+        let input = hex::decode("01000008044000").unwrap();
+        let err = parse_alp_command(&input, DIALECT, &FileRegistry::new()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("at byte 7 in action 1 (opcode 0x04), file 0x40"));
     }
 }
@@ -0,0 +1,536 @@
+//! Declarative layouts of application files, so they can be decoded without writing Rust
+//!
+//! A schema lists files by id, each with a sequence of named fields:
+//!
+//! ```yaml
+//! files:
+//!   - file_id: 0x40
+//!     name: sensor
+//!     fields:
+//!       - name: temperature
+//!         size: 2
+//!         signed: true
+//!         scale: 0.01
+//!       - name: state
+//!         values: { 0: idle, 1: measuring }
+//!       - name: flags
+//!         bits:
+//!           - { name: alarm, bits: 1 }
+//!           - { name: battery, bits: 3 }
+//!       - name: label
+//!         type: string
+//!         size: 8
+//! ```
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use dash7::file::{CustomFile, FileDecoder, FileRegistry, Serializable};
+use deku::{DekuError, DekuWrite};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    #[default]
+    Int,
+    String,
+}
+
+/// Part of an integer field, most significant bits first
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BitField {
+    name: String,
+    bits: u32,
+
+    /// Names of the values, keyed on the value
+    #[serde(default)]
+    values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Field {
+    name: String,
+
+    #[serde(rename = "type", default)]
+    field_type: FieldType,
+
+    /// Size in bytes. Integers default to a single byte.
+    size: Option<usize>,
+
+    #[serde(default)]
+    signed: bool,
+
+    #[serde(default)]
+    endian: Endian,
+
+    /// The value is `raw * scale + offset`
+    scale: Option<f64>,
+    offset: Option<f64>,
+
+    /// Names of the values, keyed on the value
+    #[serde(default)]
+    values: BTreeMap<String, String>,
+
+    #[serde(default)]
+    bits: Vec<BitField>,
+}
+
+/// Layout of a single application file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSchema {
+    file_id: u8,
+    name: String,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    files: Vec<FileSchema>,
+}
+
+fn parse_int(value: &str) -> Result<i128> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let parsed = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .with_context(|| format!("Invalid value {:?}", value))?;
+    Ok(if negative { -parsed } else { parsed })
+}
+
+/// Checks the keys of `values` are integers, and resolves them
+fn parse_values(values: &BTreeMap<String, String>) -> Result<BTreeMap<i128, String>> {
+    values
+        .iter()
+        .map(|(key, name)| Ok((parse_int(key)?, name.clone())))
+        .collect()
+}
+
+impl Field {
+    fn size(&self) -> usize {
+        self.size.unwrap_or(1)
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.field_type {
+            FieldType::Int => {
+                ensure!(
+                    (1..=8).contains(&self.size()),
+                    "integers are 1 to 8 bytes, not {}",
+                    self.size()
+                );
+                let bits: u32 = self.bits.iter().map(|field| field.bits).sum();
+                ensure!(
+                    bits as usize <= self.size() * 8,
+                    "bit fields take {} bits, but there are only {}",
+                    bits,
+                    self.size() * 8
+                );
+                ensure!(
+                    self.bits.iter().all(|field| field.bits > 0),
+                    "bit fields take at least a bit"
+                );
+                for field in &self.bits {
+                    parse_values(&field.values)
+                        .with_context(|| format!("In bit field {}", field.name))?;
+                }
+                parse_values(&self.values)?;
+            }
+            FieldType::String => {
+                ensure!(self.size.is_some(), "strings need a size");
+                ensure!(
+                    self.scale.is_none()
+                        && self.offset.is_none()
+                        && self.values.is_empty()
+                        && self.bits.is_empty(),
+                    "strings have no scale, offset, values or bits"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn integer(&self, data: &[u8]) -> i128 {
+        let mut bytes = data.to_vec();
+        if self.endian == Endian::Little {
+            bytes.reverse();
+        }
+        let raw = bytes
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+
+        let width = data.len() as u32 * 8;
+        if self.signed && width < 64 && raw >> (width - 1) & 1 == 1 {
+            i128::from(raw) - (1i128 << width)
+        } else if self.signed {
+            i128::from(raw as i64)
+        } else {
+            i128::from(raw)
+        }
+    }
+
+    fn decode(&self, data: &[u8]) -> Value {
+        match self.field_type {
+            FieldType::String => {
+                let end = data
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(data.len());
+                Value::String(String::from_utf8_lossy(&data[..end]).into_owned())
+            }
+            FieldType::Int if !self.bits.is_empty() => {
+                let raw = self.integer(data) as u64;
+                let mut shift = data.len() as u32 * 8;
+                let fields = self
+                    .bits
+                    .iter()
+                    .map(|field| {
+                        shift -= field.bits;
+                        let mask = u64::MAX >> (64 - field.bits);
+                        let value = i128::from((raw >> shift) & mask);
+                        (field.name.clone(), named(value, &field.values))
+                    })
+                    .collect();
+                Value::Bits(fields)
+            }
+            FieldType::Int => {
+                let value = self.integer(data);
+                match (self.scale, self.offset) {
+                    _ if !self.values.is_empty() => named(value, &self.values),
+                    (None, None) => Value::Int(value),
+                    (scale, offset) => {
+                        Value::Float(value as f64 * scale.unwrap_or(1.0) + offset.unwrap_or(0.0))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn named(value: i128, values: &BTreeMap<String, String>) -> Value {
+    let values = parse_values(values).unwrap_or_default();
+    match values.get(&value) {
+        Some(name) => Value::Name(name.clone()),
+        None => Value::Int(value),
+    }
+}
+
+/// A decoded field
+#[derive(Clone, PartialEq)]
+enum Value {
+    Int(i128),
+    Float(f64),
+    /// Name of an enumerated value
+    Name(String),
+    String(String),
+    Bits(Vec<(String, Value)>),
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{:?}", value),
+            Self::Name(name) => write!(f, "{}", name),
+            Self::String(value) => write!(f, "{:?}", value),
+            Self::Bits(fields) => {
+                let mut map = f.debug_map();
+                for (name, value) in fields {
+                    map.key(&format_args!("{}", name)).value(value);
+                }
+                map.finish()
+            }
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Int(value) => serializer.serialize_i128(*value),
+            Self::Float(value) => serializer.serialize_f64(*value),
+            Self::Name(value) | Self::String(value) => serializer.serialize_str(value),
+            Self::Bits(fields) => serialize_fields(fields, serializer),
+        }
+    }
+}
+
+/// Serializes named values as a map, in order
+fn serialize_fields<S: Serializer>(
+    fields: &[(String, Value)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(fields.len()))?;
+    for (name, value) in fields {
+        map.serialize_entry(name, value)?;
+    }
+    map.end()
+}
+
+/// A file decoded with a [`FileSchema`]. It encodes back to the bytes it was decoded from.
+#[derive(DekuWrite, Clone, PartialEq)]
+struct Record {
+    #[deku(skip)]
+    name: String,
+
+    #[deku(skip)]
+    fields: Vec<(String, Value)>,
+
+    raw: Vec<u8>,
+}
+
+/// Serialized as a map of the field names to their values
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_fields(&self.fields, serializer)
+    }
+}
+
+impl fmt::Debug for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut record = f.debug_struct(&self.name);
+        for (name, value) in &self.fields {
+            record.field(name, value);
+        }
+        record.finish()
+    }
+}
+
+impl FileSchema {
+    fn check(&self) -> Result<()> {
+        ensure!(
+            self.file_id >= 0x40,
+            "file ids below 0x40 are reserved for system files"
+        );
+        for field in &self.fields {
+            field
+                .check()
+                .with_context(|| format!("In field {}", field.name))?;
+        }
+        Ok(())
+    }
+
+    /// Size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.fields.iter().map(Field::size).sum()
+    }
+}
+
+impl FileDecoder for FileSchema {
+    fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomFile>, DekuError> {
+        if data.len() != self.size() {
+            return Err(DekuError::Parse(
+                format!(
+                    "{} is {} bytes long, not {}",
+                    self.name,
+                    self.size(),
+                    data.len()
+                )
+                .into(),
+            ));
+        }
+
+        let mut start = 0;
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| {
+                let data = &data[start..start + field.size()];
+                start += field.size();
+                (field.name.clone(), field.decode(data))
+            })
+            .collect();
+        Ok(Box::new(Serializable(Record {
+            name: self.name.clone(),
+            fields,
+            raw: data.to_vec(),
+        })))
+    }
+}
+
+impl Schema {
+    /// Loads a TOML or YAML schema, depending on its extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read schema {}", path.display()))?;
+        let schema = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            _ => bail!("Schemas are .toml or .yaml files"),
+        };
+        schema.with_context(|| format!("Invalid schema {}", path.display()))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let schema: Self = toml::from_str(content)?;
+        schema.check()?;
+        Ok(schema)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let schema: Self = serde_yaml::from_str(content)?;
+        schema.check()?;
+        Ok(schema)
+    }
+
+    fn check(&self) -> Result<()> {
+        for (idx, file) in self.files.iter().enumerate() {
+            if self.files[..idx]
+                .iter()
+                .any(|other| other.file_id == file.file_id)
+            {
+                bail!("File {:#04X} is described twice", file.file_id);
+            }
+            file.check()
+                .with_context(|| format!("In file {}", file.name))?;
+        }
+        Ok(())
+    }
+
+    /// Registry that decodes the files of this schema
+    pub fn registry(&self) -> FileRegistry {
+        let mut registry = FileRegistry::new();
+        for file in &self.files {
+            registry.register_decoder(file.file_id, file.clone());
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dash7::{
+        app::{action::Action, command::Command},
+        file::File,
+    };
+
+    use super::*;
+
+    const YAML: &str = r#"
+files:
+  - file_id: 0x40
+    name: sensor
+    fields:
+      - name: temperature
+        size: 2
+        signed: true
+        scale: 0.01
+      - name: counter
+        size: 2
+        endian: little
+      - name: state
+        values: { 0: idle, 1: measuring }
+      - name: flags
+        bits:
+          - { name: alarm, bits: 1, values: { 1: raised } }
+          - { name: battery, bits: 3 }
+      - name: label
+        type: string
+        size: 4
+"#;
+
+    const TOML: &str = r#"
+[[files]]
+file_id = 0x40
+name = "sensor"
+
+[[files.fields]]
+name = "temperature"
+size = 2
+signed = true
+scale = 0.01
+
+[[files.fields]]
+name = "counter"
+size = 2
+endian = "little"
+
+[[files.fields]]
+name = "state"
+values = { "0" = "idle", "1" = "measuring" }
+
+[[files.fields]]
+name = "flags"
+bits = [
+    { name = "alarm", bits = 1, values = { "1" = "raised" } },
+    { name = "battery", bits = 3 },
+]
+
+[[files.fields]]
+name = "label"
+type = "string"
+size = 4
+"#;
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            Schema::from_yaml(YAML).unwrap(),
+            Schema::from_toml(TOML).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let registry = Schema::from_yaml(YAML).unwrap().registry();
+        let data = hex::decode("FF38030001B06F6B0000").unwrap();
+
+        let mut command = Command::try_from(
+            &hex::decode(format!("204000{:02X}{}", data.len(), hex::encode(&data))).unwrap()[..],
+        )
+        .unwrap();
+        command.resolve_files(&registry).unwrap();
+
+        let Action::ReturnFileData(file_data) = &command.actions[0] else {
+            panic!("expected file data");
+        };
+        let File::Custom(custom) = file_data.data() else {
+            panic!("expected a custom file, got {:?}", file_data.data());
+        };
+        assert_eq!(
+            format!("{:?}", custom.value()),
+            r#"sensor { temperature: -2.0, counter: 3, state: measuring, flags: {alarm: raised, battery: 3}, label: "ok" }"#
+        );
+        assert_eq!(custom.to_bytes().unwrap(), data);
+
+        // data that does not fit the schema is an error
+        let mut command = Command::try_from(&hex::decode("204000020102").unwrap()[..]).unwrap();
+        let err = command.resolve_files(&registry).unwrap_err();
+        assert!(err.to_string().contains("sensor is 10 bytes long, not 2"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let schema = |fields: &str| {
+            Schema::from_yaml(&format!(
+                "files:\n  - file_id: 0x40\n    name: test\n    fields: [{}]",
+                fields
+            ))
+        };
+        assert!(schema("{name: a, size: 9}").is_err());
+        assert!(schema("{name: a, type: string}").is_err());
+        assert!(schema("{name: a, bits: [{name: b, bits: 9}]}").is_err());
+        assert!(schema("{name: a, values: {x: y}}").is_err());
+        assert!(schema("{name: a, unknown: 1}").is_err());
+        assert!(schema("{name: a, size: 8, signed: true}").is_ok());
+
+        assert!(Schema::from_yaml("files:\n  - {file_id: 0x01, name: a, fields: []}").is_err());
+    }
+}
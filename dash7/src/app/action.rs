@@ -1,87 +1,31 @@
-use std::borrow::Cow;
-#[cfg(feature = "std")]
-use std::fmt;
-
-#[cfg(not(feature = "std"))]
-use alloc::fmt;
+use alloc::vec::Vec;
 
 use deku::{
-    no_std_io::{self, Seek, Write},
+    no_std_io::{self, Seek, SeekFrom},
     prelude::*,
 };
 
-use crate::utils::{from_bytes, from_reader};
+use crate::{
+    dialect::Dialect,
+    error::{Error, Layer, Location},
+    utils::{decode, from_bytes, from_reader, peek_byte},
+};
+
+pub use super::opcode::OpCode;
 
+use super::interface_final::*;
 use super::operation::{
-    ActionQuery, Chunk, CopyFile, Extension, FileData, FileId, FileProperties, Forward,
-    IndirectForward, Logic, Nop, PermissionRequest, ReadFileData, RequestTag, ResponseTag,
+    ActionHeader, ActionQuery, Chunk, CopyFile, Extension, FileData, FileId, FileProperties,
+    Forward, IndirectForward, Logic, Nop, PermissionRequest, ReadFileData, RequestTag, ResponseTag,
     StatusOperand,
 };
 
-#[cfg(feature = "_wizzilab")]
-use super::interface_final::*;
-
-// ===============================================================================
-// OpCodes
-// ===============================================================================
-
-#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct OpCode(#[deku(bits = 6)] pub u8);
-
-impl OpCode {
-    pub const NOP: OpCode = OpCode(0u8);
-
-    // Read
-    pub const READ_FILE_DATA: OpCode = OpCode(0x01u8);
-    pub const READ_FILE_PROPERTIES: OpCode = OpCode(0x02u8);
-
-    // Write
-    pub const WRITE_FILE_DATA: OpCode = OpCode(0x04u8);
-    pub const WRITE_FILE_DATA_FLUSH: OpCode = OpCode(0x05u8);
-    pub const WRITE_FILE_PROPERTIES: OpCode = OpCode(0x06u8);
-    pub const ACTION_QUERY: OpCode = OpCode(0x08u8);
-    pub const BREAK_QUERY: OpCode = OpCode(0x09u8);
-    pub const PERMISSION_REQUEST: OpCode = OpCode(0x0au8);
-    pub const VERIFY_CHECKSUM: OpCode = OpCode(0x0bu8);
-
-    // Management
-    pub const EXIST_FILE: OpCode = OpCode(16u8);
-    pub const CREATE_NEW_FILE: OpCode = OpCode(17u8);
-    pub const DELETE_FILE: OpCode = OpCode(18u8);
-    pub const RESTORE_FILE: OpCode = OpCode(19u8);
-    pub const FLUSH_FILE: OpCode = OpCode(20u8);
-    pub const COPY_FILE: OpCode = OpCode(23u8);
-    pub const EXECUTE_FILE: OpCode = OpCode(31u8);
-
-    // Response
-    pub const RETURN_FILE_DATA: OpCode = OpCode(32u8);
-    pub const RETURN_FILE_PROPERTIES: OpCode = OpCode(33u8);
-    pub const STATUS: OpCode = OpCode(34u8);
-    pub const RESPONSE_TAG: OpCode = OpCode(35u8);
-
-    #[cfg(feature = "_wizzilab")]
-    pub const TX_STATUS: OpCode = OpCode(38u8);
-
-    // Special
-    pub const CHUNK: OpCode = OpCode(48u8);
-    pub const LOGIC: OpCode = OpCode(49u8);
-    pub const FORWARD: OpCode = OpCode(50u8);
-    pub const INDIRECT_FORWARD: OpCode = OpCode(51u8);
-    pub const REQUEST_TAG: OpCode = OpCode(52u8);
-    pub const EXTENSION: OpCode = OpCode(63u8);
-}
-
-impl OpCode {
-    pub fn write<W: Write + Seek>(writer: &mut Writer<W>, opcode: OpCode) -> Result<(), DekuError> {
-        opcode.to_writer(writer, ())
-    }
-}
-
 // ===============================================================================
 // Actions
 // ===============================================================================
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     /// Nop
     Nop(Nop),
@@ -125,7 +69,7 @@ pub enum Action {
     Status(StatusOperand),
     ResponseTag(ResponseTag),
 
-    #[cfg(feature = "_wizzilab")]
+    /// Wizzilab only
     TxStatus(TxStatusOperation),
 
     // Special
@@ -150,14 +94,23 @@ pub enum Action {
 // }
 
 macro_rules! read_action {
-    ($action: ident, $operation: ty, $reader: ident, $opcode: ident) => {{
-        <$operation as DekuReader<'_, _>>::from_reader_with_ctx($reader, $opcode)
+    ($action: ident, $operation: ty, $reader: ident, $ctx: expr) => {{
+        <$operation as DekuReader<'_, _>>::from_reader_with_ctx($reader, $ctx)
             .map(|action| Self::$action(action))?
     }};
 }
 
 impl<'a> DekuReader<'a, ()> for Action {
     fn from_reader_with_ctx<R>(reader: &mut Reader<R>, _: ()) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        Self::from_reader_with_ctx(reader, Dialect::default())
+    }
+}
+
+impl<'a> DekuReader<'a, Dialect> for Action {
+    fn from_reader_with_ctx<R>(reader: &mut Reader<R>, dialect: Dialect) -> Result<Self, DekuError>
     where
         R: no_std_io::Read + no_std_io::Seek,
     {
@@ -168,7 +121,7 @@ impl<'a> DekuReader<'a, ()> for Action {
         let code = <OpCode as DekuReader<'_, _>>::from_reader_with_ctx(reader, ())?;
 
         // seek back to the beginning (1 byte)
-        let _ = reader.seek_relative(-1); // TODO process err
+        let _ = reader.seek(SeekFrom::Current(-1)); // TODO process err
 
         // // Read the preamble and pass it on as context values
         // let preamble = (<u8 as DekuReader<'_, _>>::from_reader_with_ctx(
@@ -222,16 +175,16 @@ impl<'a> DekuReader<'a, ()> for Action {
                 read_action!(ReturnFileProperties, FileProperties, reader, code)
             }
             OpCode::RESPONSE_TAG => read_action!(ResponseTag, ResponseTag, reader, code),
-
-            #[cfg(feature = "_wizzilab")]
-            OpCode::TX_STATUS => read_action!(TxStatus, TxStatusOperation, reader, code),
+            OpCode::TX_STATUS if dialect.is_wizzilab() => {
+                read_action!(TxStatus, TxStatusOperation, reader, (code, dialect))
+            }
             OpCode::CHUNK => read_action!(Chunk, Chunk, reader, code),
             OpCode::LOGIC => read_action!(Logic, Logic, reader, code),
             OpCode::REQUEST_TAG => read_action!(RequestTag, RequestTag, reader, code),
-            OpCode::STATUS => read_action!(Status, StatusOperand, reader, code),
-            OpCode::FORWARD => read_action!(Forward, Forward, reader, code),
+            OpCode::STATUS => read_action!(Status, StatusOperand, reader, (code, dialect)),
+            OpCode::FORWARD => read_action!(Forward, Forward, reader, (code, dialect)),
             OpCode::INDIRECT_FORWARD => {
-                read_action!(IndirectForward, IndirectForward, reader, code)
+                read_action!(IndirectForward, IndirectForward, reader, (code, dialect))
             }
             OpCode::EXTENSION => read_action!(Extension, Extension, reader, code),
             _ => return Err(DekuError::InvalidParam("opcode".into())),
@@ -241,21 +194,21 @@ impl<'a> DekuReader<'a, ()> for Action {
 }
 
 impl TryFrom<&'_ [u8]> for Action {
-    type Error = DekuError;
+    type Error = Error;
     fn try_from(input: &'_ [u8]) -> Result<Self, Self::Error> {
-        let (rest, res) = <Self as DekuContainerRead>::from_bytes((input, 0))?;
+        let (rest, res) = Self::from_bytes_with_dialect((input, 0), Dialect::default())?;
         if !rest.0.is_empty() {
-            return Err(DekuError::Parse({
-                let res = fmt::format(format_args!("Too much data"));
-                Cow::Owned(res)
-            }));
+            return Err(Error::TooMuchData {
+                location: Location::new((input.len() - rest.0.len()) * 8),
+                remaining: rest.0.len(),
+            });
         }
         Ok(res)
     }
 }
 
 impl DekuContainerRead<'_> for Action {
-    fn from_reader<'a, R>(input: (&'a mut R, usize)) -> Result<(usize, Self), DekuError>
+    fn from_reader<R>(input: (&mut R, usize)) -> Result<(usize, Self), DekuError>
     where
         R: no_std_io::Read + no_std_io::Seek,
     {
@@ -290,7 +243,6 @@ impl DekuEnumExt<'_, OpCode> for Action {
             Action::ReturnFileData(_) => Ok(OpCode::RETURN_FILE_DATA),
             Action::ReturnFileProperties(_) => Ok(OpCode::RETURN_FILE_PROPERTIES),
             Action::ResponseTag(_) => Ok(OpCode::RESPONSE_TAG),
-            #[cfg(feature = "_wizzilab")]
             Action::TxStatus(_) => Ok(OpCode::TX_STATUS),
             Action::Chunk(_) => Ok(OpCode::CHUNK),
             Action::Logic(_) => Ok(OpCode::LOGIC),
@@ -311,6 +263,15 @@ impl DekuUpdate for Action {
 
 impl DekuWriter<()> for Action {
     fn to_writer<W>(&self, writer: &mut Writer<W>, _: ()) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        self.to_writer(writer, Dialect::default())
+    }
+}
+
+impl DekuWriter<Dialect> for Action {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, dialect: Dialect) -> Result<(), DekuError>
     where
         W: no_std_io::Write + no_std_io::Seek,
     {
@@ -337,13 +298,19 @@ impl DekuWriter<()> for Action {
             Action::ReturnFileData(action) => action.to_writer(writer, opcode)?,
             Action::ReturnFileProperties(action) => action.to_writer(writer, opcode)?,
             Action::ResponseTag(action) => action.to_writer(writer, opcode)?,
-            #[cfg(feature = "_wizzilab")]
-            Action::TxStatus(action) => action.to_writer(writer, opcode)?,
+            Action::TxStatus(action) if dialect.is_wizzilab() => {
+                action.to_writer(writer, (opcode, dialect))?
+            }
+            Action::TxStatus(_) => {
+                return Err(DekuError::InvalidParam(
+                    "TxStatus is only supported by Wizzilab".into(),
+                ))
+            }
             Action::Chunk(action) => action.to_writer(writer, opcode)?,
             Action::Logic(action) => action.to_writer(writer, opcode)?,
-            Action::Status(action) => action.to_writer(writer, opcode)?,
-            Action::Forward(action) => action.to_writer(writer, opcode)?,
-            Action::IndirectForward(action) => action.to_writer(writer, opcode)?,
+            Action::Status(action) => action.to_writer(writer, (opcode, dialect))?,
+            Action::Forward(action) => action.to_writer(writer, (opcode, dialect))?,
+            Action::IndirectForward(action) => action.to_writer(writer, (opcode, dialect))?,
             Action::RequestTag(action) => action.to_writer(writer, opcode)?,
             Action::Extension(action) => action.to_writer(writer, opcode)?,
         }
@@ -360,13 +327,150 @@ impl TryFrom<Action> for Vec<u8> {
 }
 impl DekuContainerWrite for Action {}
 
+impl Action {
+    pub fn opcode(&self) -> OpCode {
+        self.deku_id().unwrap_or_default()
+    }
+
+    /// Whether the action is a query, which decides on the execution of other actions
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Action::ActionQuery(_) | Action::BreakQuery(_) | Action::VerifyChecksum(_)
+        )
+    }
+
+    /// Header of the action, if it has one. Only actions with a header can be grouped.
+    pub fn header(&self) -> Option<&ActionHeader> {
+        match self {
+            Action::Nop(action) => Some(&action.header),
+            Action::ReadFileData(action) => Some(&action.header),
+            Action::ReadFileProperties(action)
+            | Action::ExistFile(action)
+            | Action::DeleteFile(action)
+            | Action::RestoreFile(action)
+            | Action::FlushFile(action)
+            | Action::ExecuteFile(action) => Some(&action.header),
+            Action::WriteFileData(action)
+            | Action::WriteFileDataFlush(action)
+            | Action::ReturnFileData(action) => Some(&action.header),
+            Action::WriteFileProperties(action)
+            | Action::CreateNewFile(action)
+            | Action::ReturnFileProperties(action) => Some(&action.header),
+            Action::ActionQuery(action)
+            | Action::BreakQuery(action)
+            | Action::VerifyChecksum(action) => Some(&action.header),
+            Action::PermissionRequest(action) => Some(&action.header),
+            Action::CopyFile(action) => Some(&action.header),
+            Action::Extension(action) => Some(&action.header),
+            _ => None,
+        }
+    }
+
+    pub fn header_mut(&mut self) -> Option<&mut ActionHeader> {
+        match self {
+            Action::Nop(action) => Some(&mut action.header),
+            Action::ReadFileData(action) => Some(&mut action.header),
+            Action::ReadFileProperties(action)
+            | Action::ExistFile(action)
+            | Action::DeleteFile(action)
+            | Action::RestoreFile(action)
+            | Action::FlushFile(action)
+            | Action::ExecuteFile(action) => Some(&mut action.header),
+            Action::WriteFileData(action)
+            | Action::WriteFileDataFlush(action)
+            | Action::ReturnFileData(action) => Some(&mut action.header),
+            Action::WriteFileProperties(action)
+            | Action::CreateNewFile(action)
+            | Action::ReturnFileProperties(action) => Some(&mut action.header),
+            Action::ActionQuery(action)
+            | Action::BreakQuery(action)
+            | Action::VerifyChecksum(action) => Some(&mut action.header),
+            Action::PermissionRequest(action) => Some(&mut action.header),
+            Action::CopyFile(action) => Some(&mut action.header),
+            Action::Extension(action) => Some(&mut action.header),
+            _ => None,
+        }
+    }
+
+    pub fn from_bytes_with_dialect(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        decode(input, dialect).map_err(|err| Self::locate_error(err, input.0, input.1, 0, dialect))
+    }
+
+    /// Adds the context of the action at `bit_offset` in `data`, the `index`th of its command, to `err`
+    pub(crate) fn locate_error(
+        err: Error,
+        data: &[u8],
+        bit_offset: usize,
+        index: usize,
+        dialect: Dialect,
+    ) -> Error {
+        let Some(byte) = peek_byte(data, bit_offset) else {
+            return err;
+        };
+        let opcode = OpCode(byte & 0x3F);
+
+        let err = match err {
+            Error::Deku {
+                source: DekuError::InvalidParam(ref param),
+                ..
+            } if param == "opcode" => Error::InvalidValue {
+                location: Location::new(bit_offset + 2),
+                field: "opcode",
+                expected: format!("an opcode supported by {:?}", dialect).into(),
+                found: opcode.0 as u64,
+            },
+            err => err,
+        };
+
+        let mut layers = vec![Layer::Action {
+            index,
+            opcode: opcode.0,
+        }];
+        if let Some(operand) = peek_byte(data, bit_offset + 8) {
+            match opcode {
+                OpCode::READ_FILE_DATA
+                | OpCode::READ_FILE_PROPERTIES
+                | OpCode::WRITE_FILE_DATA
+                | OpCode::WRITE_FILE_DATA_FLUSH
+                | OpCode::WRITE_FILE_PROPERTIES
+                | OpCode::EXIST_FILE
+                | OpCode::CREATE_NEW_FILE
+                | OpCode::DELETE_FILE
+                | OpCode::RESTORE_FILE
+                | OpCode::FLUSH_FILE
+                | OpCode::EXECUTE_FILE
+                | OpCode::RETURN_FILE_DATA
+                | OpCode::RETURN_FILE_PROPERTIES
+                | OpCode::INDIRECT_FORWARD => layers.push(Layer::File(operand)),
+                OpCode::STATUS if byte >> 6 != 0 => layers.push(Layer::Interface(operand)),
+                OpCode::TX_STATUS | OpCode::FORWARD => layers.push(Layer::Interface(operand)),
+                _ => {}
+            }
+        }
+
+        // the error offset is relative to the start of the input, so only the layers are added
+        err.nested(0, &layers)
+    }
+
+    pub fn to_bytes_with_dialect(&self, dialect: Dialect) -> Result<Vec<u8>, DekuError> {
+        let mut out_buf = Vec::new();
+        let mut cursor = no_std_io::Cursor::new(&mut out_buf);
+        let mut writer = Writer::new(&mut cursor);
+        self.to_writer(&mut writer, dialect)?;
+        writer.finalize()?;
+        Ok(out_buf)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use hex_literal::hex;
 
     use super::*;
-    #[cfg(feature = "_wizzilab")]
-    use crate::transport::GroupCondition;
     use crate::{
         app::{
             interface::{IndirectInterface, InterfaceConfiguration},
@@ -381,8 +485,11 @@ mod test {
         link::AccessClass,
         network::{Address, Addressee, NlsState},
         physical::{Channel, ChannelBand, ChannelClass, ChannelCoding, ChannelHeader},
-        session::{Dash7InterfaceStatus, InterfaceStatus},
-        test_tools::{test_item, WithPadding},
+        session::{
+            Dash7InterfaceStatus, Dash7InterfaceTxStatus, InterfaceFinalStatusCode,
+            InterfaceStatus, InterfaceTxStatus,
+        },
+        test_tools::{test_item, test_item_ctx, WithPadding},
     };
 
     #[test]
@@ -712,6 +819,45 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_return_partial_file_data() {
+        let return_file_data = |file_id, offset: u32, data: &[u8]| {
+            Action::ReturnFileData(FileData::new(
+                ActionHeader {
+                    group: false,
+                    response: false,
+                },
+                FileOffset {
+                    file_id,
+                    offset: offset.into(),
+                },
+                File::Other(data.to_vec()),
+                OpCode::RETURN_FILE_DATA,
+            ))
+        };
+
+        // Part of the UID file
+        test_item(
+            return_file_data(0x00, 0, &hex!("01020304")),
+            &hex!("20 00 00 04 01020304"),
+        );
+        // The end of the UID file
+        test_item(
+            return_file_data(0x00, 4, &hex!("05060708")),
+            &hex!("20 00 04 04 05060708"),
+        );
+        // More than the UID file
+        test_item(
+            return_file_data(0x00, 0, &hex!("010203040506070809")),
+            &hex!("20 00 00 09 010203040506070809"),
+        );
+        // Part of the root key, read from an offset
+        test_item(
+            return_file_data(0x18, 12, &hex!("01020304")),
+            &hex!("20 18 0C 04 01020304"),
+        );
+    }
+
     #[test]
     fn test_action_query() {
         test_item(
@@ -798,10 +944,6 @@ mod test {
         let item = Action::IndirectForward(IndirectForward::new(
             true,
             Some(IndirectInterface::Dash7(Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::VId(0xABCD),
                 NlsState::AesCcm32([1, 2, 3, 4, 5]),
                 AccessClass::unavailable(),
@@ -820,10 +962,6 @@ mod test {
         let expected = Action::IndirectForward(IndirectForward::new(
             true,
             Some(IndirectInterface::Dash7(Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::VId(0xABCD),
                 NlsState::AesCcm32([1, 2, 3, 4, 5]),
                 AccessClass::unavailable(),
@@ -932,10 +1070,6 @@ mod test {
                     sequence_number: 0,
                     response_timeout: 384.into(),
                     addressee: Addressee::new(
-                        #[cfg(feature = "_wizzilab")]
-                        false,
-                        #[cfg(feature = "_wizzilab")]
-                        GroupCondition::Any,
                         Address::UId(4123107267735781422u64),
                         NlsState::None,
                         AccessClass::new(0, 1),
@@ -946,6 +1080,60 @@ mod test {
             .into(),
         );
 
-        test_item(item, data);
+        test_item_ctx(item, data, Dialect::SpecV1_2);
+    }
+
+    #[test]
+    fn test_tx_status() {
+        let data = &hex!("66 D7 15 01 0123 02 FF 00 00 00 0000 0807 36 FF 0011 0000000000");
+
+        let item = Action::TxStatus(
+            TxStatus::from(InterfaceTxStatusOperation::from(InterfaceTxStatus::Dash7(
+                Dash7InterfaceTxStatus {
+                    channel: Channel {
+                        header: ChannelHeader::new(
+                            ChannelBand::Rfu0,
+                            ChannelClass::LoRate,
+                            ChannelCoding::Rfu,
+                        ),
+                        index: 0x0123,
+                    },
+                    target_rx_level: 2,
+                    error: InterfaceFinalStatusCode::Busy,
+                    lts: 0x0708_0000,
+                    addressee: Addressee::new(
+                        Address::VId(0x0011),
+                        NlsState::AesCcm64([0; 5]),
+                        AccessClass::new(0x0F, 0x0F),
+                    ),
+                },
+            )))
+            .into(),
+        );
+
+        test_item_ctx(item.clone(), data, Dialect::WizzilabV5_3);
+
+        // TX status is a Wizzilab extension
+        let err = Action::from_bytes_with_dialect((data, 0), Dialect::SpecV1_2).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidValue {
+                field: "opcode",
+                found: 0x26,
+                ..
+            }
+        ));
+        assert_eq!(err.bit_offset(), 2);
+        assert_eq!(
+            err.layers(),
+            &[
+                Layer::Action {
+                    index: 0,
+                    opcode: 0x26
+                },
+                Layer::Interface(0xD7)
+            ]
+        );
+        assert!(item.to_bytes_with_dialect(Dialect::SubIoTV0_1).is_err());
     }
 }
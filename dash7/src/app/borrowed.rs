@@ -0,0 +1,1238 @@
+use core::fmt;
+
+use super::opcode::OpCode;
+use crate::dialect::Dialect;
+
+const INTERFACE_LORAWAN_ABP: u8 = 0x02;
+const INTERFACE_LORAWAN_OTAA: u8 = 0x03;
+const INTERFACE_DASH7: u8 = 0xD7;
+
+/// Size of the configuration of the LoRaWAN ABP interface
+const LORAWAN_ABP_SIZE: usize = 43;
+/// Size of the configuration of the LoRaWAN OTAA interface
+const LORAWAN_OTAA_SIZE: usize = 35;
+/// Size of the D7 interface status, up to the addressee
+const DASH7_STATUS_SIZE: usize = 10;
+/// Size of an encoded file header
+const FILE_HEADER_SIZE: usize = 12;
+/// Size of a D7 permission
+const PERMISSION_SIZE: usize = 8;
+/// Id of the only known permission type
+const PERMISSION_DASH7: u8 = 0x42;
+
+/// Largest value that fits in a length field
+const MAX_LENGTH: u32 = 0x3FFF_FFFF;
+
+/// Errors raised by the borrowed codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The input ended in the middle of an action
+    UnexpectedEnd { offset: usize },
+
+    /// The opcode is unknown, or not supported by the dialect
+    UnknownOpCode { offset: usize, opcode: u8 },
+
+    /// A field holds a value that can not be decoded or encoded
+    InvalidValue { offset: usize, field: &'static str },
+
+    /// The output buffer is too small
+    BufferFull,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd { offset } => {
+                write!(f, "unexpected end of input at byte {}", offset)
+            }
+            Self::UnknownOpCode { offset, opcode } => {
+                write!(f, "unknown opcode {} at byte {}", opcode, offset)
+            }
+            Self::InvalidValue { offset, field } => {
+                write!(f, "invalid {} at byte {}", field, offset)
+            }
+            Self::BufferFull => f.write_str("output buffer is full"),
+        }
+    }
+}
+
+/// Group and response flags, found in the first byte of most actions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Group with next action
+    pub group: bool,
+    /// Ask for a response (status)
+    pub response: bool,
+}
+
+impl Header {
+    pub fn new(group: bool, response: bool) -> Self {
+        Self { group, response }
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        Self {
+            group: byte & 0x80 != 0,
+            response: byte & 0x40 != 0,
+        }
+    }
+
+    fn to_byte(self, opcode: OpCode) -> u8 {
+        flags(self.group, self.response) | opcode.0
+    }
+}
+
+fn flags(high: bool, low: bool) -> u8 {
+    ((high as u8) << 7) | ((low as u8) << 6)
+}
+
+/// Data read from or written to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileDataRef<'a> {
+    pub header: Header,
+    pub file_id: u8,
+    pub offset: u32,
+    pub data: &'a [u8],
+}
+
+/// Properties of a file. The file header is kept encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilePropertiesRef<'a> {
+    pub header: Header,
+    pub file_id: u8,
+    /// Encoded file header, 12 bytes
+    pub file_header: &'a [u8],
+}
+
+/// A query, kept encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryRef<'a> {
+    pub header: Header,
+    pub query: &'a [u8],
+}
+
+/// An interface id, followed by its encoded configuration or status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceRef<'a> {
+    pub interface_type: u8,
+    pub data: &'a [u8],
+}
+
+/// Statuses regarding actions sent in a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusRef<'a> {
+    Action {
+        action_id: u8,
+        status: u8,
+    },
+    Interface(InterfaceRef<'a>),
+
+    /// Wizzilab only
+    InterfaceFinal(InterfaceRef<'a>),
+}
+
+/// An ALP action that borrows its payload from the buffer it was decoded from
+///
+/// The variants follow [`Action`](super::action::Action). Queries, file headers, interface
+/// configurations and statuses are not decoded any further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionRef<'a> {
+    Nop(Header),
+    ReadFileData {
+        header: Header,
+        file_id: u8,
+        offset: u32,
+        length: u32,
+    },
+    ReadFileProperties {
+        header: Header,
+        file_id: u8,
+    },
+    WriteFileData(FileDataRef<'a>),
+    WriteFileDataFlush(FileDataRef<'a>),
+    WriteFileProperties(FilePropertiesRef<'a>),
+    ActionQuery(QueryRef<'a>),
+    BreakQuery(QueryRef<'a>),
+    PermissionRequest {
+        header: Header,
+        level: u8,
+        /// D7 permission, 8 bytes
+        permission: &'a [u8],
+    },
+    VerifyChecksum(QueryRef<'a>),
+    ExistFile {
+        header: Header,
+        file_id: u8,
+    },
+    CreateNewFile(FilePropertiesRef<'a>),
+    DeleteFile {
+        header: Header,
+        file_id: u8,
+    },
+    RestoreFile {
+        header: Header,
+        file_id: u8,
+    },
+    FlushFile {
+        header: Header,
+        file_id: u8,
+    },
+    CopyFile {
+        header: Header,
+        src_file_id: u8,
+        dst_file_id: u8,
+    },
+    ExecuteFile {
+        header: Header,
+        file_id: u8,
+    },
+    ReturnFileData(FileDataRef<'a>),
+    ReturnFileProperties(FilePropertiesRef<'a>),
+    Status(StatusRef<'a>),
+    ResponseTag {
+        end_of_packet: bool,
+        error: bool,
+        id: u8,
+    },
+
+    /// Wizzilab only
+    TxStatus(InterfaceRef<'a>),
+    Chunk(u8),
+    Logic(u8),
+    Forward {
+        response: bool,
+        configuration: InterfaceRef<'a>,
+    },
+    IndirectForward {
+        response: bool,
+        configuration: Option<InterfaceRef<'a>>,
+    },
+    RequestTag {
+        end_of_packet: bool,
+        id: u8,
+    },
+    Extension(Header),
+}
+
+/// Reads from a byte slice, keeping track of the offset for error reporting
+#[derive(Debug)]
+struct Input<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Input<'a> {
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(Error::UnexpectedEnd {
+                offset: self.data.len(),
+            })?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn since(&self, start: usize) -> &'a [u8] {
+        &self.data[start..self.offset]
+    }
+
+    fn length(&mut self) -> Result<u32, Error> {
+        let first = self.u8()?;
+        let mut value = (first & 0x3F) as u32;
+        for byte in self.take((first >> 6) as usize)? {
+            value = (value << 8) | *byte as u32;
+        }
+        Ok(value)
+    }
+
+    fn file_offset(&mut self) -> Result<(u8, u32), Error> {
+        Ok((self.u8()?, self.length()?))
+    }
+
+    fn length_prefixed(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.length()?;
+        self.take(length as usize)
+    }
+
+    fn skip_query(&mut self) -> Result<(), Error> {
+        let offset = self.offset;
+        let byte = self.u8()?;
+        let mask_present = byte & 0x10 != 0;
+
+        match byte >> 5 {
+            // non void
+            0 => {
+                self.length()?;
+            }
+            // comparison with zero
+            1 => {
+                let length = self.length()?;
+                if mask_present {
+                    self.take(length as usize)?;
+                }
+            }
+            // comparison with value
+            2 => {
+                let length = self.length()? as usize;
+                if mask_present {
+                    self.take(length)?;
+                }
+                self.take(length)?;
+            }
+            // comparison with other file
+            3 => {
+                let length = self.length()?;
+                if mask_present {
+                    self.take(length as usize)?;
+                }
+                self.file_offset()?;
+            }
+            // bitmap range comparison
+            4 => {
+                let length = self.length()?;
+                self.length()?;
+                self.length()?;
+                self.take(length as usize)?;
+            }
+            // string token search
+            7 => {
+                let length = self.length()? as usize;
+                self.take(length)?;
+                self.take(length)?;
+            }
+            _ => {
+                return Err(Error::InvalidValue {
+                    offset,
+                    field: "query type",
+                })
+            }
+        }
+
+        self.file_offset()?;
+        Ok(())
+    }
+
+    fn skip_addressee(&mut self, dialect: Dialect) -> Result<(), Error> {
+        let offset = self.offset;
+        let control = self.u8()?;
+
+        // Wizzilab uses the RFU bit of the NLS method for use_vid
+        if !dialect.is_wizzilab() && control & 0x08 != 0 {
+            return Err(Error::InvalidValue {
+                offset,
+                field: "NLS method",
+            });
+        }
+
+        // access class
+        self.u8()?;
+
+        let address = match (control >> 4) & 0x03 {
+            // NbId
+            0 => 1,
+            // NoId
+            1 => 0,
+            // UId
+            2 => 8,
+            // VId
+            _ => 2,
+        };
+        let nls_state = if control & 0x07 == 0 { 0 } else { 5 };
+        self.take(address + nls_state)?;
+        Ok(())
+    }
+
+    fn file_data(&mut self, header: Header) -> Result<FileDataRef<'a>, Error> {
+        let (file_id, offset) = self.file_offset()?;
+        Ok(FileDataRef {
+            header,
+            file_id,
+            offset,
+            data: self.length_prefixed()?,
+        })
+    }
+
+    fn file_properties(&mut self, header: Header) -> Result<FilePropertiesRef<'a>, Error> {
+        Ok(FilePropertiesRef {
+            header,
+            file_id: self.u8()?,
+            file_header: self.take(FILE_HEADER_SIZE)?,
+        })
+    }
+
+    fn query(&mut self, header: Header) -> Result<QueryRef<'a>, Error> {
+        let start = self.offset;
+        self.skip_query()?;
+        Ok(QueryRef {
+            header,
+            query: self.since(start),
+        })
+    }
+
+    fn configuration(&mut self, dialect: Dialect) -> Result<InterfaceRef<'a>, Error> {
+        let interface_type = self.u8()?;
+        let start = self.offset;
+
+        match interface_type {
+            INTERFACE_LORAWAN_ABP => {
+                self.take(LORAWAN_ABP_SIZE)?;
+            }
+            INTERFACE_LORAWAN_OTAA => {
+                self.take(LORAWAN_OTAA_SIZE)?;
+            }
+            INTERFACE_DASH7 => {
+                // qos, dormant session timeout and the optional execution delay timeout
+                self.take(if dialect.has_execution_delay_timeout() {
+                    3
+                } else {
+                    2
+                })?;
+                self.skip_addressee(dialect)?;
+            }
+            _ => (),
+        }
+
+        Ok(InterfaceRef {
+            interface_type,
+            data: self.since(start),
+        })
+    }
+
+    fn indirect_interface(&mut self, dialect: Dialect) -> Result<InterfaceRef<'a>, Error> {
+        let interface_type = self.u8()?;
+        let start = self.offset;
+
+        if interface_type == INTERFACE_DASH7 {
+            self.skip_addressee(dialect)?;
+        }
+
+        Ok(InterfaceRef {
+            interface_type,
+            data: self.since(start),
+        })
+    }
+
+    fn interface_status(&mut self, dialect: Dialect) -> Result<InterfaceRef<'a>, Error> {
+        let interface_type = self.u8()?;
+
+        // Subiot v0.0 was missing the length field
+        let data = if dialect.has_interface_status_length() {
+            self.length_prefixed()?
+        } else {
+            let start = self.offset;
+            if interface_type == INTERFACE_DASH7 {
+                self.take(DASH7_STATUS_SIZE)?;
+                self.skip_addressee(dialect)?;
+            }
+            self.since(start)
+        };
+
+        Ok(InterfaceRef {
+            interface_type,
+            data,
+        })
+    }
+
+    fn interface_length_prefixed(&mut self) -> Result<InterfaceRef<'a>, Error> {
+        Ok(InterfaceRef {
+            interface_type: self.u8()?,
+            data: self.length_prefixed()?,
+        })
+    }
+
+    fn status(&mut self, status_type: u8, dialect: Dialect) -> Result<StatusRef<'a>, Error> {
+        match status_type {
+            0 => Ok(StatusRef::Action {
+                action_id: self.u8()?,
+                status: self.u8()?,
+            }),
+            1 => Ok(StatusRef::Interface(self.interface_status(dialect)?)),
+            2 if dialect.is_wizzilab() => {
+                Ok(StatusRef::InterfaceFinal(self.interface_length_prefixed()?))
+            }
+            _ => Err(Error::InvalidValue {
+                offset: self.offset - 1,
+                field: "status type",
+            }),
+        }
+    }
+
+    fn action(&mut self, dialect: Dialect) -> Result<ActionRef<'a>, Error> {
+        let offset = self.offset;
+        let byte = self.u8()?;
+        let header = Header::from_byte(byte);
+        let high = byte & 0x80 != 0;
+        let low = byte & 0x40 != 0;
+
+        let action = match OpCode(byte & 0x3F) {
+            OpCode::NOP => ActionRef::Nop(header),
+            OpCode::READ_FILE_DATA => {
+                let (file_id, offset) = self.file_offset()?;
+                ActionRef::ReadFileData {
+                    header,
+                    file_id,
+                    offset,
+                    length: self.length()?,
+                }
+            }
+            OpCode::READ_FILE_PROPERTIES => ActionRef::ReadFileProperties {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::WRITE_FILE_DATA => ActionRef::WriteFileData(self.file_data(header)?),
+            OpCode::WRITE_FILE_DATA_FLUSH => ActionRef::WriteFileDataFlush(self.file_data(header)?),
+            OpCode::WRITE_FILE_PROPERTIES => {
+                ActionRef::WriteFileProperties(self.file_properties(header)?)
+            }
+            OpCode::ACTION_QUERY => ActionRef::ActionQuery(self.query(header)?),
+            OpCode::BREAK_QUERY => ActionRef::BreakQuery(self.query(header)?),
+            OpCode::PERMISSION_REQUEST => {
+                let level = self.u8()?;
+                if level > 1 {
+                    return Err(Error::InvalidValue {
+                        offset: self.offset - 1,
+                        field: "permission level",
+                    });
+                }
+                if self.u8()? != PERMISSION_DASH7 {
+                    return Err(Error::InvalidValue {
+                        offset: self.offset - 1,
+                        field: "permission type",
+                    });
+                }
+                ActionRef::PermissionRequest {
+                    header,
+                    level,
+                    permission: self.take(PERMISSION_SIZE)?,
+                }
+            }
+            OpCode::VERIFY_CHECKSUM => ActionRef::VerifyChecksum(self.query(header)?),
+            OpCode::EXIST_FILE => ActionRef::ExistFile {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::CREATE_NEW_FILE => ActionRef::CreateNewFile(self.file_properties(header)?),
+            OpCode::DELETE_FILE => ActionRef::DeleteFile {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::RESTORE_FILE => ActionRef::RestoreFile {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::FLUSH_FILE => ActionRef::FlushFile {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::COPY_FILE => ActionRef::CopyFile {
+                header,
+                src_file_id: self.u8()?,
+                dst_file_id: self.u8()?,
+            },
+            OpCode::EXECUTE_FILE => ActionRef::ExecuteFile {
+                header,
+                file_id: self.u8()?,
+            },
+            OpCode::RETURN_FILE_DATA => ActionRef::ReturnFileData(self.file_data(header)?),
+            OpCode::RETURN_FILE_PROPERTIES => {
+                ActionRef::ReturnFileProperties(self.file_properties(header)?)
+            }
+            OpCode::STATUS => ActionRef::Status(self.status(byte >> 6, dialect)?),
+            OpCode::RESPONSE_TAG => ActionRef::ResponseTag {
+                end_of_packet: high,
+                error: low,
+                id: self.u8()?,
+            },
+            OpCode::TX_STATUS if dialect.is_wizzilab() => {
+                // only interface tx statuses exist
+                if byte >> 6 != 1 {
+                    return Err(Error::InvalidValue {
+                        offset,
+                        field: "tx status type",
+                    });
+                }
+                ActionRef::TxStatus(self.interface_length_prefixed()?)
+            }
+            OpCode::CHUNK => ActionRef::Chunk(byte >> 6),
+            OpCode::LOGIC => ActionRef::Logic(byte >> 6),
+            OpCode::FORWARD => ActionRef::Forward {
+                response: low,
+                configuration: self.configuration(dialect)?,
+            },
+            OpCode::INDIRECT_FORWARD => ActionRef::IndirectForward {
+                response: low,
+                configuration: if high {
+                    Some(self.indirect_interface(dialect)?)
+                } else {
+                    None
+                },
+            },
+            OpCode::REQUEST_TAG => ActionRef::RequestTag {
+                end_of_packet: high,
+                id: self.u8()?,
+            },
+            OpCode::EXTENSION => ActionRef::Extension(header),
+            OpCode(opcode) => return Err(Error::UnknownOpCode { offset, opcode }),
+        };
+
+        Ok(action)
+    }
+}
+
+/// Writes into a byte slice
+struct Output<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Output<'_> {
+    fn u8(&mut self, value: u8) -> Result<(), Error> {
+        self.bytes(&[value])
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferFull)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn length(&mut self, value: u32) -> Result<(), Error> {
+        let extra = match value {
+            0..=0x3F => 0,
+            0x40..=0x3FFF => 1,
+            0x4000..=0x3F_FFFF => 2,
+            0x40_0000..=MAX_LENGTH => 3,
+            _ => {
+                return Err(Error::InvalidValue {
+                    offset: self.len,
+                    field: "length",
+                })
+            }
+        };
+
+        let mut bytes = value.to_be_bytes();
+        let bytes = &mut bytes[3 - extra..];
+        bytes[0] |= (extra as u8) << 6;
+        self.bytes(bytes)
+    }
+
+    fn length_prefixed(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.length(data.len().try_into().unwrap_or(u32::MAX))?;
+        self.bytes(data)
+    }
+
+    fn exact(&mut self, data: &[u8], size: usize, field: &'static str) -> Result<(), Error> {
+        if data.len() != size {
+            return Err(Error::InvalidValue {
+                offset: self.len,
+                field,
+            });
+        }
+        self.bytes(data)
+    }
+
+    fn two_bits(&mut self, value: u8, opcode: OpCode, field: &'static str) -> Result<(), Error> {
+        if value > 3 {
+            return Err(Error::InvalidValue {
+                offset: self.len,
+                field,
+            });
+        }
+        self.u8((value << 6) | opcode.0)
+    }
+
+    fn file_data(&mut self, opcode: OpCode, file_data: &FileDataRef) -> Result<(), Error> {
+        self.u8(file_data.header.to_byte(opcode))?;
+        self.u8(file_data.file_id)?;
+        self.length(file_data.offset)?;
+        self.length_prefixed(file_data.data)
+    }
+
+    fn file_properties(
+        &mut self,
+        opcode: OpCode,
+        properties: &FilePropertiesRef,
+    ) -> Result<(), Error> {
+        self.u8(properties.header.to_byte(opcode))?;
+        self.u8(properties.file_id)?;
+        self.exact(properties.file_header, FILE_HEADER_SIZE, "file header")
+    }
+
+    fn file_id(&mut self, opcode: OpCode, header: &Header, file_id: u8) -> Result<(), Error> {
+        self.u8(header.to_byte(opcode))?;
+        self.u8(file_id)
+    }
+
+    fn query(&mut self, opcode: OpCode, query: &QueryRef) -> Result<(), Error> {
+        self.u8(query.header.to_byte(opcode))?;
+        self.bytes(query.query)
+    }
+
+    fn interface(&mut self, interface: &InterfaceRef) -> Result<(), Error> {
+        self.u8(interface.interface_type)?;
+        self.bytes(interface.data)
+    }
+
+    fn interface_length_prefixed(&mut self, interface: &InterfaceRef) -> Result<(), Error> {
+        self.u8(interface.interface_type)?;
+        self.length_prefixed(interface.data)
+    }
+
+    fn status(&mut self, status: &StatusRef, dialect: Dialect) -> Result<(), Error> {
+        match status {
+            StatusRef::Action { action_id, status } => {
+                self.two_bits(0, OpCode::STATUS, "status type")?;
+                self.u8(*action_id)?;
+                self.u8(*status)
+            }
+            StatusRef::Interface(interface) => {
+                self.two_bits(1, OpCode::STATUS, "status type")?;
+                // Subiot v0.0 was missing the length field
+                if dialect.has_interface_status_length() {
+                    self.interface_length_prefixed(interface)
+                } else {
+                    self.interface(interface)
+                }
+            }
+            StatusRef::InterfaceFinal(interface) if dialect.is_wizzilab() => {
+                self.two_bits(2, OpCode::STATUS, "status type")?;
+                self.interface_length_prefixed(interface)
+            }
+            StatusRef::InterfaceFinal(_) => Err(Error::InvalidValue {
+                offset: self.len,
+                field: "status type",
+            }),
+        }
+    }
+
+    fn action(&mut self, action: &ActionRef, dialect: Dialect) -> Result<(), Error> {
+        let opcode = action.opcode();
+
+        match action {
+            ActionRef::Nop(header) | ActionRef::Extension(header) => {
+                self.u8(header.to_byte(opcode))
+            }
+            ActionRef::ReadFileData {
+                header,
+                file_id,
+                offset,
+                length,
+            } => {
+                self.u8(header.to_byte(opcode))?;
+                self.u8(*file_id)?;
+                self.length(*offset)?;
+                self.length(*length)
+            }
+            ActionRef::ReadFileProperties { header, file_id }
+            | ActionRef::ExistFile { header, file_id }
+            | ActionRef::DeleteFile { header, file_id }
+            | ActionRef::RestoreFile { header, file_id }
+            | ActionRef::FlushFile { header, file_id }
+            | ActionRef::ExecuteFile { header, file_id } => self.file_id(opcode, header, *file_id),
+            ActionRef::WriteFileData(file_data)
+            | ActionRef::WriteFileDataFlush(file_data)
+            | ActionRef::ReturnFileData(file_data) => self.file_data(opcode, file_data),
+            ActionRef::WriteFileProperties(properties)
+            | ActionRef::CreateNewFile(properties)
+            | ActionRef::ReturnFileProperties(properties) => {
+                self.file_properties(opcode, properties)
+            }
+            ActionRef::ActionQuery(query)
+            | ActionRef::BreakQuery(query)
+            | ActionRef::VerifyChecksum(query) => self.query(opcode, query),
+            ActionRef::PermissionRequest {
+                header,
+                level,
+                permission,
+            } => {
+                self.u8(header.to_byte(opcode))?;
+                self.u8(*level)?;
+                self.u8(PERMISSION_DASH7)?;
+                self.exact(permission, PERMISSION_SIZE, "permission")
+            }
+            ActionRef::CopyFile {
+                header,
+                src_file_id,
+                dst_file_id,
+            } => {
+                self.u8(header.to_byte(opcode))?;
+                self.u8(*src_file_id)?;
+                self.u8(*dst_file_id)
+            }
+            ActionRef::Status(status) => self.status(status, dialect),
+            ActionRef::ResponseTag {
+                end_of_packet,
+                error,
+                id,
+            } => {
+                self.u8(flags(*end_of_packet, *error) | opcode.0)?;
+                self.u8(*id)
+            }
+            ActionRef::TxStatus(interface) => {
+                if !dialect.is_wizzilab() {
+                    return Err(Error::UnknownOpCode {
+                        offset: self.len,
+                        opcode: opcode.0,
+                    });
+                }
+                self.two_bits(1, opcode, "tx status type")?;
+                self.interface_length_prefixed(interface)
+            }
+            ActionRef::Chunk(step) => self.two_bits(*step, opcode, "chunk step"),
+            ActionRef::Logic(logic) => self.two_bits(*logic, opcode, "logic"),
+            ActionRef::Forward {
+                response,
+                configuration,
+            } => {
+                self.u8(flags(false, *response) | opcode.0)?;
+                self.interface(configuration)
+            }
+            ActionRef::IndirectForward {
+                response,
+                configuration,
+            } => {
+                self.u8(flags(configuration.is_some(), *response) | opcode.0)?;
+                match configuration {
+                    Some(configuration) => self.interface(configuration),
+                    None => Ok(()),
+                }
+            }
+            ActionRef::RequestTag { end_of_packet, id } => {
+                self.u8(flags(*end_of_packet, false) | opcode.0)?;
+                self.u8(*id)
+            }
+        }
+    }
+}
+
+impl<'a> ActionRef<'a> {
+    /// Decodes a single action from the start of `data`, returning it with the number of bytes used
+    pub fn decode(data: &'a [u8], dialect: Dialect) -> Result<(Self, usize), Error> {
+        let mut input = Input { data, offset: 0 };
+        let action = input.action(dialect)?;
+        Ok((action, input.offset))
+    }
+
+    /// Encodes the action into `buf`, returning the number of bytes written
+    pub fn encode(&self, buf: &mut [u8], dialect: Dialect) -> Result<usize, Error> {
+        let mut output = Output { buf, len: 0 };
+        output.action(self, dialect)?;
+        Ok(output.len)
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        match self {
+            Self::Nop(_) => OpCode::NOP,
+            Self::ReadFileData { .. } => OpCode::READ_FILE_DATA,
+            Self::ReadFileProperties { .. } => OpCode::READ_FILE_PROPERTIES,
+            Self::WriteFileData(_) => OpCode::WRITE_FILE_DATA,
+            Self::WriteFileDataFlush(_) => OpCode::WRITE_FILE_DATA_FLUSH,
+            Self::WriteFileProperties(_) => OpCode::WRITE_FILE_PROPERTIES,
+            Self::ActionQuery(_) => OpCode::ACTION_QUERY,
+            Self::BreakQuery(_) => OpCode::BREAK_QUERY,
+            Self::PermissionRequest { .. } => OpCode::PERMISSION_REQUEST,
+            Self::VerifyChecksum(_) => OpCode::VERIFY_CHECKSUM,
+            Self::ExistFile { .. } => OpCode::EXIST_FILE,
+            Self::CreateNewFile(_) => OpCode::CREATE_NEW_FILE,
+            Self::DeleteFile { .. } => OpCode::DELETE_FILE,
+            Self::RestoreFile { .. } => OpCode::RESTORE_FILE,
+            Self::FlushFile { .. } => OpCode::FLUSH_FILE,
+            Self::CopyFile { .. } => OpCode::COPY_FILE,
+            Self::ExecuteFile { .. } => OpCode::EXECUTE_FILE,
+            Self::ReturnFileData(_) => OpCode::RETURN_FILE_DATA,
+            Self::ReturnFileProperties(_) => OpCode::RETURN_FILE_PROPERTIES,
+            Self::Status(_) => OpCode::STATUS,
+            Self::ResponseTag { .. } => OpCode::RESPONSE_TAG,
+            Self::TxStatus(_) => OpCode::TX_STATUS,
+            Self::Chunk(_) => OpCode::CHUNK,
+            Self::Logic(_) => OpCode::LOGIC,
+            Self::Forward { .. } => OpCode::FORWARD,
+            Self::IndirectForward { .. } => OpCode::INDIRECT_FORWARD,
+            Self::RequestTag { .. } => OpCode::REQUEST_TAG,
+            Self::Extension(_) => OpCode::EXTENSION,
+        }
+    }
+}
+
+/// An ALP command that is decoded lazily from a borrowed buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandRef<'a> {
+    data: &'a [u8],
+    dialect: Dialect,
+}
+
+impl<'a> CommandRef<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_dialect(data, Dialect::default())
+    }
+
+    pub fn with_dialect(data: &'a [u8], dialect: Dialect) -> Self {
+        Self { data, dialect }
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Iterates over the actions of the command
+    ///
+    /// Iteration stops after the first error, and after an indirect forward: what follows it is
+    /// the payload to forward, see [`Actions::remainder`].
+    pub fn actions(&self) -> Actions<'a> {
+        Actions {
+            input: Input {
+                data: self.data,
+                offset: 0,
+            },
+            dialect: self.dialect,
+            done: false,
+        }
+    }
+
+    /// Returns the request tag id, if the command starts with one
+    pub fn request_tag(&self) -> Option<u8> {
+        match self.actions().next() {
+            Some(Ok(ActionRef::RequestTag { id, .. })) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Returns the response tag id, if the command starts with one
+    pub fn response_tag(&self) -> Option<u8> {
+        match self.actions().next() {
+            Some(Ok(ActionRef::ResponseTag { id, .. })) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over the actions of a [`CommandRef`]
+#[derive(Debug)]
+pub struct Actions<'a> {
+    input: Input<'a>,
+    dialect: Dialect,
+    done: bool,
+}
+
+impl<'a> Actions<'a> {
+    /// Bytes that have not been decoded yet
+    pub fn remainder(&self) -> &'a [u8] {
+        &self.input.data[self.input.offset..]
+    }
+}
+
+impl<'a> Iterator for Actions<'a> {
+    type Item = Result<ActionRef<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.input.offset >= self.input.data.len() {
+            return None;
+        }
+
+        let action = self.input.action(self.dialect);
+        self.done = matches!(action, Err(_) | Ok(ActionRef::IndirectForward { .. }));
+        Some(action)
+    }
+}
+
+/// Builds an ALP command in a fixed-capacity buffer
+#[derive(Debug, Clone)]
+pub struct CommandBuffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    dialect: Dialect,
+}
+
+impl<const N: usize> Default for CommandBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CommandBuffer<N> {
+    pub fn new() -> Self {
+        Self::with_dialect(Dialect::default())
+    }
+
+    pub fn with_dialect(dialect: Dialect) -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            dialect,
+        }
+    }
+
+    /// Appends an action. The buffer is left untouched when the action does not fit.
+    pub fn push(&mut self, action: &ActionRef) -> Result<(), Error> {
+        self.len += action.encode(&mut self.buf[self.len..], self.dialect)?;
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn command(&self) -> CommandRef<'_> {
+        CommandRef::with_dialect(self.as_bytes(), self.dialect)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+
+    const SPEC: Dialect = Dialect::SpecV1_2;
+
+    /// Decodes all actions and encodes them again
+    #[track_caller]
+    fn roundtrip(data: &[u8], dialect: Dialect) {
+        let command = CommandRef::with_dialect(data, dialect);
+        let mut buffer = CommandBuffer::<256>::with_dialect(dialect);
+        for action in command.actions() {
+            buffer.push(&action.unwrap()).unwrap();
+        }
+        assert_eq!(buffer.as_bytes(), data);
+
+        #[cfg(feature = "alloc")]
+        {
+            // the allocating decoder must agree on the number of actions
+            let (_, decoded) =
+                super::super::command::Command::from_bytes_with_dialect((data, 0), dialect)
+                    .unwrap();
+            assert_eq!(decoded.actions.len(), command.actions().count());
+        }
+    }
+
+    #[test]
+    fn test_read_file_data() {
+        let data = hex!("B4 42 41 00 00 08 81 04 02 03 C0");
+        let command = CommandRef::with_dialect(&data, SPEC);
+        let mut actions = command.actions();
+
+        assert_eq!(
+            actions.next(),
+            Some(Ok(ActionRef::RequestTag {
+                end_of_packet: true,
+                id: 0x42
+            }))
+        );
+        assert_eq!(
+            actions.next(),
+            Some(Ok(ActionRef::ReadFileData {
+                header: Header::new(false, true),
+                file_id: 0,
+                offset: 0,
+                length: 8,
+            }))
+        );
+        assert_eq!(
+            actions.next(),
+            Some(Ok(ActionRef::ReadFileData {
+                header: Header::new(true, false),
+                file_id: 4,
+                offset: 2,
+                length: 3,
+            }))
+        );
+        assert_eq!(
+            actions.next(),
+            Some(Ok(ActionRef::Nop(Header::new(true, true))))
+        );
+        assert_eq!(actions.next(), None);
+        assert_eq!(command.request_tag(), Some(0x42));
+
+        roundtrip(&data, SPEC);
+    }
+
+    #[test]
+    fn test_file_data_borrows() {
+        let data = hex!("20 F9 05 03 010203");
+        let (action, size) = ActionRef::decode(&data, SPEC).unwrap();
+        assert_eq!(size, data.len());
+
+        let ActionRef::ReturnFileData(file_data) = action else {
+            panic!("unexpected action {:?}", action);
+        };
+        assert_eq!(file_data.file_id, 0xF9);
+        assert_eq!(file_data.offset, 5);
+        assert_eq!(file_data.data, &hex!("010203"));
+        assert_eq!(file_data.data.as_ptr(), data[4..].as_ptr());
+    }
+
+    #[test]
+    fn test_actions() {
+        for data in [
+            &hex!("84 F9 05 03 010203")[..],
+            &hex!("21 09 B8 13 01 02 DEADBEEF BAADFACE"),
+            &hex!("0A 01 42 0102030405060708"),
+            &hex!("17 42 24"),
+            &hex!("C8 00 04 05 06"),
+            &hex!("C9 00 04 05 06"),
+            &hex!("0B 00 04 05 06"),
+            &hex!("48 54 01 02 03 04 00"),
+            &hex!("72 00"),
+            &hex!("B4 08"),
+            &hex!("A3 08"),
+            &hex!("22 02 F6"),
+            &hex!("F3 D7 37 FF ABCD 01 02 03 04 05"),
+            &hex!("62 D7 14 32 00 32 2D 3E 50 80 00 00 58 20 01 39 38 38 37 00 39 00 2E"),
+            &hex!("32 D7 01 00 00 10 01"),
+        ] {
+            roundtrip(data, SPEC);
+        }
+    }
+
+    #[test]
+    fn test_dialects() {
+        // D7 forward, with execution delay timeout and a wizzilab addressee
+        let wizzilab = hex!("32 D7 01 00 00 B8 01 ABCD");
+        roundtrip(&wizzilab, Dialect::WizzilabV5_3);
+        assert_eq!(
+            CommandRef::with_dialect(&wizzilab, SPEC).actions().next(),
+            Some(Err(Error::InvalidValue {
+                offset: 5,
+                field: "NLS method"
+            }))
+        );
+
+        // sub-iot has no execution delay timeout
+        let subiot = hex!("32 D7 01 00 10 01");
+        roundtrip(&subiot, Dialect::SubIoTV0_1);
+
+        // sub-iot v0.0 has no interface status length
+        let subiot_v0_0 = hex!("62 D7 32 00 32 2D 3E 50 80 00 00 58 20 01 39 38 38 37 00 39 00 2E");
+        roundtrip(&subiot_v0_0, Dialect::SubIoTV0_0);
+
+        let tx_status = hex!("66 D7 15 01 0123 02 FF 00 00 00 0000 0807 36 FF 0011 0000000000");
+        roundtrip(&tx_status, Dialect::WizzilabV5_3);
+        assert_eq!(
+            ActionRef::decode(&tx_status, SPEC),
+            Err(Error::UnknownOpCode {
+                offset: 0,
+                opcode: 38
+            })
+        );
+    }
+
+    #[test]
+    fn test_indirect_forward_remainder() {
+        let data = hex!("F3 D7 37 FF ABCD 01 02 03 04 05 01 00 00 08");
+        let command = CommandRef::with_dialect(&data, SPEC);
+        let mut actions = command.actions();
+
+        assert!(matches!(
+            actions.next(),
+            Some(Ok(ActionRef::IndirectForward {
+                response: true,
+                configuration: Some(InterfaceRef {
+                    interface_type: 0xD7,
+                    ..
+                })
+            }))
+        ));
+        assert_eq!(actions.next(), None);
+        assert_eq!(actions.remainder(), &hex!("01 00 00 08"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            CommandRef::new(&hex!("01 00 00 08 3E")).actions().nth(1),
+            Some(Err(Error::UnknownOpCode {
+                offset: 4,
+                opcode: 0x3E
+            }))
+        );
+        assert_eq!(
+            CommandRef::new(&hex!("04 40 00 05 01 02")).actions().next(),
+            Some(Err(Error::UnexpectedEnd { offset: 6 }))
+        );
+
+        let mut actions = CommandRef::new(&hex!("3E 01 00 00 08")).actions();
+        assert!(actions.next().unwrap().is_err());
+        assert_eq!(actions.next(), None);
+    }
+
+    #[test]
+    fn test_buffer_full() {
+        let action = ActionRef::WriteFileData(FileDataRef {
+            header: Header::default(),
+            file_id: 0x40,
+            offset: 0,
+            data: &hex!("01020304"),
+        });
+
+        let mut buffer = CommandBuffer::<8>::new();
+        buffer.push(&action).unwrap();
+        assert_eq!(buffer.as_bytes(), &hex!("04 40 00 04 01020304"));
+
+        assert_eq!(buffer.push(&action), Err(Error::BufferFull));
+        assert_eq!(buffer.len(), 8);
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_length() {
+        for (value, encoded) in [
+            (1, &hex!("01")[..]),
+            (65, &hex!("40 41")),
+            (4263936, &hex!("C0 41 10 00")),
+        ] {
+            let mut buf = [0; 4];
+            let mut output = Output {
+                buf: &mut buf,
+                len: 0,
+            };
+            output.length(value).unwrap();
+            assert_eq!(&buf[..encoded.len()], encoded);
+
+            let mut input = Input {
+                data: encoded,
+                offset: 0,
+            };
+            assert_eq!(input.length(), Ok(value));
+        }
+
+        let mut buf = [0; 4];
+        let mut output = Output {
+            buf: &mut buf,
+            len: 0,
+        };
+        assert!(output.length(MAX_LENGTH + 1).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{file::File, network::Addressee, session::QoS};
+
+use super::{
+    action::{Action, OpCode},
+    command::Command,
+    interface::{Dash7InterfaceConfiguration, IndirectInterface, InterfaceConfiguration},
+    operation::{
+        ActionHeader, ActionQuery, Chunk, ChunkStep, CopyFile, FileData, FileId, FileOffset,
+        Forward, IndirectForward, Logic, LogicOp, Nop, Query, ReadFileData, RequestTag,
+        RequestTagHeader,
+    },
+    validate::Diagnostic,
+};
+
+/// Reasons a [`CommandBuilder`] can not build a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The command has no actions
+    Empty,
+
+    /// The actions are not combined in a way that makes sense, see [`Command::validate`]
+    Invalid(Diagnostic),
+
+    /// A group needs at least one action
+    EmptyGroup,
+
+    /// The action has no group flag, so it can not be part of a group
+    NotGroupable { opcode: OpCode },
+
+    /// The file id of a file can not be derived from its type
+    UnknownFileId,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "command has no actions"),
+            Self::Invalid(diagnostic) => write!(f, "{}", diagnostic),
+            Self::EmptyGroup => write!(f, "group has no actions"),
+            Self::NotGroupable { opcode } => {
+                write!(f, "opcode {:#04X} can not be grouped", opcode.0)
+            }
+            Self::UnknownFileId => write!(f, "file id unknown, give it explicitly"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+/// Builds a [`Command`] one action at a time.
+///
+/// Opcodes are filled in, and file ids are derived from typed files. Whether the actions are
+/// ordered in a way that makes sense is checked by [`CommandBuilder::build`].
+///
+/// ```
+/// use dash7::app::command::Command;
+///
+/// let command = Command::builder()
+///     .request_tag(1)
+///     .response(true)
+///     .read_file(0x00, 0, 8)
+///     .build()
+///     .unwrap();
+/// assert_eq!(command.to_bytes().unwrap(), [0xB4, 0x01, 0x41, 0x00, 0x00, 0x08]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandBuilder {
+    actions: Vec<Action>,
+    response: bool,
+    error: Option<BuildError>,
+}
+
+impl CommandBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the response flag of the actions added after this call
+    pub fn response(mut self, response: bool) -> Self {
+        self.response = response;
+        self
+    }
+
+    fn header(&self) -> ActionHeader {
+        ActionHeader::new(false, self.response)
+    }
+
+    fn fail(mut self, error: BuildError) -> Self {
+        self.error.get_or_insert(error);
+        self
+    }
+
+    /// Adds any action as is
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn nop(self) -> Self {
+        let action = Action::Nop(Nop {
+            header: self.header(),
+            opcode: OpCode::NOP,
+        });
+        self.action(action)
+    }
+
+    /// Tags the command, so responses to it can be recognized. The request tag has to come first.
+    pub fn request_tag(self, id: u8) -> Self {
+        self.action(Action::RequestTag(RequestTag {
+            header: RequestTagHeader {
+                end_of_packet: true,
+            },
+            opcode: OpCode::REQUEST_TAG,
+            id,
+        }))
+    }
+
+    pub fn read_file(self, file_id: u8, offset: u32, length: u32) -> Self {
+        let action = Action::ReadFileData(ReadFileData {
+            header: self.header(),
+            opcode: OpCode::READ_FILE_DATA,
+            offset: FileOffset {
+                file_id,
+                offset: offset.into(),
+            },
+            length: length.into(),
+        });
+        self.action(action)
+    }
+
+    pub fn read_file_properties(self, file_id: u8) -> Self {
+        let action =
+            Action::ReadFileProperties(self.file_id(OpCode::READ_FILE_PROPERTIES, file_id));
+        self.action(action)
+    }
+
+    /// Writes a whole file. The file id follows from the type of the file.
+    pub fn write_file(self, file: File) -> Self {
+        match file.file_id() {
+            Some(file_id) => {
+                let action = Action::WriteFileData(self.file_data(
+                    OpCode::WRITE_FILE_DATA,
+                    file_id,
+                    0,
+                    file,
+                ));
+                self.action(action)
+            }
+            None => self.fail(BuildError::UnknownFileId),
+        }
+    }
+
+    /// Writes raw data to a file, starting at `offset`
+    pub fn write_file_data(self, file_id: u8, offset: u32, data: impl Into<Vec<u8>>) -> Self {
+        let action = Action::WriteFileData(self.file_data(
+            OpCode::WRITE_FILE_DATA,
+            file_id,
+            offset,
+            File::Other(data.into()),
+        ));
+        self.action(action)
+    }
+
+    pub fn exist_file(self, file_id: u8) -> Self {
+        let action = Action::ExistFile(self.file_id(OpCode::EXIST_FILE, file_id));
+        self.action(action)
+    }
+
+    pub fn delete_file(self, file_id: u8) -> Self {
+        let action = Action::DeleteFile(self.file_id(OpCode::DELETE_FILE, file_id));
+        self.action(action)
+    }
+
+    pub fn restore_file(self, file_id: u8) -> Self {
+        let action = Action::RestoreFile(self.file_id(OpCode::RESTORE_FILE, file_id));
+        self.action(action)
+    }
+
+    pub fn flush_file(self, file_id: u8) -> Self {
+        let action = Action::FlushFile(self.file_id(OpCode::FLUSH_FILE, file_id));
+        self.action(action)
+    }
+
+    pub fn execute_file(self, file_id: u8) -> Self {
+        let action = Action::ExecuteFile(self.file_id(OpCode::EXECUTE_FILE, file_id));
+        self.action(action)
+    }
+
+    pub fn copy_file(self, src_file_id: u8, dst_file_id: u8) -> Self {
+        let action = Action::CopyFile(CopyFile {
+            header: self.header(),
+            opcode: OpCode::COPY_FILE,
+            src_file_id,
+            dst_file_id,
+        });
+        self.action(action)
+    }
+
+    /// Only executes the next action, or group of actions, if the query matches
+    pub fn query(self, query: Query) -> Self {
+        let action = Action::ActionQuery(self.action_query(OpCode::ACTION_QUERY, query));
+        self.action(action)
+    }
+
+    /// Stops executing the command if the query does not match
+    pub fn break_query(self, query: Query) -> Self {
+        let action = Action::BreakQuery(self.action_query(OpCode::BREAK_QUERY, query));
+        self.action(action)
+    }
+
+    /// Combines the queries that follow with `op`, instead of AND-ing them
+    pub fn logic(self, logic: LogicOp) -> Self {
+        self.action(Action::Logic(Logic {
+            logic,
+            opcode: OpCode::LOGIC,
+        }))
+    }
+
+    pub fn chunk(self, step: ChunkStep) -> Self {
+        self.action(Action::Chunk(Chunk::from(step)))
+    }
+
+    /// Forwards the rest of the command over an interface
+    pub fn forward(self, configuration: InterfaceConfiguration) -> Self {
+        let action = Action::Forward(Forward::new(self.response, configuration));
+        self.action(action)
+    }
+
+    /// Forwards the rest of the command over Dash7
+    pub fn forward_dash7(self, addressee: Addressee, qos: QoS) -> Self {
+        self.forward(InterfaceConfiguration::Dash7(Dash7InterfaceConfiguration {
+            qos,
+            addressee,
+            ..Default::default()
+        }))
+    }
+
+    /// Forwards the rest of the command over the interface stored in a file
+    pub fn indirect_forward(self, configuration: Option<IndirectInterface>) -> Self {
+        let action = Action::IndirectForward(IndirectForward::new(self.response, configuration));
+        self.action(action)
+    }
+
+    /// Groups the actions added by `build`: if one of them fails, the others are skipped
+    pub fn group(mut self, build: impl FnOnce(CommandBuilder) -> CommandBuilder) -> Self {
+        let group = build(CommandBuilder::new().response(self.response));
+        if let Some(error) = group.error {
+            return self.fail(error);
+        }
+
+        let count = group.actions.len();
+        if count == 0 {
+            return self.fail(BuildError::EmptyGroup);
+        }
+
+        for (idx, mut action) in group.actions.into_iter().enumerate() {
+            // every action is grouped with the next one, up to the last of the group.
+            // Logic has no group flag, it is part of the group of queries it precedes.
+            let opcode = action.opcode();
+            match action.header_mut() {
+                Some(header) => header.group = idx + 1 < count,
+                None if opcode == OpCode::LOGIC => {}
+                None => return self.fail(BuildError::NotGroupable { opcode }),
+            }
+            self.actions.push(action);
+        }
+        self
+    }
+
+    /// Checks the order of the actions and builds the command. The first problem found is returned.
+    pub fn build(self) -> Result<Command, BuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.actions.is_empty() {
+            return Err(BuildError::Empty);
+        }
+
+        let command = Command::new(self.actions);
+        match command.validate().into_iter().next() {
+            Some(diagnostic) => Err(BuildError::Invalid(diagnostic)),
+            None => Ok(command),
+        }
+    }
+
+    fn file_id(&self, opcode: OpCode, file_id: u8) -> FileId {
+        FileId {
+            header: self.header(),
+            opcode,
+            file_id,
+        }
+    }
+
+    fn file_data(&self, opcode: OpCode, file_id: u8, offset: u32, file: File) -> FileData {
+        let offset = FileOffset {
+            file_id,
+            offset: offset.into(),
+        };
+        FileData::new(self.header(), offset, file, opcode)
+    }
+
+    fn action_query(&self, opcode: OpCode, query: Query) -> ActionQuery {
+        ActionQuery {
+            header: self.header(),
+            opcode,
+            query,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{
+        app::query::NonVoid,
+        file::FirmwareVersion,
+        link::AccessClass,
+        network::{Address, NlsState},
+    };
+
+    #[test]
+    fn test_read() {
+        let command = CommandBuilder::new()
+            .request_tag(1)
+            .response(true)
+            .read_file(0x40, 4, 8)
+            .build()
+            .unwrap();
+        assert_eq!(command.request_id(), Some(1));
+        assert_eq!(command.to_bytes().unwrap(), hex!("B4 01 41 40 04 08"));
+    }
+
+    #[test]
+    fn test_group() {
+        let command = Command::builder()
+            .query(Query::NonVoid(NonVoid {
+                length: 1u32.into(),
+                file: FileOffset::no_offset(0x40),
+            }))
+            .group(|group| group.read_file(0x40, 0, 1).write_file_data(0x41, 0, [0x01]))
+            .build()
+            .unwrap();
+        assert_eq!(
+            command.to_bytes().unwrap(),
+            hex!("08 00 01 40 00   81 40 00 01   04 41 00 01 01")
+        );
+    }
+
+    #[test]
+    fn test_write_file() {
+        let version = FirmwareVersion {
+            major: 1,
+            minor: 2,
+            application_name: "dash7s".into(),
+            git_sha1: "abcdef0".into(),
+        };
+        let command = Command::builder()
+            .write_file(File::FirmwareVersion(version.clone()))
+            .build()
+            .unwrap();
+
+        let bytes = command.to_bytes().unwrap();
+        assert_eq!(&bytes[..3], hex!("04 02 00"));
+        assert_eq!(Command::try_from(bytes.as_slice()).unwrap(), command);
+
+        assert_eq!(
+            Command::builder().write_file(File::Other(vec![1])).build(),
+            Err(BuildError::UnknownFileId)
+        );
+        assert_eq!(File::FirmwareVersion(version).file_id(), Some(0x02));
+    }
+
+    #[test]
+    fn test_forward() {
+        let addressee = Addressee::new(Address::NoId, NlsState::None, AccessClass::new(0x01, 0x01));
+        let command = Command::builder()
+            .forward_dash7(addressee, QoS::default())
+            .read_file(0x00, 0, 8)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            command.actions[0],
+            Action::Forward(Forward {
+                configuration: InterfaceConfiguration::Dash7(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert_eq!(CommandBuilder::new().build(), Err(BuildError::Empty));
+        assert_eq!(
+            Command::builder().nop().request_tag(1).build(),
+            Err(BuildError::Invalid(Diagnostic::MisplacedRequestTag {
+                index: 1
+            }))
+        );
+        assert_eq!(
+            Command::builder()
+                .indirect_forward(None)
+                .read_file(0, 0, 8)
+                .build(),
+            Err(BuildError::Invalid(
+                Diagnostic::ActionsAfterIndirectForward { index: 0 }
+            ))
+        );
+        assert_eq!(
+            Command::builder().logic(LogicOp::Xor).nop().build(),
+            Err(BuildError::Invalid(Diagnostic::LogicWithoutQuery {
+                index: 0
+            }))
+        );
+        assert_eq!(
+            Command::builder().group(|group| group).build(),
+            Err(BuildError::EmptyGroup)
+        );
+        assert_eq!(
+            Command::builder()
+                .group(|group| group.nop().request_tag(2))
+                .build(),
+            Err(BuildError::NotGroupable {
+                opcode: OpCode::REQUEST_TAG
+            })
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use alloc::vec::Vec;
+use core::{fmt, time::Duration};
+
+use deku::DekuError;
+
+use crate::dialect::Dialect;
+
+use super::{
+    action::Action,
+    command::Command,
+    operation::{Chunk, ChunkStep},
+};
+
+/// Problems reassembling or splitting chunked commands
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ChunkError {
+    /// A continue or end chunk arrived without a start chunk before it. It is dropped.
+    MissingStart { step: ChunkStep },
+
+    /// A start chunk arrived before the previous chunked command ended. The previous one is
+    /// dropped, the new one is kept.
+    Interrupted,
+
+    /// No chunk arrived within the timeout, the chunked command is dropped
+    TimedOut,
+
+    /// A chunk action is not the first action of its command
+    Misplaced { index: usize },
+
+    /// An action does not fit in a frame, even on its own
+    ActionTooLarge { index: usize, size: usize },
+
+    /// An action could not be encoded to find its size
+    Encode(DekuError),
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingStart { step } => write!(f, "chunk {:?} without a start chunk", step),
+            Self::Interrupted => write!(f, "chunked command interrupted by a new start chunk"),
+            Self::TimedOut => write!(f, "chunked command timed out"),
+            Self::Misplaced { index } => {
+                write!(f, "action {}: chunk must be the first action", index)
+            }
+            Self::ActionTooLarge { index, size } => {
+                write!(f, "action {}: {} bytes do not fit in a frame", index, size)
+            }
+            Self::Encode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChunkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Encode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DekuError> for ChunkError {
+    fn from(err: DekuError) -> Self {
+        Self::Encode(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pending {
+    actions: Vec<Action>,
+    /// Time at which the last chunk arrived
+    last: Duration,
+}
+
+/// Collects the chunks of a chunked command into one command.
+///
+/// Time is supplied by the caller, as the time since any fixed point, so the reassembler works
+/// without a clock. Commands without a chunk action, and start-end chunks, are complete on their
+/// own and do not interrupt a chunked command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reassembler {
+    timeout: Duration,
+    pending: Option<Pending>,
+}
+
+impl Reassembler {
+    /// Creates a reassembler that drops a chunked command when no chunk arrives within `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: None,
+        }
+    }
+
+    /// Whether a chunked command was started, but did not end yet
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Drops a chunked command that timed out at `now`
+    pub fn expire(&mut self, now: Duration) -> Result<(), ChunkError> {
+        match &self.pending {
+            Some(pending) if now.saturating_sub(pending.last) > self.timeout => {
+                self.pending = None;
+                Err(ChunkError::TimedOut)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds a command that arrived at `now`. Returns the whole command once it is complete.
+    pub fn push(&mut self, command: Command, now: Duration) -> Result<Option<Command>, ChunkError> {
+        if let Some(index) = command
+            .actions
+            .iter()
+            .skip(1)
+            .position(|action| matches!(action, Action::Chunk(_)))
+        {
+            return Err(ChunkError::Misplaced { index: index + 1 });
+        }
+
+        let mut actions = command.actions;
+        let step = match actions.first() {
+            Some(Action::Chunk(chunk)) => chunk.step,
+            _ => return Ok(Some(Command::new(actions))),
+        };
+        actions.remove(0);
+
+        let expired = self.expire(now);
+        match step {
+            ChunkStep::StartEnd => Ok(Some(Command::new(actions))),
+            ChunkStep::Start => {
+                let interrupted = self.pending.replace(Pending { actions, last: now });
+                match interrupted {
+                    Some(_) => Err(ChunkError::Interrupted),
+                    None => Ok(None),
+                }
+            }
+            ChunkStep::Continue | ChunkStep::End => {
+                let Some(pending) = &mut self.pending else {
+                    expired?;
+                    return Err(ChunkError::MissingStart { step });
+                };
+                pending.actions.append(&mut actions);
+                pending.last = now;
+
+                if step == ChunkStep::End {
+                    let pending = self.pending.take().expect("a chunked command is pending");
+                    return Ok(Some(Command::new(pending.actions)));
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Splits a command into chunks that each encode to at most `max_size` bytes.
+///
+/// A command that fits is returned as is. Actions are never split, so an action that does not fit
+/// in a frame next to its chunk action is an error.
+pub fn split(
+    command: &Command,
+    max_size: usize,
+    dialect: Dialect,
+) -> Result<Vec<Command>, ChunkError> {
+    let sizes = command
+        .actions
+        .iter()
+        .map(|action| action.to_bytes_with_dialect(dialect).map(|data| data.len()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if sizes.iter().sum::<usize>() <= max_size {
+        return Ok(vec![command.clone()]);
+    }
+
+    // every chunk starts with a chunk action of a single byte
+    let mut chunks: Vec<Vec<Action>> = vec![];
+    let mut size = max_size;
+    for (index, (action, action_size)) in command.actions.iter().zip(sizes).enumerate() {
+        if 1 + action_size > max_size {
+            return Err(ChunkError::ActionTooLarge {
+                index,
+                size: action_size,
+            });
+        }
+        if size + action_size > max_size {
+            chunks.push(vec![]);
+            size = 1;
+        }
+        chunks
+            .last_mut()
+            .expect("a chunk was started")
+            .push(action.clone());
+        size += action_size;
+    }
+
+    let last = chunks.len() - 1;
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, actions)| {
+            let step = match idx {
+                0 => ChunkStep::Start,
+                idx if idx == last => ChunkStep::End,
+                _ => ChunkStep::Continue,
+            };
+            let mut chunk = vec![Action::Chunk(Chunk::from(step))];
+            chunk.extend(actions);
+            Command::new(chunk)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chunk(step: ChunkStep, file_ids: &[u8]) -> Command {
+        let mut actions = vec![Action::Chunk(Chunk::from(step))];
+        actions.extend(reads(file_ids).actions);
+        Command::new(actions)
+    }
+
+    fn reads(file_ids: &[u8]) -> Command {
+        file_ids
+            .iter()
+            .fold(Command::builder(), |builder, file_id| {
+                builder.read_file(*file_id, 0, 8)
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn at(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let mut reassembler = Reassembler::new(at(5));
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::Start, &[1, 2]), at(0)),
+            Ok(None)
+        );
+        // commands without chunks pass through
+        assert_eq!(reassembler.push(reads(&[9]), at(1)), Ok(Some(reads(&[9]))));
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::Continue, &[3]), at(2)),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::End, &[4]), at(6)),
+            Ok(Some(reads(&[1, 2, 3, 4])))
+        );
+        assert!(!reassembler.is_pending());
+
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::StartEnd, &[5]), at(7)),
+            Ok(Some(reads(&[5])))
+        );
+    }
+
+    #[test]
+    fn test_reassemble_errors() {
+        let mut reassembler = Reassembler::new(at(5));
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::End, &[1]), at(0)),
+            Err(ChunkError::MissingStart {
+                step: ChunkStep::End
+            })
+        );
+
+        reassembler
+            .push(chunk(ChunkStep::Start, &[1]), at(0))
+            .unwrap();
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::Start, &[2]), at(1)),
+            Err(ChunkError::Interrupted)
+        );
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::End, &[3]), at(2)),
+            Ok(Some(reads(&[2, 3])))
+        );
+
+        reassembler
+            .push(chunk(ChunkStep::Start, &[1]), at(10))
+            .unwrap();
+        assert_eq!(reassembler.expire(at(15)), Ok(()));
+        assert_eq!(
+            reassembler.push(chunk(ChunkStep::Continue, &[2]), at(21)),
+            Err(ChunkError::TimedOut)
+        );
+        assert!(!reassembler.is_pending());
+
+        let mut command = reads(&[1]);
+        command
+            .actions
+            .push(Action::Chunk(Chunk::from(ChunkStep::End)));
+        assert_eq!(
+            reassembler.push(command, at(30)),
+            Err(ChunkError::Misplaced { index: 1 })
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        // every read is 4 bytes long
+        let command = Command::builder()
+            .request_tag(1)
+            .read_file(1, 0, 8)
+            .read_file(2, 0, 8)
+            .read_file(3, 0, 8)
+            .build()
+            .unwrap();
+        assert_eq!(
+            split(&command, 14, Dialect::default()),
+            Ok(vec![command.clone()])
+        );
+
+        let chunks = split(&command, 8, Dialect::default()).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].to_bytes().unwrap().len(), 7);
+        assert!(chunks.iter().all(|chunk| chunk.validate().is_empty()));
+
+        let mut reassembler = Reassembler::new(at(1));
+        let reassembled: Vec<_> = chunks
+            .into_iter()
+            .filter_map(|chunk| reassembler.push(chunk, at(0)).unwrap())
+            .collect();
+        assert_eq!(reassembled, vec![command.clone()]);
+
+        assert_eq!(
+            split(&command, 4, Dialect::default()),
+            Err(ChunkError::ActionTooLarge { index: 1, size: 4 })
+        );
+    }
+}
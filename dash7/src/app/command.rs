@@ -1,26 +1,32 @@
-use std::borrow::Cow;
-#[cfg(feature = "std")]
-use std::fmt::{self, Display};
-
-#[cfg(not(feature = "std"))]
-use alloc::fmt;
+use alloc::{
+    fmt::{self, Display},
+    string::ToString,
+    vec::Vec,
+};
 
 use deku::{
-    no_std_io::{self, Cursor, Seek},
+    no_std_io::{self, Cursor, Seek, SeekFrom},
     prelude::*,
 };
 
 use crate::{
-    session::InterfaceStatus,
-    utils::{from_bytes, from_reader},
+    dialect::Dialect,
+    error::{Error, Layer, Location},
+    file::FileRegistry,
+    session::{InterfaceStatus, QoS},
+    utils::{from_reader, pad_rest, position},
 };
 
 use super::{
     action::Action,
+    builder::CommandBuilder,
+    execute::{self, Node, Outcome},
     operation::{RequestTag, ResponseTag, ResponseTagHeader, Status},
+    validate::{self, Diagnostic},
 };
 
 #[derive(Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Command {
     // we cannot process an indirect forward without knowing the interface type, which is stored in the interface file
     // as identified by the indirectforward itself
@@ -37,47 +43,132 @@ impl<'a> DekuReader<'a, u32> for Command {
         R: no_std_io::Read + no_std_io::Seek,
         Self: Sized,
     {
-        let mut command = Command { actions: vec![] };
-
-        let valid_length = |r: &mut Reader<R>, l: u32| -> Result<bool, DekuError> {
-            Ok(match l {
-                0 => true,
-                _ => {
-                    r.stream_position()
-                        .map_err(|err| DekuError::Io(err.kind()))?
-                        < (l as u64)
-                }
-            })
-        };
+        Self::from_reader_with_ctx(reader, (length, Dialect::default()))
+    }
+}
+
+impl<'a> DekuReader<'a, (u32, Dialect)> for Command {
+    fn from_reader_with_ctx<R>(
+        reader: &mut Reader<R>,
+        (length, dialect): (u32, Dialect),
+    ) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+        Self: Sized,
+    {
+        let mut actions = Vec::new();
+        read_actions(reader, (length, dialect), &mut actions).map_err(|(_, err)| err)?;
+        Ok(Command { actions })
+    }
+}
 
-        while valid_length(reader, length)? && !reader.end() {
-            if let Some(Action::IndirectForward(_)) = command.actions.last() {
-                return Ok(command);
+/// Reads actions into `actions` until `length` bytes are read, or the input ends.
+///
+/// On failure, the bit offset at which the failing action starts is returned with the error.
+fn read_actions<R>(
+    reader: &mut Reader<R>,
+    (length, dialect): (u32, Dialect),
+    actions: &mut Vec<Action>,
+) -> Result<(), (usize, DekuError)>
+where
+    R: no_std_io::Read + no_std_io::Seek,
+{
+    // no_std_io has no stream_position
+    #[allow(clippy::seek_from_current)]
+    let valid_length = |r: &mut Reader<R>, l: u32| -> Result<bool, DekuError> {
+        Ok(match l {
+            0 => true,
+            _ => {
+                r.seek(SeekFrom::Current(0))
+                    .map_err(|err| DekuError::Io(err.kind()))?
+                    < (l as u64)
             }
-            let action = Action::from_reader_with_ctx(reader, ())?;
-            command.actions.push(action);
+        })
+    };
+
+    loop {
+        let start = position(reader).map_err(|err| (reader.bits_read, err))?;
+        if !valid_length(reader, length).map_err(|err| (start, err))? || reader.end() {
+            return Ok(());
         }
-        return Ok(command);
+        if let Some(Action::IndirectForward(_)) = actions.last() {
+            return Ok(());
+        }
+        let action = Action::from_reader_with_ctx(reader, dialect).map_err(|err| (start, err))?;
+        actions.push(action);
     }
 }
 
 impl Command {
-    pub fn from_reader<'a, R>(input: (&'a mut R, usize)) -> Result<(usize, Self), DekuError>
+    pub fn from_reader<R>(input: (&mut R, usize)) -> Result<(usize, Self), DekuError>
     where
         R: no_std_io::Read + no_std_io::Seek,
     {
         from_reader(input, 0)
     }
 
-    pub fn from_bytes(input: (&'_ [u8], usize)) -> Result<((&'_ [u8], usize), Self), DekuError> {
-        from_bytes(input, 0)
+    pub fn from_bytes(input: (&'_ [u8], usize)) -> Result<((&'_ [u8], usize), Self), Error> {
+        Self::from_bytes_with_dialect(input, Dialect::default())
     }
 
     pub fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
+        self.to_bytes_with_dialect(Dialect::default())
+    }
+
+    pub fn from_bytes_with_dialect(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        let mut cursor = Cursor::new(input.0);
+        let reader = &mut Reader::new(&mut cursor);
+        if input.1 != 0 {
+            reader
+                .skip_bits(input.1)
+                .map_err(|err| Error::from_deku(err, 0))?;
+        }
+
+        let mut actions = Vec::new();
+        let result = read_actions(reader, (0, dialect), &mut actions);
+        let bits_read = position(reader).map_err(|err| Error::from_deku(err, reader.bits_read))?;
+        match result {
+            Ok(()) => Ok((pad_rest(input, bits_read), Self { actions })),
+            Err((start, err)) => Err(Action::locate_error(
+                Error::from_deku(err, bits_read),
+                input.0,
+                start,
+                actions.len(),
+                dialect,
+            )),
+        }
+    }
+
+    /// Decodes like [`Self::from_bytes_with_dialect`], with the data of file data actions decoded
+    /// into custom files, see [`Self::resolve_files`]
+    pub fn from_bytes_with_registry<'a>(
+        input: (&'a [u8], usize),
+        dialect: Dialect,
+        registry: &FileRegistry,
+    ) -> Result<((&'a [u8], usize), Self), Error> {
+        let (rest, mut command) = Self::from_bytes_with_dialect(input, dialect)?;
+        // actions encode to the bytes they were decoded from
+        let mut bit_offset = input.1;
+        for (index, action) in command.actions.iter_mut().enumerate() {
+            Self::resolve_action(action, index, registry)
+                .map_err(|err| err.nested(bit_offset, &[]))?;
+            bit_offset += action
+                .to_bytes_with_dialect(dialect)
+                .map_err(|err| Error::from_deku(err, bit_offset))?
+                .len()
+                * 8;
+        }
+        Ok((rest, command))
+    }
+
+    pub fn to_bytes_with_dialect(&self, dialect: Dialect) -> Result<Vec<u8>, DekuError> {
         let mut out_buf = Vec::new();
         let mut cursor = Cursor::new(&mut out_buf);
         let mut writer = Writer::new(&mut cursor);
-        DekuWriter::to_writer(self, &mut writer, 0)?;
+        DekuWriter::to_writer(self, &mut writer, (0, dialect))?;
         writer.finalize()?;
         Ok(out_buf)
     }
@@ -85,23 +176,56 @@ impl Command {
 
 /// Stub implementation so we can implement DekuContainerWrite
 impl DekuWriter<u32> for Command {
-    fn to_writer<W>(&self, writer: &mut Writer<W>, _: u32) -> Result<(), DekuError>
+    fn to_writer<W>(&self, writer: &mut Writer<W>, length: u32) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        self.to_writer(writer, (length, Dialect::default()))
+    }
+}
+
+impl DekuWriter<(u32, Dialect)> for Command {
+    fn to_writer<W>(
+        &self,
+        writer: &mut Writer<W>,
+        (_, dialect): (u32, Dialect),
+    ) -> Result<(), DekuError>
     where
         W: no_std_io::Write + no_std_io::Seek,
     {
         for action in self.actions.iter() {
-            action.to_writer(writer, ())?;
+            action.to_writer(writer, dialect)?;
         }
         Ok(())
     }
 }
 
 impl Command {
+    /// Creates a command as is, use [`Command::validate`] to check the actions
     pub fn new(actions: Vec<Action>) -> Self {
-        // TODO: validate actions
         Self { actions }
     }
 
+    /// Checks whether the actions are combined in a way that makes sense: tags, forwards, groups,
+    /// queries, chunks and typed file data. An empty list means no problems were found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate::validate(&self.actions)
+    }
+
+    /// Executes the actions on `node` and returns what happened to each of them.
+    ///
+    /// A failing action skips the rest of its group, a query gates the rest of its group or the
+    /// group after it, and a break query that does not match stops the command. With
+    /// `qos.stop_on_error`, a failing action stops the command too.
+    pub fn execute(&self, node: &mut impl Node, qos: &QoS) -> Vec<Outcome> {
+        execute::execute(&self.actions, node, qos)
+    }
+
+    /// Starts building a command, see [`CommandBuilder`]
+    pub fn builder() -> CommandBuilder {
+        CommandBuilder::new()
+    }
+
     pub fn interface_status(&self) -> Option<&InterfaceStatus> {
         for action in self.actions.iter() {
             if let Action::Status(status) = &action {
@@ -127,6 +251,44 @@ impl Command {
         actions
     }
 
+    /// Decodes the data of file data actions into custom files, see [`FileRegistry`]
+    ///
+    /// Stops at the first file the registry fails to decode. The error is located at the start of
+    /// the command, use [`Self::from_bytes_with_registry`] to locate it in the input.
+    pub fn resolve_files(&mut self, registry: &FileRegistry) -> Result<(), Error> {
+        for (index, action) in self.actions.iter_mut().enumerate() {
+            Self::resolve_action(action, index, registry)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_action(
+        action: &mut Action,
+        index: usize,
+        registry: &FileRegistry,
+    ) -> Result<(), Error> {
+        let opcode = action.opcode();
+        if let Action::WriteFileData(file_data)
+        | Action::WriteFileDataFlush(file_data)
+        | Action::ReturnFileData(file_data) = action
+        {
+            let file_id = file_data.offset.file_id;
+            file_data.resolve(registry).map_err(|err| {
+                Error::from_deku(err, 0).nested(
+                    0,
+                    &[
+                        Layer::Action {
+                            index,
+                            opcode: opcode.0,
+                        },
+                        Layer::File(file_id),
+                    ],
+                )
+            })?;
+        }
+        Ok(())
+    }
+
     pub fn request_tag(&self) -> Option<&RequestTag> {
         for action in self.actions.iter() {
             if let Action::RequestTag(operation) = action {
@@ -172,14 +334,14 @@ impl Command {
 }
 
 impl TryFrom<&'_ [u8]> for Command {
-    type Error = DekuError;
+    type Error = Error;
     fn try_from(input: &'_ [u8]) -> Result<Self, Self::Error> {
         let (rest, res) = Self::from_bytes((input, 0))?;
         if !rest.0.is_empty() {
-            return Err(DekuError::Parse({
-                let res = fmt::format(format_args!("Too much data"));
-                Cow::Owned(res)
-            }));
+            return Err(Error::TooMuchData {
+                location: Location::new((input.len() - rest.0.len()) * 8),
+                remaining: rest.0.len(),
+            });
         }
         Ok(res)
     }
@@ -192,7 +354,6 @@ impl TryFrom<Command> for Vec<u8> {
     }
 }
 
-#[cfg(feature = "std")]
 impl Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let tag_str = self
@@ -217,7 +378,7 @@ impl Display for Command {
         f.write_str(&format!("({})", status))?;
 
         let actions = self.actions_without_interface_status();
-        if actions.len() > 0 {
+        if !actions.is_empty() {
             f.write_str("\n\tactions:\n")?;
 
             for action in actions.iter() {
@@ -226,7 +387,7 @@ impl Display for Command {
         }
 
         if let Some(interface_status) = self.interface_status() {
-            if actions.len() > 0 {
+            if !actions.is_empty() {
                 f.write_str("\n")?;
             }
 
@@ -243,8 +404,6 @@ mod test {
     use hex_literal::hex;
 
     use super::*;
-    #[cfg(feature = "_wizzilab")]
-    use crate::transport::GroupCondition;
     use crate::{
         app::{
             action::OpCode,
@@ -254,12 +413,13 @@ mod test {
                 ResponseTagHeader, Status,
             },
         },
+        error::Layer,
         file::File,
         link::AccessClass,
         network::{Address, Addressee, NlsState},
         physical::{Channel, ChannelBand, ChannelClass, ChannelCoding, ChannelHeader},
         session::{Dash7InterfaceStatus, InterfaceStatus},
-        test_tools::test_item,
+        test_tools::{test_item, test_item_ctx},
     };
 
     #[test]
@@ -540,7 +700,6 @@ mod test {
         .is_last_response());
     }
 
-    #[cfg(feature = "subiot_v0_0")]
     #[test]
     fn test_simple_received_return_file_data_command_subiot_v0_0() {
         let data = [
@@ -598,14 +757,14 @@ mod test {
                     ActionHeader::default(),
                     FileOffset::no_offset(0x51),
                     File::Other("Hello world".into()),
+                    OpCode::RETURN_FILE_DATA,
                 )),
             ],
         };
 
-        test_item(item, &data);
+        test_item_ctx(item, &data, (0, Dialect::SubIoTV0_0));
     }
 
-    #[cfg(feature = "subiot_v0_0")]
     #[test]
     fn test_simple_received_return_file_data_command_with_tag_request() {
         let data = [
@@ -639,6 +798,7 @@ mod test {
                         end_of_packet: true,
                     },
                     id: 25,
+                    opcode: OpCode::REQUEST_TAG,
                 }),
                 Action::Status(
                     Status::Interface(
@@ -671,11 +831,12 @@ mod test {
                     ActionHeader::default(),
                     FileOffset::no_offset(0x51),
                     File::Other("Hello world".into()),
+                    OpCode::RETURN_FILE_DATA,
                 )),
             ],
         };
 
-        test_item(item, &data);
+        test_item_ctx(item, &data, (0, Dialect::SubIoTV0_0));
     }
 
     #[test]
@@ -712,10 +873,6 @@ mod test {
                             sequence_number: 0,
                             response_timeout: 384.into(),
                             addressee: Addressee::new(
-                                #[cfg(feature = "_wizzilab")]
-                                false,
-                                #[cfg(feature = "_wizzilab")]
-                                GroupCondition::Any,
                                 Address::UId(4123107267735781422u64),
                                 NlsState::None,
                                 AccessClass::new(0, 1),
@@ -748,6 +905,82 @@ mod test {
             ],
         };
 
-        test_item(item, data);
+        test_item_ctx(item, data, (0, Dialect::SpecV1_2));
+    }
+
+    #[test]
+    fn test_command_errors() {
+        // write file data to file 0x40, without its length
+        let err = Command::try_from(&hex!("01 00 00 08 04 40 00")[..]).unwrap_err();
+        assert!(matches!(err, Error::Incomplete { .. }));
+        assert_eq!(
+            err.layers(),
+            &[
+                Layer::Action {
+                    index: 1,
+                    opcode: 0x04
+                },
+                Layer::File(0x40)
+            ]
+        );
+
+        let err = Command::from_bytes_with_dialect((&hex!("01 00 00 08 3E"), 0), Dialect::SpecV1_2)
+            .unwrap_err();
+        assert_eq!(err.bit_offset(), 34);
+        assert_eq!(
+            err.to_string(),
+            "invalid opcode at byte 4, bit 2 in action 1 (opcode 0x3E): expected an opcode supported by SpecV1_2, found 0x3E"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_command_serde() {
+        let item = Command {
+            actions: vec![
+                Action::RequestTag(RequestTag {
+                    header: RequestTagHeader {
+                        end_of_packet: true,
+                    },
+                    opcode: OpCode::REQUEST_TAG,
+                    id: 9,
+                }),
+                Action::Forward(Forward::new(
+                    false,
+                    InterfaceConfiguration::Dash7(
+                        crate::app::interface::Dash7InterfaceConfiguration {
+                            dormant_session_timeout: 384.into(),
+                            addressee: Addressee::new(
+                                Address::UId(4123107267735781422u64),
+                                NlsState::None,
+                                AccessClass::new(0, 1),
+                            ),
+                            ..Default::default()
+                        },
+                    ),
+                )),
+                Action::ReadFileData(ReadFileData {
+                    header: ActionHeader {
+                        group: false,
+                        response: true,
+                    },
+                    opcode: OpCode::READ_FILE_DATA,
+                    offset: FileOffset {
+                        file_id: 0,
+                        offset: 0u32.into(),
+                    },
+                    length: 8u32.into(),
+                }),
+            ],
+        };
+
+        let json = serde_json::to_value(&item).unwrap();
+        let forward = &json["actions"][1]["Forward"]["configuration"]["Dash7"];
+        assert_eq!(forward["dormant_session_timeout"], 384);
+        assert_eq!(forward["addressee"]["address"]["UId"], "393838370039002E");
+        assert_eq!(forward["qos"]["response_mode"], "No");
+        assert_eq!(json["actions"][2]["ReadFileData"]["length"], 8);
+
+        assert_eq!(serde_json::from_value::<Command>(json).unwrap(), item);
     }
 }
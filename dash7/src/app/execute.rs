@@ -0,0 +1,400 @@
+use alloc::vec::Vec;
+
+use crate::session::QoS;
+
+use super::{
+    action::Action,
+    operation::StatusCode,
+    query::{combine, FileAccess, Query},
+};
+
+/// What happened, or will happen, to an action when a node executes its command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The action was executed. It failed when the status is an error.
+    Executed(StatusCode),
+
+    /// The query was evaluated and did or did not match
+    Evaluated(bool),
+
+    /// Skipped because a query before it did not match
+    SkippedByQuery,
+
+    /// Skipped because an earlier action of its group failed
+    SkippedByGroup,
+
+    /// Not executed because a break query did not match, or an action failed and the QoS asks to
+    /// stop on error
+    Stopped,
+
+    /// Sent on by an earlier forward action, instead of being executed
+    Forwarded,
+}
+
+/// A node that executes the actions of a command, on top of the files queries are evaluated against
+pub trait Node: FileAccess {
+    /// Executes the `index`th action of the command. Queries and logic are handled by the caller.
+    fn perform(&mut self, index: usize, action: &Action) -> Result<(), StatusCode>;
+
+    /// Verifies the checksum of a file, as described by `query`. Not supported by default.
+    fn verify_checksum(&mut self, _query: &Query) -> Result<bool, StatusCode> {
+        Err(StatusCode::UNKNOWN_OPERATION)
+    }
+}
+
+/// End, exclusive, of the group that starts at `start`. Logic belongs to the queries after it.
+fn group_end(actions: &[Action], start: usize) -> usize {
+    let mut end = start;
+    while end + 1 < actions.len() {
+        let action = &actions[end];
+        let grouped = action.header().is_some_and(|header| header.group);
+        if !grouped && !matches!(action, Action::Logic(_)) {
+            break;
+        }
+        end += 1;
+    }
+    end + 1
+}
+
+fn evaluate(action: &Action, node: &mut impl Node) -> Result<bool, StatusCode> {
+    match action {
+        Action::ActionQuery(query) | Action::BreakQuery(query) => query.query.evaluate(node),
+        Action::VerifyChecksum(query) => node.verify_checksum(&query.query),
+        _ => unreachable!("only queries are evaluated"),
+    }
+}
+
+/// Executes `actions` on `node`, see [`super::command::Command::execute`]
+pub(crate) fn execute(actions: &[Action], node: &mut impl Node, qos: &QoS) -> Vec<Outcome> {
+    let mut outcomes = Vec::with_capacity(actions.len());
+    // result of the queries that make up a whole group, which gate the next group
+    let mut gate = None;
+
+    let mut start = 0;
+    while start < actions.len() {
+        let end = group_end(actions, start);
+        if gate.take() == Some(false) {
+            outcomes.resize(end, Outcome::SkippedByQuery);
+            start = end;
+            continue;
+        }
+
+        let mut logic = None;
+        let mut index = start;
+        while index < end {
+            let action = &actions[index];
+            if let Action::Logic(operation) = action {
+                logic = Some(&operation.logic);
+                outcomes.push(Outcome::Executed(StatusCode::OK));
+                index += 1;
+                continue;
+            }
+
+            if action.is_query() {
+                let run_end = (index..end)
+                    .find(|idx| !actions[*idx].is_query())
+                    .unwrap_or(end);
+
+                let mut results = Vec::new();
+                for query in &actions[index..run_end] {
+                    match evaluate(query, node) {
+                        Ok(result) => {
+                            results.push(result);
+                            outcomes.push(Outcome::Evaluated(result));
+                        }
+                        Err(status) => {
+                            outcomes.push(Outcome::Executed(status));
+                            break;
+                        }
+                    }
+                }
+                if results.len() < run_end - index {
+                    // a query could not be evaluated, which fails the group like any other action
+                    if qos.stop_on_error {
+                        outcomes.resize(actions.len(), Outcome::Stopped);
+                        return outcomes;
+                    }
+                    outcomes.resize(end, Outcome::SkippedByGroup);
+                    break;
+                }
+
+                let matched = combine(logic.take(), results);
+                if matches!(action, Action::BreakQuery(_)) {
+                    if !matched {
+                        outcomes.resize(actions.len(), Outcome::Stopped);
+                        return outcomes;
+                    }
+                } else if run_end == end {
+                    gate = Some(matched);
+                } else if !matched {
+                    outcomes.resize(end, Outcome::SkippedByQuery);
+                    break;
+                }
+                index = run_end;
+                continue;
+            }
+
+            match node.perform(index, action) {
+                Ok(()) => outcomes.push(Outcome::Executed(StatusCode::OK)),
+                Err(status) => {
+                    outcomes.push(Outcome::Executed(status));
+                    if qos.stop_on_error {
+                        outcomes.resize(actions.len(), Outcome::Stopped);
+                        return outcomes;
+                    }
+                    outcomes.resize(end, Outcome::SkippedByGroup);
+                    break;
+                }
+            }
+            if matches!(action, Action::Forward(_) | Action::IndirectForward(_)) {
+                outcomes.resize(actions.len(), Outcome::Forwarded);
+                return outcomes;
+            }
+            index += 1;
+        }
+        start = end;
+    }
+
+    outcomes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::{
+        action::OpCode,
+        command::Command,
+        interface::InterfaceConfiguration,
+        operation::{ActionHeader, ActionQuery, FileOffset, LogicOp},
+        query::NonVoid,
+    };
+
+    /// Holds a single file, and fails the actions at the given indices
+    struct TestNode {
+        failing: Vec<usize>,
+        performed: Vec<usize>,
+    }
+
+    impl FileAccess for TestNode {
+        fn read(&self, file: &FileOffset, length: usize) -> Result<Vec<u8>, StatusCode> {
+            match file.file_id {
+                0x40 => Ok([1, 2].into_iter().take(length).collect()),
+                _ => Err(StatusCode::FILE_ID_MISSING),
+            }
+        }
+    }
+
+    impl Node for TestNode {
+        fn perform(&mut self, index: usize, _action: &Action) -> Result<(), StatusCode> {
+            self.performed.push(index);
+            match self.failing.contains(&index) {
+                true => Err(StatusCode::UNKNOWN_ERROR),
+                false => Ok(()),
+            }
+        }
+    }
+
+    fn execute(command: Command, failing: &[usize], qos: &QoS) -> (Vec<Outcome>, Vec<usize>) {
+        let mut node = TestNode {
+            failing: failing.to_vec(),
+            performed: vec![],
+        };
+        let outcomes = command.execute(&mut node, qos);
+        (outcomes, node.performed)
+    }
+
+    /// Matches when the file holds `length` bytes
+    fn query(length: u32) -> Query {
+        Query::NonVoid(NonVoid {
+            length: length.into(),
+            file: FileOffset::no_offset(0x40),
+        })
+    }
+
+    const OK: Outcome = Outcome::Executed(StatusCode::OK);
+    const FAILED: Outcome = Outcome::Executed(StatusCode::UNKNOWN_ERROR);
+
+    #[test]
+    fn test_groups() {
+        let command = || {
+            Command::builder()
+                .group(|group| group.nop().nop().nop())
+                .nop()
+                .build()
+                .unwrap()
+        };
+        assert_eq!(
+            execute(command(), &[1], &QoS::default()),
+            (vec![OK, FAILED, Outcome::SkippedByGroup, OK], vec![0, 1, 3])
+        );
+
+        let qos = QoS {
+            stop_on_error: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            execute(command(), &[1], &qos),
+            (
+                vec![OK, FAILED, Outcome::Stopped, Outcome::Stopped],
+                vec![0, 1]
+            )
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let command = |length| {
+            Command::builder()
+                .query(query(length))
+                .group(|group| group.nop().nop())
+                .nop()
+                .build()
+                .unwrap()
+        };
+        assert_eq!(
+            execute(command(2), &[], &QoS::default()).0,
+            vec![Outcome::Evaluated(true), OK, OK, OK]
+        );
+        assert_eq!(
+            execute(command(3), &[], &QoS::default()).0,
+            vec![
+                Outcome::Evaluated(false),
+                Outcome::SkippedByQuery,
+                Outcome::SkippedByQuery,
+                OK
+            ]
+        );
+
+        // a query inside a group gates the rest of that group
+        let command = Command::builder()
+            .group(|group| group.query(query(3)).nop())
+            .nop()
+            .build()
+            .unwrap();
+        assert_eq!(
+            execute(command, &[], &QoS::default()).0,
+            vec![Outcome::Evaluated(false), Outcome::SkippedByQuery, OK]
+        );
+
+        // a query on a missing file fails its group
+        let command = Command::builder()
+            .group(|group| {
+                group
+                    .query(Query::NonVoid(NonVoid {
+                        length: 1u32.into(),
+                        file: FileOffset::no_offset(0x41),
+                    }))
+                    .nop()
+            })
+            .nop()
+            .build()
+            .unwrap();
+        assert_eq!(
+            execute(command, &[], &QoS::default()).0,
+            vec![
+                Outcome::Executed(StatusCode::FILE_ID_MISSING),
+                Outcome::SkippedByGroup,
+                OK
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logic() {
+        let command = |logic: Option<LogicOp>| {
+            let builder = match logic {
+                Some(logic) => Command::builder().logic(logic),
+                None => Command::builder(),
+            };
+            builder
+                .group(|group| group.query(query(3)).query(query(2)))
+                .nop()
+                .build()
+                .unwrap()
+        };
+        let evaluated = [Outcome::Evaluated(false), Outcome::Evaluated(true)];
+
+        assert_eq!(
+            execute(command(None), &[], &QoS::default()).0,
+            [&evaluated[..], &[Outcome::SkippedByQuery]].concat()
+        );
+        assert_eq!(
+            execute(command(Some(LogicOp::Or)), &[], &QoS::default()).0,
+            [&[OK], &evaluated[..], &[OK]].concat()
+        );
+        assert_eq!(
+            execute(command(Some(LogicOp::Nand)), &[], &QoS::default()).0,
+            [&[OK], &evaluated[..], &[OK]].concat()
+        );
+        assert_eq!(
+            execute(command(Some(LogicOp::Nor)), &[], &QoS::default()).0,
+            [&[OK], &evaluated[..], &[Outcome::SkippedByQuery]].concat()
+        );
+    }
+
+    #[test]
+    fn test_break_query() {
+        let command = |length| {
+            Command::builder()
+                .nop()
+                .break_query(query(length))
+                .nop()
+                .nop()
+                .build()
+                .unwrap()
+        };
+        assert_eq!(
+            execute(command(2), &[], &QoS::default()).0,
+            vec![OK, Outcome::Evaluated(true), OK, OK]
+        );
+        assert_eq!(
+            execute(command(3), &[], &QoS::default()),
+            (
+                vec![
+                    OK,
+                    Outcome::Evaluated(false),
+                    Outcome::Stopped,
+                    Outcome::Stopped
+                ],
+                vec![0]
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let command = Command::builder()
+            .group(|group| {
+                group
+                    .action(Action::VerifyChecksum(ActionQuery {
+                        header: ActionHeader::default(),
+                        opcode: OpCode::VERIFY_CHECKSUM,
+                        query: query(1),
+                    }))
+                    .nop()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            execute(command, &[], &QoS::default()).0,
+            vec![
+                Outcome::Executed(StatusCode::UNKNOWN_OPERATION),
+                Outcome::SkippedByGroup
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward() {
+        let command = Command::builder()
+            .nop()
+            .forward(InterfaceConfiguration::Host)
+            .read_file(0x40, 0, 1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            execute(command, &[], &QoS::default()),
+            (vec![OK, OK, Outcome::Forwarded], vec![0, 1])
+        );
+    }
+}
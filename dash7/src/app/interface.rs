@@ -1,12 +1,16 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
-use crate::{network::Addressee, session::QoS, types::VarInt};
+use crate::{dialect::Dialect, network::Addressee, session::QoS, types::VarInt};
 
 /// Section 9.2.1
 ///
 /// Parameters to handle the sending of a request.
 // ALP SPEC: Add link to D7a section
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::default()")]
 pub struct Dash7InterfaceConfiguration {
     pub qos: QoS,
 
@@ -24,15 +28,21 @@ pub struct Dash7InterfaceConfiguration {
 
     /// Response Execution Delay in Compressed Format, unit is in milliseconds.
     ///
-    /// Time given to the target to process the request.
-    #[cfg(not(feature = "_subiot"))]
+    /// Time given to the target to process the request. Not present in Sub-IoT
+    #[deku(
+        skip,
+        cond = "!dialect.has_execution_delay_timeout()",
+        default = "VarInt::default()"
+    )]
     pub execution_delay_timeout: VarInt,
 
     /// Address of the target.
+    #[deku(ctx = "dialect")]
     pub addressee: Addressee,
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LoRaWANInterfaceConfiguration {
     /// Automatic data rate enabled
     #[deku(pad_bits_before = "5", bits = 1)]
@@ -46,6 +56,7 @@ pub struct LoRaWANInterfaceConfiguration {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LoRaWANOTAAInterfaceConfiguration {
     pub base: LoRaWANInterfaceConfiguration,
 
@@ -60,6 +71,7 @@ pub struct LoRaWANOTAAInterfaceConfiguration {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LoRaWANABPInterfaceConfiguration {
     pub base: LoRaWANInterfaceConfiguration,
 
@@ -75,7 +87,12 @@ pub struct LoRaWANABPInterfaceConfiguration {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(id_type = "u8")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    id_type = "u8",
+    ctx = "dialect: Dialect",
+    ctx_default = "Dialect::default()"
+)]
 pub enum InterfaceConfiguration {
     #[default]
     #[deku(id = "0x00")]
@@ -91,14 +108,19 @@ pub enum InterfaceConfiguration {
     LoRaWanOTAA(LoRaWANOTAAInterfaceConfiguration),
 
     #[deku(id = "0xD7")]
-    Dash7(Dash7InterfaceConfiguration),
+    Dash7(#[deku(ctx = "dialect")] Dash7InterfaceConfiguration),
 
     #[deku(id_pat = "_")]
     Unknown(u8),
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(id_type = "u8")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    id_type = "u8",
+    ctx = "dialect: Dialect",
+    ctx_default = "Dialect::default()"
+)]
 pub enum IndirectInterface {
     #[default]
     #[deku(id = "0x00")]
@@ -108,7 +130,7 @@ pub enum IndirectInterface {
     Serial,
 
     #[deku(id = "0xD7")]
-    Dash7(Addressee),
+    Dash7(#[deku(ctx = "dialect")] Addressee),
 
     #[deku(id_pat = "_")]
     Unknown(u8),
@@ -117,178 +139,171 @@ pub enum IndirectInterface {
 #[cfg(test)]
 mod test {
     use super::*;
-    #[cfg(feature = "_wizzilab")]
-    use crate::transport::GroupCondition;
     use crate::{
         link::AccessClass,
         network::{Address, NlsState},
         session::{ResponseMode, RetryMode},
-        test_tools::test_item,
+        test_tools::{test_item, test_item_ctx},
+        transport::GroupCondition,
     };
     use hex_literal::hex;
 
+    fn configuration(dialect: Dialect, addressee: Addressee) -> Dash7InterfaceConfiguration {
+        Dash7InterfaceConfiguration {
+            qos: QoS {
+                retry_mode: RetryMode::No,
+                response_mode: ResponseMode::Any,
+                stop_on_error: false,
+                record: false,
+            },
+            dormant_session_timeout: 0x20.into(),
+            execution_delay_timeout: if dialect.has_execution_delay_timeout() {
+                0x34.into()
+            } else {
+                VarInt::default()
+            },
+            addressee,
+        }
+    }
+
     #[test]
     fn test_dash7_interface_configuration() {
+        let addressee = Addressee::new(
+            Address::VId(0xABCD),
+            NlsState::AesCcm32([1, 2, 3, 4, 5]),
+            AccessClass::new(0x0F, 0x0F),
+        );
+
         test_item(
-            Dash7InterfaceConfiguration {
-                qos: QoS {
-                    retry_mode: RetryMode::No,
-                    response_mode: ResponseMode::Any,
-                    stop_on_error: false,
-                    record: false,
-                },
-                dormant_session_timeout: 0x20.into(),
-
-                #[cfg(not(feature = "_subiot"))]
-                execution_delay_timeout: 0x34.into(),
-
-                addressee: Addressee::new(
-                    #[cfg(feature = "_wizzilab")]
-                    false,
-                    #[cfg(feature = "_wizzilab")]
-                    GroupCondition::Any,
-                    Address::VId(0xABCD),
-                    NlsState::AesCcm32([1, 2, 3, 4, 5]),
-                    AccessClass::new(0x0F, 0x0F),
-                ),
+            configuration(Dialect::default(), addressee.clone()),
+            if Dialect::default().has_execution_delay_timeout() {
+                &hex!("02 28 2D 37 FF ABCD 0102030405")
+            } else {
+                &hex!("02 28 37 FF ABCD 0102030405")
             },
-            #[cfg(not(feature = "_subiot"))]
-            &hex!("02 28 2D 37 FF ABCD 0102030405"),
-            #[cfg(feature = "_subiot")]
-            &hex!("02 28 37 FF ABCD 0102030405"),
-        )
+        );
+
+        for (dialect, data) in [
+            (
+                Dialect::SpecV1_2,
+                &hex!("02 28 2D 37 FF ABCD 0102030405")[..],
+            ),
+            (
+                Dialect::SubIoTV0_1,
+                &hex!("02 28 37 FF ABCD 0102030405")[..],
+            ),
+            (
+                Dialect::WizzilabV5_3,
+                &hex!("02 28 2D 37 FF ABCD 0102030405")[..],
+            ),
+        ] {
+            test_item_ctx(configuration(dialect, addressee.clone()), data, dialect);
+        }
     }
 
     #[test]
     fn test_dash7_interface_configuration_with_address_nbid() {
-        test_item(
-            Dash7InterfaceConfiguration {
-                qos: QoS {
-                    retry_mode: RetryMode::No,
-                    response_mode: ResponseMode::Any,
-                    stop_on_error: false,
-                    record: false,
-                },
-                dormant_session_timeout: 0x20.into(),
-
-                #[cfg(not(feature = "_subiot"))]
-                execution_delay_timeout: 0x34.into(),
-
-                addressee: Addressee::new(
-                    #[cfg(feature = "_wizzilab")]
-                    true,
-                    #[cfg(feature = "_wizzilab")]
-                    GroupCondition::NotEqual,
-                    Address::NbId(0x15.into()),
-                    NlsState::None,
-                    AccessClass::default(),
-                ),
-            },
-            #[cfg(feature = "_spec")]
+        let mut addressee = Addressee::new(
+            Address::NbId(0x15.into()),
+            NlsState::None,
+            AccessClass::default(),
+        );
+
+        test_item_ctx(
+            configuration(Dialect::SpecV1_2, addressee.clone()),
             &hex!("02 28 2D 00 00 15"),
-            #[cfg(feature = "_subiot")]
+            Dialect::SpecV1_2,
+        );
+        test_item_ctx(
+            configuration(Dialect::SubIoTV0_1, addressee.clone()),
             &hex!("02 28 00 00 15"),
-            #[cfg(feature = "_wizzilab")]
+            Dialect::SubIoTV0_1,
+        );
+        addressee.use_vid = true;
+        addressee.group_condition = GroupCondition::NotEqual;
+        test_item_ctx(
+            configuration(Dialect::WizzilabV5_3, addressee),
             &hex!("02 28 2D 48 00 15"),
-        )
+            Dialect::WizzilabV5_3,
+        );
     }
+
     #[test]
     fn test_dash7_interface_configuration_with_address_noid() {
-        test_item(
-            Dash7InterfaceConfiguration {
-                qos: QoS {
-                    retry_mode: RetryMode::No,
-                    response_mode: ResponseMode::Any,
-                    stop_on_error: false,
-                    record: false,
-                },
-                dormant_session_timeout: 0x20.into(),
-
-                #[cfg(not(feature = "_subiot"))]
-                execution_delay_timeout: 0x34.into(),
-
-                addressee: Addressee::new(
-                    #[cfg(feature = "_wizzilab")]
-                    false,
-                    #[cfg(feature = "_wizzilab")]
-                    GroupCondition::Equal,
-                    Address::NoId,
-                    NlsState::AesCbcMac128([0x0A, 0x0B, 0x0C, 0x0D, 0x0E]),
-                    AccessClass::new(0x02, 0x04),
-                ),
-            },
-            #[cfg(feature = "_spec")]
+        let mut addressee = Addressee::new(
+            Address::NoId,
+            NlsState::AesCbcMac128([0x0A, 0x0B, 0x0C, 0x0D, 0x0E]),
+            AccessClass::new(0x02, 0x04),
+        );
+
+        test_item_ctx(
+            configuration(Dialect::SpecV1_2, addressee.clone()),
             &hex!("02 28 2D 12 24 0A 0B 0C 0D 0E"),
-            #[cfg(feature = "_subiot")]
+            Dialect::SpecV1_2,
+        );
+        test_item_ctx(
+            configuration(Dialect::SubIoTV0_1, addressee.clone()),
             &hex!("02 28 12 24 0A 0B 0C 0D 0E"),
-            #[cfg(feature = "_wizzilab")]
+            Dialect::SubIoTV0_1,
+        );
+        addressee.group_condition = GroupCondition::Equal;
+        test_item_ctx(
+            configuration(Dialect::WizzilabV5_3, addressee),
             &hex!("02 28 2D 92 24 0A 0B 0C 0D 0E"),
-        )
+            Dialect::WizzilabV5_3,
+        );
     }
 
     #[test]
     fn test_dash7_interface_configuration_with_address_uid() {
-        test_item(
-            Dash7InterfaceConfiguration {
-                qos: QoS {
-                    retry_mode: RetryMode::No,
-                    response_mode: ResponseMode::Any,
-                    stop_on_error: false,
-                    record: false,
-                },
-                dormant_session_timeout: 0x20.into(),
-
-                #[cfg(not(feature = "_subiot"))]
-                execution_delay_timeout: 0x34.into(),
-
-                addressee: Addressee::new(
-                    #[cfg(feature = "_wizzilab")]
-                    true,
-                    #[cfg(feature = "_wizzilab")]
-                    GroupCondition::GreaterThan,
-                    Address::UId(0x0001020304050607),
-                    NlsState::AesCcm64([0xA1, 0xA2, 0xA3, 0xA4, 0xA5]),
-                    AccessClass::new(0x04, 0x08),
-                ),
-            },
-            #[cfg(feature = "_spec")]
+        let mut addressee = Addressee::new(
+            Address::UId(0x0001020304050607),
+            NlsState::AesCcm64([0xA1, 0xA2, 0xA3, 0xA4, 0xA5]),
+            AccessClass::new(0x04, 0x08),
+        );
+
+        test_item_ctx(
+            configuration(Dialect::SpecV1_2, addressee.clone()),
             &hex!("02 28 2D 26 48 0001020304050607 A1A2A3A4A5"),
-            #[cfg(feature = "_subiot")]
+            Dialect::SpecV1_2,
+        );
+        test_item_ctx(
+            configuration(Dialect::SubIoTV0_1, addressee.clone()),
             &hex!("02 28 26 48 0001020304050607 A1A2A3A4A5"),
-            #[cfg(feature = "_wizzilab")]
+            Dialect::SubIoTV0_1,
+        );
+        addressee.use_vid = true;
+        addressee.group_condition = GroupCondition::GreaterThan;
+        test_item_ctx(
+            configuration(Dialect::WizzilabV5_3, addressee),
             &hex!("02 28 2D EE 48 0001020304050607 A1A2A3A4A5"),
-        )
+            Dialect::WizzilabV5_3,
+        );
     }
 
     #[test]
     fn test_dash7_interface_configuration_with_address_vid() {
-        test_item(
-            Dash7InterfaceConfiguration {
-                qos: QoS {
-                    retry_mode: RetryMode::No,
-                    response_mode: ResponseMode::Any,
-                    stop_on_error: false,
-                    record: false,
-                },
-                dormant_session_timeout: 0x20.into(),
-
-                #[cfg(not(feature = "_subiot"))]
-                execution_delay_timeout: 0x34.into(),
-
-                addressee: Addressee::new(
-                    #[cfg(feature = "_wizzilab")]
-                    false,
-                    #[cfg(feature = "_wizzilab")]
-                    GroupCondition::Any,
-                    Address::VId(0xABCD),
-                    NlsState::AesCcm32([0xA1, 0xA2, 0xA3, 0xA4, 0xA5]),
-                    AccessClass::new(0x0F, 0x0F),
-                ),
-            },
-            #[cfg(not(feature = "_subiot"))]
-            &hex!("02 28 2D 37 FF AB CD A1A2A3A4A5"),
-            #[cfg(feature = "_subiot")]
-            &hex!("02 28 37 FF AB CD A1A2A3A4A5"),
-        )
+        let addressee = Addressee::new(
+            Address::VId(0xABCD),
+            NlsState::AesCcm32([0xA1, 0xA2, 0xA3, 0xA4, 0xA5]),
+            AccessClass::new(0x0F, 0x0F),
+        );
+
+        for (dialect, data) in [
+            (
+                Dialect::SpecV1_2,
+                &hex!("02 28 2D 37 FF AB CD A1A2A3A4A5")[..],
+            ),
+            (
+                Dialect::SubIoTV0_0,
+                &hex!("02 28 37 FF AB CD A1A2A3A4A5")[..],
+            ),
+            (
+                Dialect::WizzilabV5_3,
+                &hex!("02 28 2D 37 FF AB CD A1A2A3A4A5")[..],
+            ),
+        ] {
+            test_item_ctx(configuration(dialect, addressee.clone()), data, dialect);
+        }
     }
 }
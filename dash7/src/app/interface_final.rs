@@ -1,9 +1,14 @@
+use alloc::vec::Vec;
+
 use deku::no_std_io::{Cursor, Read, Seek, Write};
 use deku::prelude::*;
 
 use super::action::OpCode;
 use super::operation::{Length, Status};
-use crate::session::{InterfaceFinalStatus, InterfaceTxStatus};
+use crate::{
+    dialect::Dialect,
+    session::{InterfaceFinalStatus, InterfaceTxStatus},
+};
 
 fn read_length_prefixed<'a, R, T, L, C>(reader: &mut Reader<R>, ctx: C) -> Result<T, DekuError>
 where
@@ -21,9 +26,10 @@ where
     W: Write + Seek,
 {
     // first write the whole item into a byte buffer
-    let mut out_buf_cur = Cursor::new(Vec::new());
+    let mut out_buf = Vec::new();
+    let mut out_buf_cur = Cursor::new(&mut out_buf);
     let mut tmp_writer = Writer::new(&mut out_buf_cur);
-    let _ = item.to_writer(&mut tmp_writer, ctx)?;
+    item.to_writer(&mut tmp_writer, ctx)?;
     let _ = tmp_writer.finalize();
 
     // get the length of it
@@ -38,6 +44,7 @@ where
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceFinalStatusOperation {
     pub interface_id: u8,
 
@@ -57,9 +64,9 @@ impl From<InterfaceFinalStatus> for InterfaceFinalStatusOperation {
     }
 }
 
-impl Into<Status> for InterfaceFinalStatusOperation {
-    fn into(self) -> Status {
-        Status::InterfaceFinal(self).into()
+impl From<InterfaceFinalStatusOperation> for Status {
+    fn from(operation: InterfaceFinalStatusOperation) -> Self {
+        Status::InterfaceFinal(operation)
     }
 }
 
@@ -83,11 +90,12 @@ impl InterfaceFinalStatus {
             InterfaceFinalStatus::Other(val) => val.len() as u32,
             _ => 0,
         };
-        return write_length_prefixed(writer, status, (interface_id, vec_size));
+        write_length_prefixed(writer, status, (interface_id, vec_size))
     }
 }
 
 #[derive(DekuRead, DekuWrite, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum TxStatusType {
     #[deku(id = "1")]
@@ -95,32 +103,49 @@ pub enum TxStatusType {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
-#[deku(ctx = "_opcode: OpCode")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "_opcode: OpCode, dialect: Dialect")]
 pub struct TxStatusOperation {
-    #[deku(update = "self.status.deku_id().unwrap()", pad_bits_after = "6")]
+    #[deku(update = "self.status.deku_id().unwrap()")]
     status_type: TxStatusType,
 
     #[deku(writer = "_opcode.to_writer(deku::writer, ())")]
     pub opcode: OpCode,
 
-    #[deku(ctx = "*status_type")]
+    #[deku(ctx = "*status_type, dialect")]
     pub status: TxStatus,
 }
 
+impl From<TxStatus> for TxStatusOperation {
+    fn from(status: TxStatus) -> Self {
+        Self {
+            status_type: status.deku_id().unwrap(),
+            opcode: OpCode::TX_STATUS,
+            status,
+        }
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
-#[deku(ctx = "status_type: TxStatusType", id = "status_type")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "status_type: TxStatusType, dialect: Dialect",
+    id = "status_type"
+)]
 pub enum TxStatus {
     #[deku(id = "TxStatusType::Interface")]
-    Interface(InterfaceTxStatusOperation),
+    Interface(#[deku(ctx = "dialect")] InterfaceTxStatusOperation),
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::WizzilabV5_3")]
 pub struct InterfaceTxStatusOperation {
     pub interface_id: u8,
 
     #[deku(
-        reader = "InterfaceTxStatus::read(deku::reader, *interface_id)",
-        writer = "InterfaceTxStatus::write(deku::writer, &self.status, self.interface_id)"
+        reader = "InterfaceTxStatus::read(deku::reader, *interface_id, dialect)",
+        writer = "InterfaceTxStatus::write(deku::writer, &self.status, self.interface_id, dialect)"
     )]
     pub status: InterfaceTxStatus,
 }
@@ -134,26 +159,35 @@ impl From<InterfaceTxStatus> for InterfaceTxStatusOperation {
     }
 }
 
+impl From<InterfaceTxStatusOperation> for TxStatus {
+    fn from(operation: InterfaceTxStatusOperation) -> Self {
+        TxStatus::Interface(operation)
+    }
+}
+
 impl InterfaceTxStatus {
     pub fn read<'a, R>(
         reader: &mut Reader<'a, R>,
         interface_id: u8,
+        dialect: Dialect,
     ) -> Result<InterfaceTxStatus, DekuError>
     where
         R: Read + Seek,
     {
-        return read_length_prefixed(reader, interface_id);
+        let length = <Length as DekuReader<'_, _>>::from_reader_with_ctx(reader, ())?;
+        InterfaceTxStatus::from_reader_with_ctx(reader, (interface_id, length.into(), dialect))
     }
 
     pub fn write<W: Write + Seek>(
         writer: &mut Writer<W>,
         status: &InterfaceTxStatus,
         interface_id: u8,
+        dialect: Dialect,
     ) -> Result<(), DekuError> {
         let vec_size = match status {
             InterfaceTxStatus::Other(val) => val.len() as u32,
             _ => 0,
         };
-        return write_length_prefixed(writer, status, (interface_id, vec_size));
+        write_length_prefixed(writer, status, (interface_id, vec_size, dialect))
     }
 }
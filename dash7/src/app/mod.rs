@@ -1,14 +1,47 @@
 /// Operations used to build the ALP Actions
+#[cfg(feature = "alloc")]
 pub mod operation;
 
+#[cfg(feature = "alloc")]
 pub mod interface;
 
 /// ALP basic Actions used to build Commands
+#[cfg(feature = "alloc")]
 pub mod action;
 
+#[cfg(feature = "alloc")]
 pub mod query;
 
+#[cfg(feature = "alloc")]
 pub mod command;
 
-#[cfg(feature = "_wizzilab")]
+/// Checks on how the actions of a command are combined
+#[cfg(feature = "alloc")]
+pub mod validate;
+
+/// Execution of ALP commands, following group and query semantics
+#[cfg(feature = "alloc")]
+pub mod execute;
+
+/// Reassembly and splitting of chunked commands
+#[cfg(feature = "alloc")]
+pub mod chunk;
+
+/// Correlation of requests and responses through their tags
+#[cfg(feature = "alloc")]
+pub mod tag;
+
+/// Fluent construction of ALP commands
+#[cfg(feature = "alloc")]
+pub mod builder;
+
+/// Wizzilab specific operations
+#[cfg(feature = "alloc")]
 mod interface_final;
+
+/// ALP operation codes
+mod opcode;
+pub use opcode::OpCode;
+
+/// Zero-copy decoding and fixed-capacity encoding of ALP commands, without an allocator
+pub mod borrowed;
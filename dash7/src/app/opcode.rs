@@ -0,0 +1,67 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use deku::{
+    no_std_io::{Seek, Write},
+    prelude::*,
+};
+
+// ===============================================================================
+// OpCodes
+// ===============================================================================
+
+#[cfg_attr(feature = "alloc", derive(DekuRead, DekuWrite))]
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpCode(#[cfg_attr(feature = "alloc", deku(bits = 6))] pub u8);
+
+impl OpCode {
+    pub const NOP: OpCode = OpCode(0u8);
+
+    // Read
+    pub const READ_FILE_DATA: OpCode = OpCode(0x01u8);
+    pub const READ_FILE_PROPERTIES: OpCode = OpCode(0x02u8);
+
+    // Write
+    pub const WRITE_FILE_DATA: OpCode = OpCode(0x04u8);
+    pub const WRITE_FILE_DATA_FLUSH: OpCode = OpCode(0x05u8);
+    pub const WRITE_FILE_PROPERTIES: OpCode = OpCode(0x06u8);
+    pub const ACTION_QUERY: OpCode = OpCode(0x08u8);
+    pub const BREAK_QUERY: OpCode = OpCode(0x09u8);
+    pub const PERMISSION_REQUEST: OpCode = OpCode(0x0au8);
+    pub const VERIFY_CHECKSUM: OpCode = OpCode(0x0bu8);
+
+    // Management
+    pub const EXIST_FILE: OpCode = OpCode(16u8);
+    pub const CREATE_NEW_FILE: OpCode = OpCode(17u8);
+    pub const DELETE_FILE: OpCode = OpCode(18u8);
+    pub const RESTORE_FILE: OpCode = OpCode(19u8);
+    pub const FLUSH_FILE: OpCode = OpCode(20u8);
+    pub const COPY_FILE: OpCode = OpCode(23u8);
+    pub const EXECUTE_FILE: OpCode = OpCode(31u8);
+
+    // Response
+    pub const RETURN_FILE_DATA: OpCode = OpCode(32u8);
+    pub const RETURN_FILE_PROPERTIES: OpCode = OpCode(33u8);
+    pub const STATUS: OpCode = OpCode(34u8);
+    pub const RESPONSE_TAG: OpCode = OpCode(35u8);
+
+    /// Wizzilab only
+    pub const TX_STATUS: OpCode = OpCode(38u8);
+
+    // Special
+    pub const CHUNK: OpCode = OpCode(48u8);
+    pub const LOGIC: OpCode = OpCode(49u8);
+    pub const FORWARD: OpCode = OpCode(50u8);
+    pub const INDIRECT_FORWARD: OpCode = OpCode(51u8);
+    pub const REQUEST_TAG: OpCode = OpCode(52u8);
+    pub const EXTENSION: OpCode = OpCode(63u8);
+}
+
+#[cfg(feature = "alloc")]
+impl OpCode {
+    pub fn write<W: Write + Seek>(writer: &mut Writer<W>, opcode: OpCode) -> Result<(), DekuError> {
+        opcode.to_writer(writer, ())
+    }
+}
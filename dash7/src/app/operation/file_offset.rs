@@ -1,9 +1,12 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use super::Length;
 
 /// Describe the location of some data on the filesystem (file + data offset).
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileOffset {
     pub file_id: u8,
     pub offset: Length,
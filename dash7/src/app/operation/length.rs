@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use deku::{
     ctx::{BitSize, Endian},
     no_std_io,
@@ -5,6 +7,7 @@ use deku::{
 };
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Length(
     #[deku(
         reader = "Length::read(deku::reader)",
@@ -13,9 +16,9 @@ pub struct Length(
     pub(crate) u32,
 );
 
-impl Into<u32> for Length {
-    fn into(self) -> u32 {
-        self.0 as u32
+impl From<Length> for u32 {
+    fn from(value: Length) -> Self {
+        value.0
     }
 }
 
@@ -25,9 +28,9 @@ impl From<u32> for Length {
     }
 }
 
-impl Into<usize> for Length {
-    fn into(self) -> usize {
-        self.0 as usize
+impl From<Length> for usize {
+    fn from(value: Length) -> Self {
+        value.0 as usize
     }
 }
 
@@ -60,10 +63,10 @@ impl Length {
     where
         W: no_std_io::Write + no_std_io::Seek,
     {
-        let num_extra_bits = Length::required_bits(*value).checked_sub(6).unwrap_or(0);
+        let num_extra_bits = Length::required_bits(*value).saturating_sub(6);
 
         let mut num_extra_bytes = num_extra_bits.checked_div(u8::BITS).unwrap_or(0);
-        if (num_extra_bits % u8::BITS) > 0 {
+        if !num_extra_bits.is_multiple_of(u8::BITS) {
             num_extra_bytes += 1;
         }
 
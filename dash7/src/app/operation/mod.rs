@@ -1,4 +1,6 @@
-use deku::{no_std_io, prelude::*};
+use alloc::vec::Vec;
+
+use deku::{ctx::Limit, no_std_io, prelude::*};
 
 pub use super::query::Query;
 use super::{
@@ -6,7 +8,12 @@ use super::{
     interface::{IndirectInterface, InterfaceConfiguration},
 };
 use crate::utils::write_length_prefixed;
-use crate::{data::FileHeader, file::File, session::InterfaceStatus};
+use crate::{
+    data::FileHeader,
+    dialect::Dialect,
+    file::{File, FileRegistry},
+    session::InterfaceStatus,
+};
 use crate::{session::InterfaceType, utils::write_length_prefixed_ext};
 
 mod file_offset;
@@ -14,7 +21,6 @@ mod length;
 pub use file_offset::*;
 pub use length::*;
 
-#[cfg(feature = "_wizzilab")]
 pub use super::interface_final::*;
 
 // ===============================================================================
@@ -22,6 +28,7 @@ pub use super::interface_final::*;
 // ===============================================================================
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatusCode(#[deku(bits = 8)] pub u8);
 
 impl StatusCode {
@@ -53,6 +60,7 @@ impl StatusCode {
 
 /// Result of an action in a previously sent request
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ActionStatus {
     /// Index of the ALP action associated with this status, in the original request as seen from
     /// the receiver side.
@@ -66,6 +74,7 @@ pub struct ActionStatus {
 
 // ALP SPEC: where is this defined? Link? Not found in either specs !
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(id_type = "u8")]
 pub enum Permission {
     #[deku(id = "0x42")] // ALP_SPEC Undefined
@@ -79,6 +88,7 @@ impl Default for Permission {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(id_type = "u8")]
 pub enum PermissionLevel {
     #[default]
@@ -90,6 +100,7 @@ pub enum PermissionLevel {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ActionHeader {
     /// Group with next action
     #[deku(bits = 1)]
@@ -109,6 +120,7 @@ impl ActionHeader {
 // Nop
 /// Does nothing
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct Nop {
     pub header: ActionHeader,
@@ -120,6 +132,7 @@ pub struct Nop {
 /// Checks whether a file exists
 // ALP_SPEC: How is the result of this command different from a read file of size 0?
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct FileId {
     pub header: ActionHeader,
@@ -134,6 +147,7 @@ pub struct FileId {
 // TODO: figure out a way to immediately decode the file
 // This will probably invole
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct FileData {
     pub header: ActionHeader,
@@ -161,13 +175,35 @@ impl FileData {
         }
     }
 
-    fn read<'a, R>(reader: &mut Reader<R>, offset: &FileOffset) -> Result<File, DekuError>
+    pub fn data(&self) -> &File {
+        &self.data
+    }
+
+    /// Decodes the data into a custom file, when it starts at the beginning of the file and
+    /// `registry` has a decoder for it. Data at an offset is a part of the file, which is kept raw.
+    pub fn resolve(&mut self, registry: &FileRegistry) -> Result<(), DekuError> {
+        if u32::from(self.offset.offset) == 0 {
+            registry.resolve(self.offset.file_id, &mut self.data)?;
+        }
+        Ok(())
+    }
+
+    fn read<R>(reader: &mut Reader<R>, offset: &FileOffset) -> Result<File, DekuError>
     where
         R: no_std_io::Read + no_std_io::Seek,
     {
-        let length = <Length as DekuReader<'_, _>>::from_reader_with_ctx(reader, ())?;
+        let length: u32 = <Length as DekuReader<'_, _>>::from_reader_with_ctx(reader, ())?.into();
         let file_id = offset.file_id.try_into()?;
-        File::from_reader_with_ctx(reader, (file_id, Into::<u32>::into(length)))
+        let data = Vec::<u8>::from_reader_with_ctx(reader, Limit::new_count(length as usize))?;
+
+        // Only a whole file, read from its start, can be decoded with its layout
+        if u32::from(offset.offset) != 0 {
+            return Ok(File::Other(data));
+        }
+        match File::from_bytes((&data, 0), file_id, length) {
+            Ok((([], _), file)) => Ok(file),
+            _ => Ok(File::Other(data)),
+        }
     }
 
     fn write<W>(writer: &mut Writer<W>, data: &File, offset: &FileOffset) -> Result<(), DekuError>
@@ -184,6 +220,7 @@ impl FileData {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct FileProperties {
     pub header: ActionHeader,
@@ -198,6 +235,7 @@ pub struct FileProperties {
 // Read
 /// Read data from a file
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct ReadFileData {
     pub header: ActionHeader,
@@ -210,6 +248,7 @@ pub struct ReadFileData {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct ActionQuery {
     pub header: ActionHeader,
@@ -222,6 +261,7 @@ pub struct ActionQuery {
 
 /// Request a level of permission using some permission type
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct PermissionRequest {
     pub header: ActionHeader,
@@ -241,6 +281,7 @@ pub struct PermissionRequest {
 //
 // Wouldn't it be more appropriate to have 1 size and 2 file offsets?
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct CopyFile {
     pub header: ActionHeader,
@@ -254,8 +295,8 @@ pub struct CopyFile {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
-
 pub enum StatusType {
     #[default]
     #[deku(id = "0")]
@@ -263,17 +304,18 @@ pub enum StatusType {
     #[deku(id = "1")]
     Interface,
 
-    //#[cfg(feature="wizzilab")]
+    /// Wizzilab only
     #[deku(id = "2")]
     InterfaceFinal,
 }
 
 /// Statuses regarding actions sent in a request
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(
-    ctx = "status_type: StatusType",
+    ctx = "status_type: StatusType, dialect: Dialect",
     id = "status_type",
-    ctx_default = "Default::default()"
+    ctx_default = "Default::default(), Dialect::default()"
 )]
 pub enum Status {
     // ALP SPEC: This is named status, but it should be named action status compared to the '2'
@@ -281,9 +323,8 @@ pub enum Status {
     #[deku(id = "StatusType::Action")]
     Action(ActionStatus),
     #[deku(id = "StatusType::Interface")]
-    Interface(InterfaceStatusOperation),
+    Interface(#[deku(ctx = "dialect")] InterfaceStatusOperation),
 
-    #[cfg(feature = "_wizzilab")]
     #[deku(id = "StatusType::InterfaceFinal")]
     InterfaceFinal(InterfaceFinalStatusOperation),
     // ALP SPEC: Where are the stack errors?
@@ -295,35 +336,39 @@ impl Default for Status {
     }
 }
 
-impl Into<StatusOperand> for Status {
-    fn into(self) -> StatusOperand {
-        StatusOperand {
-            status_type: self.deku_id().unwrap(),
+impl From<Status> for StatusOperand {
+    fn from(status: Status) -> Self {
+        Self {
+            status_type: status.deku_id().unwrap(),
             opcode: OpCode::STATUS,
-            status: self,
+            status,
         }
     }
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(ctx = "_opcode: OpCode")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "_opcode: OpCode, dialect: Dialect")]
 pub struct StatusOperand {
+    #[deku(assert = "dialect.is_wizzilab() || *status_type != StatusType::InterfaceFinal")]
     pub status_type: StatusType,
 
     #[deku(writer = "_opcode.to_writer(deku::writer, ())")]
     pub opcode: OpCode,
 
-    #[deku(ctx = "*status_type")]
+    #[deku(ctx = "*status_type, dialect")]
     pub status: Status,
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::default()")]
 pub struct InterfaceStatusOperation {
     pub interface_type: InterfaceType,
 
     #[deku(
-        reader = "InterfaceStatusOperation::read(deku::reader, *interface_type)",
-        writer = "InterfaceStatusOperation::write(deku::writer, &self.status)"
+        reader = "InterfaceStatusOperation::read(deku::reader, *interface_type, dialect)",
+        writer = "InterfaceStatusOperation::write(deku::writer, &self.status, dialect)"
     )]
     pub status: InterfaceStatus,
 }
@@ -337,35 +382,36 @@ impl From<InterfaceStatus> for InterfaceStatusOperation {
     }
 }
 
-impl Into<Status> for InterfaceStatusOperation {
-    fn into(self) -> Status {
-        Status::Interface(self).into()
+impl From<InterfaceStatusOperation> for Status {
+    fn from(operation: InterfaceStatusOperation) -> Self {
+        Status::Interface(operation)
     }
 }
 
 impl InterfaceStatusOperation {
-    #[cfg(not(feature = "subiot_v0_0"))]
     pub fn read<'a, R>(
         reader: &mut Reader<'a, R>,
         interface_type: InterfaceType,
+        dialect: Dialect,
     ) -> Result<InterfaceStatus, DekuError>
     where
         R: no_std_io::Read + no_std_io::Seek,
     {
         // Subiot v0.0 was missing the length field
-        #[allow(unused_assignments)]
-        let mut length = Length(0);
-
-        #[cfg(not(feature = "subiot_v0_0"))]
-        {
-            length = Length::from_reader_with_ctx(reader, ())?;
-        }
+        let length = if dialect.has_interface_status_length() {
+            Length::from_reader_with_ctx(reader, ())?
+        } else {
+            Length(0)
+        };
 
-        InterfaceStatus::from_reader_with_ctx(reader, (interface_type, length.into()))
+        InterfaceStatus::from_reader_with_ctx(reader, (interface_type, length.into(), dialect))
     }
 
-    #[cfg(not(feature = "subiot_v0_0"))]
-    pub fn write<W>(writer: &mut Writer<W>, status: &InterfaceStatus) -> Result<(), DekuError>
+    pub fn write<W>(
+        writer: &mut Writer<W>,
+        status: &InterfaceStatus,
+        dialect: Dialect,
+    ) -> Result<(), DekuError>
     where
         W: no_std_io::Write + no_std_io::Seek,
     {
@@ -373,17 +419,19 @@ impl InterfaceStatusOperation {
             InterfaceStatus::Other(val) => val.len() as u32,
             _ => 0,
         };
+        let ctx = (status.deku_id()?, vec_size, dialect);
 
         // Subiot v0.0 was missing the length field
-        #[cfg(feature = "subiot_v0_0")]
-        return DekuWriter::to_writer(status, writer, (interface_type.try_into()?, vec_size));
-
-        #[cfg(not(feature = "subiot_v0_0"))]
-        return write_length_prefixed(writer, status, (status.deku_id().unwrap(), vec_size));
+        if dialect.has_interface_status_length() {
+            write_length_prefixed(writer, status, ctx)
+        } else {
+            status.to_writer(writer, ctx)
+        }
     }
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResponseTagHeader {
     /// Header
     /// End of packet
@@ -400,6 +448,7 @@ pub struct ResponseTagHeader {
 ///
 /// This allows matching responses to requests when doing multiple requests in parallel.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct ResponseTag {
     pub header: ResponseTagHeader,
@@ -411,7 +460,8 @@ pub struct ResponseTag {
 }
 
 // Special
-#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[derive(DekuRead, DekuWrite, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum ChunkStep {
     #[default]
@@ -425,10 +475,10 @@ pub enum ChunkStep {
     StartEnd,
 }
 
-impl Into<Chunk> for ChunkStep {
-    fn into(self) -> Chunk {
-        Chunk {
-            step: self,
+impl From<ChunkStep> for Chunk {
+    fn from(step: ChunkStep) -> Self {
+        Self {
+            step,
             opcode: OpCode::CHUNK,
         }
     }
@@ -442,6 +492,7 @@ impl Into<Chunk> for ChunkStep {
 /// present, the ALP Command is not chunked (implicit START/END). The Group (11.5.3) and Break Query conditions are
 /// extended over all chunks of the ALP Command.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct Chunk {
     pub step: ChunkStep,
@@ -452,6 +503,7 @@ pub struct Chunk {
 
 /// Provide logical link of a group of queries
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum LogicOp {
     #[default]
@@ -466,6 +518,7 @@ pub enum LogicOp {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct Logic {
     pub logic: LogicOp,
@@ -475,6 +528,7 @@ pub struct Logic {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForwardHeader {
     #[deku(bits = 1, pad_bits_before = "1")]
     pub response: bool,
@@ -482,13 +536,15 @@ pub struct ForwardHeader {
 
 /// Forward rest of the command over the interface
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(ctx = "_opcode: OpCode")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "_opcode: OpCode, dialect: Dialect")]
 pub struct Forward {
     pub header: ForwardHeader,
 
     #[deku(writer = "_opcode.to_writer(deku::writer, ())")]
     pub opcode: OpCode,
 
+    #[deku(ctx = "dialect")]
     pub configuration: InterfaceConfiguration,
 }
 
@@ -503,6 +559,7 @@ impl Forward {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IndirectForwardHeader {
     #[deku(bits = 1)]
     pub overloaded: bool,
@@ -522,7 +579,8 @@ impl IndirectForwardHeader {
 
 /// Forward rest of the command over the interface
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(ctx = "_opcode: OpCode")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "_opcode: OpCode, dialect: Dialect")]
 pub struct IndirectForward {
     #[deku(
         update = "IndirectForwardHeader::new(self.configuration.is_some(), self.header.response)"
@@ -532,7 +590,7 @@ pub struct IndirectForward {
     #[deku(writer = "_opcode.to_writer(deku::writer, ())")]
     pub opcode: OpCode,
 
-    #[deku(cond = "header.overloaded")]
+    #[deku(cond = "header.overloaded", ctx = "dialect")]
     pub configuration: Option<IndirectInterface>,
 }
 
@@ -550,6 +608,7 @@ impl IndirectForward {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RequestTagHeader {
     #[deku(bits = 1, pad_bits_after = "1")]
     pub end_of_packet: bool,
@@ -557,6 +616,7 @@ pub struct RequestTagHeader {
 
 /// Provide command payload identifier
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct RequestTag {
     pub header: RequestTagHeader,
@@ -568,6 +628,7 @@ pub struct RequestTag {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "_opcode: OpCode")]
 pub struct Extension {
     pub header: ActionHeader,
@@ -581,14 +642,12 @@ mod test {
     use hex_literal::hex;
 
     use super::*;
-    #[cfg(feature = "_wizzilab")]
-    use crate::transport::GroupCondition;
     use crate::{
         link::AccessClass,
         network::{Address, Addressee, NlsState},
         physical::{Channel, ChannelBand, ChannelClass, ChannelCoding, ChannelHeader},
         session::Dash7InterfaceStatus,
-        test_tools::test_item,
+        test_tools::{test_item, test_item_ctx},
     };
 
     #[test]
@@ -644,10 +703,6 @@ mod test {
             sequence_number: 0,
             response_timeout: 384.into(),
             addressee: Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::UId(4123107267735781422u64),
                 NlsState::None,
                 AccessClass::new(0, 1),
@@ -655,6 +710,6 @@ mod test {
         })
         .into();
 
-        test_item::<InterfaceStatusOperation>(item, data);
+        test_item_ctx(item, data, Dialect::SpecV1_2);
     }
 }
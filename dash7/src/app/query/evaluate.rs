@@ -0,0 +1,463 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use super::{
+    ArithmeticComparisonType, ArithmeticQueryParams, BitmapRangeComparison,
+    ComparisonWithOtherFile, ComparisonWithValue, ComparisonWithZero, NonVoid, Query,
+    RangeComparisonType, StringTokenSearch,
+};
+use crate::app::operation::{FileOffset, LogicOp, StatusCode};
+
+/// Access to the contents of files, to evaluate queries against
+pub trait FileAccess {
+    /// Reads up to `length` bytes at `file`. Less data is returned when the file is shorter.
+    fn read(&self, file: &FileOffset, length: usize) -> Result<Vec<u8>, StatusCode>;
+}
+
+/// Combines the results of a group of queries with a logic action. Without one, they are AND-ed.
+pub fn combine(logic: Option<&LogicOp>, results: impl IntoIterator<Item = bool>) -> bool {
+    let mut results = results.into_iter();
+    match logic {
+        None => results.all(|result| result),
+        Some(LogicOp::Or) => results.any(|result| result),
+        Some(LogicOp::Xor) => results.fold(false, |acc, result| acc ^ result),
+        Some(LogicOp::Nor) => !results.any(|result| result),
+        Some(LogicOp::Nand) => !results.all(|result| result),
+    }
+}
+
+impl Query {
+    /// Evaluates the query against the contents of `files`.
+    ///
+    /// A query on data beyond the end of a file does not match. Errors reading a file, like a
+    /// missing file, are returned as is.
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        match self {
+            Self::NonVoid(query) => query.evaluate(files),
+            Self::ComparisonWithZero(query) => query.evaluate(files),
+            Self::ComparisonWithValue(query) => query.evaluate(files),
+            Self::ComparisonWithOtherFile(query) => query.evaluate(files),
+            Self::BitmapRangeComparison(query) => query.evaluate(files),
+            Self::StringTokenSearch(query) => query.evaluate(files),
+        }
+    }
+
+    /// Evaluates a group of queries and combines their results, see [`combine`]
+    pub fn evaluate_all<'a>(
+        logic: Option<&LogicOp>,
+        queries: impl IntoIterator<Item = &'a Query>,
+        files: &impl FileAccess,
+    ) -> Result<bool, StatusCode> {
+        let results = queries
+            .into_iter()
+            .map(|query| query.evaluate(files))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(combine(logic, results))
+    }
+}
+
+/// Reads exactly `length` bytes, or `None` when the file is shorter
+fn read_exact(
+    files: &impl FileAccess,
+    file: &FileOffset,
+    length: usize,
+) -> Result<Option<Vec<u8>>, StatusCode> {
+    let data = files.read(file, length)?;
+    Ok((data.len() == length).then_some(data))
+}
+
+/// The mask, if it is present, empty otherwise
+fn present(mask: &[u8], mask_present: bool) -> &[u8] {
+    match mask_present {
+        true => mask,
+        false => &[],
+    }
+}
+
+fn masked(data: &[u8], mask: &[u8]) -> Vec<u8> {
+    data.iter()
+        .enumerate()
+        .map(|(idx, byte)| byte & mask.get(idx).unwrap_or(&0xFF))
+        .collect()
+}
+
+/// Byte of a big-endian integer that orders the same as the integer, when compared unsigned
+fn ordered_byte(idx: usize, byte: u8, signed: bool) -> u8 {
+    match signed && idx == 0 {
+        true => byte ^ 0x80,
+        false => byte,
+    }
+}
+
+impl ArithmeticQueryParams {
+    /// Compares `lhs` with `rhs`, big-endian integers of the same length
+    pub fn compare(&self, lhs: &[u8], rhs: &[u8]) -> bool {
+        let key = |data: &[u8]| -> Vec<u8> {
+            data.iter()
+                .enumerate()
+                .map(|(idx, byte)| ordered_byte(idx, *byte, self.signed))
+                .collect()
+        };
+        let ordering = key(lhs).cmp(&key(rhs));
+
+        match self.comparison_type {
+            ArithmeticComparisonType::Inequal => ordering != Ordering::Equal,
+            ArithmeticComparisonType::Equal => ordering == Ordering::Equal,
+            ArithmeticComparisonType::LessThan => ordering == Ordering::Less,
+            ArithmeticComparisonType::LessThanOrEqual => ordering != Ordering::Greater,
+            ArithmeticComparisonType::GreaterThan => ordering == Ordering::Greater,
+            ArithmeticComparisonType::GreaterThanOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl NonVoid {
+    /// Matches when the file holds `length` bytes at its offset
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        Ok(read_exact(files, &self.file, self.length.into())?.is_some())
+    }
+}
+
+impl ComparisonWithZero {
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        let length = self.length.into();
+        let Some(data) = read_exact(files, &self.file, length)? else {
+            return Ok(false);
+        };
+        let mask = present(&self.mask, self.mask_present);
+        Ok(self.params.compare(&masked(&data, mask), &vec![0; length]))
+    }
+}
+
+impl ComparisonWithValue {
+    /// Compares the masked file content with the masked value
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        let Some(data) = read_exact(files, &self.file, self.value.len())? else {
+            return Ok(false);
+        };
+        let mask = present(&self.mask, self.mask_present);
+        Ok(self
+            .params
+            .compare(&masked(&data, mask), &masked(&self.value, mask)))
+    }
+}
+
+impl ComparisonWithOtherFile {
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        let length = self.length.into();
+        let (Some(data1), Some(data2)) = (
+            read_exact(files, &self.file1, length)?,
+            read_exact(files, &self.file2, length)?,
+        ) else {
+            return Ok(false);
+        };
+        let mask = present(&self.mask, self.mask_present);
+        Ok(self
+            .params
+            .compare(&masked(&data1, mask), &masked(&data2, mask)))
+    }
+}
+
+impl BitmapRangeComparison {
+    /// Reads `length` bytes of the file as an integer, which is in range when it lies within
+    /// `start..stop` and, if a bitmap is given, its bit at `value - start` is set.
+    ///
+    /// Integers of more than 8 bytes are not supported.
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        let length = self.length.into();
+        if length > 8 {
+            return Err(StatusCode::OPERATION_WRONG_FORMAT);
+        }
+        let Some(data) = read_exact(files, &self.file, length)? else {
+            return Ok(false);
+        };
+
+        let mut value = data
+            .iter()
+            .fold(0i128, |acc, byte| (acc << 8) | *byte as i128);
+        if self.params.signed && data.first().is_some_and(|byte| byte & 0x80 != 0) {
+            value -= 1 << (8 * length);
+        }
+
+        let start = u32::from(self.start) as i128;
+        let stop = u32::from(self.stop) as i128;
+        let mut in_range = (start..stop).contains(&value);
+        if in_range && self.mask_present {
+            let bit = (value - start) as usize;
+            in_range = self
+                .mask
+                .get(bit / 8)
+                .is_some_and(|byte| byte & (0x80 >> (bit % 8)) != 0);
+        }
+
+        Ok(match self.params.comparison_type {
+            RangeComparisonType::InRange => in_range,
+            RangeComparisonType::NotInRange => !in_range,
+        })
+    }
+}
+
+impl StringTokenSearch {
+    /// Searches the file, from its offset to its end, for the masked token. It matches where no more
+    /// than `max_errors` bytes differ.
+    pub fn evaluate(&self, files: &impl FileAccess) -> Result<bool, StatusCode> {
+        let data = files.read(&self.file, usize::MAX)?;
+        let mask = present(&self.mask, self.mask_present);
+        if self.value.is_empty() {
+            return Ok(true);
+        }
+
+        let errors = |window: &[u8]| {
+            window
+                .iter()
+                .zip(&self.value)
+                .enumerate()
+                .filter(|(idx, (lhs, rhs))| (*lhs ^ *rhs) & mask.get(*idx).unwrap_or(&0xFF) != 0)
+                .count()
+        };
+        Ok(data
+            .windows(self.value.len())
+            .any(|window| errors(window) <= self.max_errors as usize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::collections::BTreeMap;
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::app::query::{ArithmeticComparisonType as Comparison, RangeQueryParams};
+
+    struct Files(BTreeMap<u8, Vec<u8>>);
+
+    impl FileAccess for Files {
+        fn read(&self, file: &FileOffset, length: usize) -> Result<Vec<u8>, StatusCode> {
+            let data = self
+                .0
+                .get(&file.file_id)
+                .ok_or(StatusCode::FILE_ID_MISSING)?;
+            let start = usize::from(file.offset).min(data.len());
+            let end = start.saturating_add(length).min(data.len());
+            Ok(data[start..end].to_vec())
+        }
+    }
+
+    fn files() -> Files {
+        Files(BTreeMap::from([
+            (0x40, hex!("00 7F 80 FF").to_vec()),
+            (0x41, hex!("00 7F 80 00").to_vec()),
+            (0x42, b"hello dash7".to_vec()),
+        ]))
+    }
+
+    fn at(file_id: u8, offset: u32) -> FileOffset {
+        FileOffset {
+            file_id,
+            offset: offset.into(),
+        }
+    }
+
+    fn params(signed: bool, comparison_type: Comparison) -> ArithmeticQueryParams {
+        ArithmeticQueryParams {
+            signed,
+            comparison_type,
+        }
+    }
+
+    #[test]
+    fn test_non_void() {
+        let query = |offset, length: u32| {
+            Query::NonVoid(NonVoid {
+                length: length.into(),
+                file: at(0x40, offset),
+            })
+            .evaluate(&files())
+        };
+        assert_eq!(query(0, 4), Ok(true));
+        assert_eq!(query(2, 4), Ok(false));
+        assert_eq!(
+            Query::NonVoid(NonVoid {
+                length: 1u32.into(),
+                file: at(0x50, 0),
+            })
+            .evaluate(&files()),
+            Err(StatusCode::FILE_ID_MISSING)
+        );
+    }
+
+    #[test]
+    fn test_comparison_with_zero() {
+        let query = |mask: Vec<u8>, comparison_type, offset| {
+            Query::ComparisonWithZero(ComparisonWithZero::new(
+                params(false, comparison_type),
+                mask,
+                at(0x40, offset),
+            ))
+            .evaluate(&files())
+        };
+        assert_eq!(query(vec![0xFF], Comparison::Equal, 0), Ok(true));
+        assert_eq!(
+            query(vec![0xFF, 0xFF], Comparison::GreaterThan, 0),
+            Ok(true)
+        );
+        assert_eq!(query(vec![0x80], Comparison::Equal, 1), Ok(true));
+        assert_eq!(query(vec![0xFF, 0xFF], Comparison::Equal, 3), Ok(false));
+    }
+
+    #[test]
+    fn test_comparison_with_value() {
+        let query = |signed, comparison_type, mask: Vec<u8>, value: Vec<u8>| {
+            Query::ComparisonWithValue(ComparisonWithValue::new(
+                params(signed, comparison_type),
+                mask,
+                value,
+                at(0x40, 2),
+            ))
+            .evaluate(&files())
+        };
+        // 0x80FF is large unsigned, but negative signed
+        assert_eq!(
+            query(false, Comparison::GreaterThan, vec![], vec![0x7F, 0x00]),
+            Ok(true)
+        );
+        assert_eq!(
+            query(true, Comparison::LessThan, vec![], vec![0x7F, 0x00]),
+            Ok(true)
+        );
+        assert_eq!(
+            query(true, Comparison::LessThanOrEqual, vec![], vec![0x80, 0x00]),
+            Ok(false)
+        );
+        assert_eq!(
+            query(false, Comparison::Equal, vec![0xF0, 0x0F], vec![0x8A, 0xAF]),
+            Ok(true)
+        );
+        assert_eq!(
+            query(false, Comparison::Inequal, vec![], vec![0x80, 0xFF]),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_comparison_with_other_file() {
+        let query = |mask: Vec<u8>, comparison_type| {
+            Query::ComparisonWithOtherFile(ComparisonWithOtherFile::new(
+                params(false, comparison_type),
+                mask,
+                at(0x40, 0),
+                at(0x41, 0),
+            ))
+            .evaluate(&files())
+        };
+        assert_eq!(query(vec![0xFF; 4], Comparison::GreaterThan), Ok(true));
+        assert_eq!(
+            query(vec![0xFF, 0xFF, 0xFF, 0x00], Comparison::Equal),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_bitmap_range_comparison() {
+        let query = |signed, comparison_type, start, stop, mask: Vec<u8>, offset| {
+            Query::BitmapRangeComparison(BitmapRangeComparison::new(
+                RangeQueryParams {
+                    signed,
+                    comparison_type,
+                },
+                start,
+                stop,
+                mask,
+                at(0x40, offset),
+            ))
+            .evaluate(&files())
+        };
+        // the byte at offset 1 is 0x7F
+        assert_eq!(
+            query(
+                false,
+                RangeComparisonType::InRange,
+                0x70,
+                0x80,
+                vec![0x00],
+                1
+            ),
+            Ok(false)
+        );
+        assert_eq!(
+            query(
+                false,
+                RangeComparisonType::InRange,
+                0x7E,
+                0x80,
+                vec![0x40],
+                1
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            query(
+                false,
+                RangeComparisonType::NotInRange,
+                0,
+                0x7F,
+                vec![0xFF],
+                1
+            ),
+            Ok(true)
+        );
+        // the byte at offset 3 is 0xFF, -1 when signed
+        assert_eq!(
+            query(true, RangeComparisonType::InRange, 0, 0xFF, vec![0xFF], 3),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_string_token_search() {
+        let query = |max_errors, mask: Vec<u8>, value: &[u8]| {
+            Query::StringTokenSearch(StringTokenSearch::new(
+                max_errors,
+                mask,
+                value.to_vec(),
+                at(0x42, 0),
+            ))
+            .evaluate(&files())
+        };
+        assert_eq!(query(0, vec![], b"dash7"), Ok(true));
+        assert_eq!(query(0, vec![], b"dash8"), Ok(false));
+        assert_eq!(query(1, vec![], b"dash8"), Ok(true));
+        assert_eq!(
+            query(0, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x00], b"dash8"),
+            Ok(true)
+        );
+        assert_eq!(query(2, vec![], b"dash7!!!!!!!"), Ok(false));
+    }
+
+    #[test]
+    fn test_combine() {
+        let results = [true, true, false];
+        assert!(!combine(None, results));
+        assert!(combine(Some(&LogicOp::Or), results));
+        assert!(!combine(Some(&LogicOp::Xor), results));
+        assert!(!combine(Some(&LogicOp::Nor), results));
+        assert!(combine(Some(&LogicOp::Nand), results));
+        assert!(combine(None, []));
+
+        let queries = [
+            Query::NonVoid(NonVoid {
+                length: 4u32.into(),
+                file: at(0x40, 0),
+            }),
+            Query::NonVoid(NonVoid {
+                length: 4u32.into(),
+                file: at(0x42, 10),
+            }),
+        ];
+        assert_eq!(Query::evaluate_all(None, &queries, &files()), Ok(false));
+        assert_eq!(
+            Query::evaluate_all(Some(&LogicOp::Xor), &queries, &files()),
+            Ok(true)
+        );
+    }
+}
@@ -1,8 +1,14 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use super::operation::{FileOffset, Length};
 
+mod evaluate;
+pub use evaluate::{combine, FileAccess};
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, id_type = "u8")]
 pub enum ArithmeticComparisonType {
     #[default]
@@ -21,6 +27,7 @@ pub enum ArithmeticComparisonType {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArithmeticQueryParams {
     #[deku(bits = 1)]
     pub signed: bool,
@@ -28,6 +35,7 @@ pub struct ArithmeticQueryParams {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, id_type = "u8")]
 pub enum RangeComparisonType {
     #[default]
@@ -38,6 +46,7 @@ pub enum RangeComparisonType {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RangeQueryParams {
     #[deku(bits = 1)]
     pub signed: bool,
@@ -45,6 +54,7 @@ pub struct RangeQueryParams {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, id_type = "u8")]
 pub enum Query {
     #[deku(id = "0x00")]
@@ -70,6 +80,7 @@ impl Default for Query {
 // ALP_SPEC Does this fail if the content overflows the file?
 /// Checks if the file content exists.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NonVoid {
     #[deku(pad_bits_before = "5")]
     pub length: Length,
@@ -77,8 +88,9 @@ pub struct NonVoid {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComparisonWithZero {
-    #[deku(bits = 1, update = "self.mask.len() > 0")]
+    #[deku(bits = 1, update = "!self.mask.is_empty()")]
     mask_present: bool,
 
     pub params: ArithmeticQueryParams,
@@ -94,7 +106,7 @@ pub struct ComparisonWithZero {
 impl ComparisonWithZero {
     pub fn new(params: ArithmeticQueryParams, mask: Vec<u8>, file: FileOffset) -> Self {
         Self {
-            mask_present: mask.len() > 0,
+            mask_present: !mask.is_empty(),
             params,
             length: mask.len().into(),
             mask,
@@ -105,8 +117,9 @@ impl ComparisonWithZero {
 
 /// Compare some file content optionally masked, with a value
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComparisonWithValue {
-    #[deku(bits = 1, update = "self.mask.len() > 0")]
+    #[deku(bits = 1, update = "!self.mask.is_empty()")]
     mask_present: bool,
 
     pub params: ArithmeticQueryParams,
@@ -131,7 +144,7 @@ impl ComparisonWithValue {
         file: FileOffset,
     ) -> Self {
         Self {
-            mask_present: mask.len() > 0,
+            mask_present: !mask.is_empty(),
             params,
             length: value.len().into(),
             mask,
@@ -143,8 +156,9 @@ impl ComparisonWithValue {
 
 /// Compare content of 2 files optionally masked
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComparisonWithOtherFile {
-    #[deku(bits = 1, update = "self.mask.len() > 0")]
+    #[deku(bits = 1, update = "!self.mask.is_empty()")]
     mask_present: bool,
     pub params: ArithmeticQueryParams,
 
@@ -164,7 +178,7 @@ impl ComparisonWithOtherFile {
         file2: FileOffset,
     ) -> Self {
         Self {
-            mask_present: mask.len() > 0,
+            mask_present: !mask.is_empty(),
             params,
             length: mask.len().into(),
             mask,
@@ -176,8 +190,9 @@ impl ComparisonWithOtherFile {
 
 /// Check if the content of a file is (not) contained in the sent bitmap values
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitmapRangeComparison {
-    #[deku(bits = 1, update = "self.mask.len() > 0")]
+    #[deku(bits = 1, update = "!self.mask.is_empty()")]
     mask_present: bool,
     pub params: RangeQueryParams,
     pub length: Length,
@@ -203,7 +218,7 @@ impl BitmapRangeComparison {
         file: FileOffset,
     ) -> Self {
         Self {
-            mask_present: mask.len() > 0,
+            mask_present: !mask.is_empty(),
             params,
             length: mask.len().into(),
             start: start.into(),
@@ -217,8 +232,9 @@ impl BitmapRangeComparison {
 /// Compare some file content, optional masked, with an array of bytes and up to a certain number
 /// of errors.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringTokenSearch {
-    #[deku(bits = 1, update = "self.mask.len() > 0", pad_bits_after = "1")]
+    #[deku(bits = 1, update = "!self.mask.is_empty()", pad_bits_after = "1")]
     mask_present: bool,
 
     // TODO: is this bitsize correct?
@@ -240,7 +256,7 @@ pub struct StringTokenSearch {
 impl StringTokenSearch {
     pub fn new(max_errors: u8, mask: Vec<u8>, value: Vec<u8>, file: FileOffset) -> Self {
         Self {
-            mask_present: mask.len() > 0,
+            mask_present: !mask.is_empty(),
             max_errors,
             length: value.len().into(),
             mask,
@@ -0,0 +1,327 @@
+use alloc::collections::{btree_map::Entry, BTreeMap};
+use core::fmt;
+
+use super::{
+    action::{Action, OpCode},
+    command::Command,
+    operation::{RequestTag, RequestTagHeader},
+};
+
+/// Problems allocating or registering request tags
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TagError {
+    /// Every request tag is taken by a pending request
+    Exhausted,
+
+    /// The request tag is taken by a pending request
+    InUse { id: u8 },
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exhausted => write!(f, "no request tags available"),
+            Self::InUse { id } => write!(f, "a request with tag {} is already pending", id),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TagError {}
+
+/// A command received in response to a tracked request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Tag of the request this responds to
+    pub id: u8,
+
+    /// Position of this response among the responses to the same request
+    pub index: usize,
+
+    /// Whether this is the last response, after which the request is finished
+    pub last: bool,
+
+    /// Whether the responder signaled an error
+    pub error: bool,
+
+    pub command: Command,
+}
+
+/// What a received command turned out to be
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received {
+    /// A response to a pending request
+    Response(Response),
+
+    /// A command that does not respond to any pending request
+    Unsolicited(Command),
+}
+
+/// Allocates request tags and matches responses to the requests they belong to.
+///
+/// A request is pending until a response with `end_of_packet` arrives, so a request broadcast
+/// with [`crate::session::ResponseMode::All`] collects the responses of every responder.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TagTracker {
+    next: u8,
+    /// Number of responses received so far, per pending request
+    pending: BTreeMap<u8, usize>,
+}
+
+impl TagTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates a tag that is not taken by a pending request, and marks it pending
+    pub fn allocate(&mut self) -> Result<u8, TagError> {
+        for _ in 0..=u8::MAX {
+            let id = self.next;
+            self.next = self.next.wrapping_add(1);
+            if self.register(id).is_ok() {
+                return Ok(id);
+            }
+        }
+        Err(TagError::Exhausted)
+    }
+
+    /// Marks a tag chosen by the caller pending
+    pub fn register(&mut self, id: u8) -> Result<(), TagError> {
+        match self.pending.entry(id) {
+            Entry::Occupied(_) => Err(TagError::InUse { id }),
+            Entry::Vacant(entry) => {
+                entry.insert(0);
+                Ok(())
+            }
+        }
+    }
+
+    /// Tracks `command` as a request. Its request tag is kept, or allocated and added when it has
+    /// none.
+    pub fn tag(&mut self, command: &mut Command) -> Result<u8, TagError> {
+        if let Some(id) = command.request_id() {
+            self.register(id)?;
+            return Ok(id);
+        }
+
+        let id = self.allocate()?;
+        // a request tag only follows a chunk
+        let index = match command.actions.first() {
+            Some(Action::Chunk(_)) => 1,
+            _ => 0,
+        };
+        command.actions.insert(
+            index,
+            Action::RequestTag(RequestTag {
+                header: RequestTagHeader {
+                    end_of_packet: true,
+                },
+                opcode: OpCode::REQUEST_TAG,
+                id,
+            }),
+        );
+        Ok(id)
+    }
+
+    /// Whether responses to the request with tag `id` are still expected
+    pub fn is_pending(&self, id: u8) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    /// Tags of all pending requests
+    pub fn pending(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pending.keys().copied()
+    }
+
+    /// Stops waiting for responses to the request with tag `id`, eg. when it timed out. Returns
+    /// whether it was pending.
+    pub fn finish(&mut self, id: u8) -> bool {
+        self.pending.remove(&id).is_some()
+    }
+
+    /// Matches a received command to its pending request
+    pub fn receive(&mut self, command: Command) -> Received {
+        let Some(tag) = command.response_tag() else {
+            return Received::Unsolicited(command);
+        };
+        let (id, header) = (tag.id, tag.header.clone());
+        let Some(count) = self.pending.get_mut(&id) else {
+            return Received::Unsolicited(command);
+        };
+
+        let index = *count;
+        *count += 1;
+        if header.end_of_packet {
+            self.pending.remove(&id);
+        }
+        Received::Response(Response {
+            id,
+            index,
+            last: header.end_of_packet,
+            error: header.error,
+            command,
+        })
+    }
+
+    /// Matches the commands of `commands` as they arrive, see [`TagStream`]
+    #[cfg(feature = "futures")]
+    pub fn stream<S>(self, commands: S) -> TagStream<S> {
+        TagStream {
+            tracker: self,
+            commands,
+        }
+    }
+}
+
+/// Stream of [`Received`] commands, matched by a [`TagTracker`] as they arrive
+#[cfg(feature = "futures")]
+#[derive(Debug)]
+pub struct TagStream<S> {
+    tracker: TagTracker,
+    commands: S,
+}
+
+#[cfg(feature = "futures")]
+impl<S> TagStream<S> {
+    /// The tracker, to tag new requests while the stream is running
+    pub fn tracker(&mut self) -> &mut TagTracker {
+        &mut self.tracker
+    }
+
+    pub fn into_inner(self) -> (TagTracker, S) {
+        (self.tracker, self.commands)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S> futures_core::Stream for TagStream<S>
+where
+    S: futures_core::Stream<Item = Command> + Unpin,
+{
+    type Item = Received;
+
+    fn poll_next(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        core::pin::Pin::new(&mut this.commands)
+            .poll_next(cx)
+            .map(|command| command.map(|command| this.tracker.receive(command)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::app::operation::{ResponseTag, ResponseTagHeader};
+
+    fn response(id: u8, end_of_packet: bool, error: bool) -> Command {
+        Command::new(vec![Action::ResponseTag(ResponseTag {
+            header: ResponseTagHeader {
+                end_of_packet,
+                error,
+            },
+            opcode: OpCode::RESPONSE_TAG,
+            id,
+        })])
+    }
+
+    fn matched(received: Received) -> (u8, usize, bool, bool) {
+        match received {
+            Received::Response(response) => {
+                (response.id, response.index, response.last, response.error)
+            }
+            Received::Unsolicited(command) => panic!("unexpected unsolicited {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_tag() {
+        let mut tracker = TagTracker::new();
+        let mut command = Command::builder().nop().build().unwrap();
+        assert_eq!(tracker.tag(&mut command), Ok(0));
+        assert_eq!(command.request_id(), Some(0));
+        assert!(command.validate().is_empty());
+
+        let mut command = Command::builder().request_tag(1).nop().build().unwrap();
+        assert_eq!(tracker.tag(&mut command.clone()), Ok(1));
+        assert_eq!(tracker.tag(&mut command), Err(TagError::InUse { id: 1 }));
+
+        // taken tags are skipped
+        assert_eq!(tracker.allocate(), Ok(2));
+        assert_eq!(tracker.pending().collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        for _ in 3..=u8::MAX {
+            tracker.allocate().unwrap();
+        }
+        assert_eq!(tracker.allocate(), Err(TagError::Exhausted));
+        assert!(tracker.finish(7));
+        assert_eq!(tracker.allocate(), Ok(7));
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut tracker = TagTracker::new();
+        let id = tracker.allocate().unwrap();
+
+        // every responder of a broadcast request answers before the last response
+        assert_eq!(
+            matched(tracker.receive(response(id, false, false))),
+            (id, 0, false, false)
+        );
+        assert_eq!(
+            matched(tracker.receive(response(id, false, true))),
+            (id, 1, false, true)
+        );
+        assert_eq!(
+            tracker.receive(response(id + 1, true, false)),
+            Received::Unsolicited(response(id + 1, true, false))
+        );
+        assert_eq!(
+            matched(tracker.receive(response(id, true, false))),
+            (id, 2, true, false)
+        );
+        assert!(!tracker.is_pending(id));
+
+        assert_eq!(
+            tracker.receive(response(id, true, false)),
+            Received::Unsolicited(response(id, true, false))
+        );
+        let command = Command::builder().nop().build().unwrap();
+        assert_eq!(
+            tracker.receive(command.clone()),
+            Received::Unsolicited(command)
+        );
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_stream() {
+        use futures::{executor::block_on, stream, StreamExt};
+
+        let mut tracker = TagTracker::new();
+        let id = tracker.allocate().unwrap();
+        let commands = stream::iter(vec![
+            response(id, false, false),
+            response(id + 1, true, false),
+            response(id, true, false),
+        ]);
+
+        let mut received = tracker.stream(commands);
+        let first = block_on(received.next()).unwrap();
+        assert_eq!(matched(first), (id, 0, false, false));
+        assert!(received.tracker().is_pending(id));
+
+        let rest: Vec<_> = block_on(received.by_ref().collect());
+        assert_eq!(
+            rest[0],
+            Received::Unsolicited(response(id + 1, true, false))
+        );
+        assert_eq!(matched(rest[1].clone()), (id, 1, true, false));
+        assert!(!received.tracker().is_pending(id));
+    }
+}
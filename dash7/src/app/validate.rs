@@ -0,0 +1,341 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{
+    action::Action,
+    operation::{ChunkStep, Status},
+};
+
+/// Problem with the way the actions of a command are combined
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// A request tag has to be the first action, only preceded by a chunk
+    MisplacedRequestTag { index: usize },
+
+    /// A command can only carry a single request or response tag
+    DuplicateTag { index: usize },
+
+    /// A response tag is followed by the data it should come after
+    ResponseTagBeforeData { index: usize },
+
+    /// A forward is the last action, so there is nothing to forward
+    NothingToForward { index: usize },
+
+    /// An indirect forward is followed by actions, which decoding stops at
+    ActionsAfterIndirectForward { index: usize },
+
+    /// A grouped action is not followed by an action to group it with
+    DanglingGroup { index: usize },
+
+    /// A query is not followed by an action it applies to
+    DanglingQuery { index: usize },
+
+    /// A logic action is not followed by the queries it combines
+    LogicWithoutQuery { index: usize },
+
+    /// A chunk step does not follow from the steps before it
+    ChunkOutOfSequence { index: usize, step: ChunkStep },
+
+    /// The typed file of a file data action is stored in another file than the one it is written to
+    FileIdMismatch {
+        index: usize,
+        file_id: u8,
+        data_file_id: u8,
+    },
+}
+
+impl Diagnostic {
+    /// Index of the offending action in the command
+    pub fn index(&self) -> usize {
+        match self {
+            Self::MisplacedRequestTag { index }
+            | Self::DuplicateTag { index }
+            | Self::ResponseTagBeforeData { index }
+            | Self::NothingToForward { index }
+            | Self::ActionsAfterIndirectForward { index }
+            | Self::DanglingGroup { index }
+            | Self::DanglingQuery { index }
+            | Self::LogicWithoutQuery { index }
+            | Self::ChunkOutOfSequence { index, .. }
+            | Self::FileIdMismatch { index, .. } => *index,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "action {}: ", self.index())?;
+        match self {
+            Self::MisplacedRequestTag { .. } => write!(f, "request tag must be the first action"),
+            Self::DuplicateTag { .. } => write!(f, "command is already tagged"),
+            Self::ResponseTagBeforeData { .. } => {
+                write!(f, "response tag must come after the returned data")
+            }
+            Self::NothingToForward { .. } => write!(f, "forward is not followed by any action"),
+            Self::ActionsAfterIndirectForward { .. } => {
+                write!(f, "indirect forward must be the last action")
+            }
+            Self::DanglingGroup { .. } => write!(f, "grouped with an action that does not exist"),
+            Self::DanglingQuery { .. } => {
+                write!(f, "query is not followed by an action it applies to")
+            }
+            Self::LogicWithoutQuery { .. } => write!(f, "logic must be followed by a query"),
+            Self::ChunkOutOfSequence { step, .. } => {
+                write!(f, "chunk step {:?} is out of sequence", step)
+            }
+            Self::FileIdMismatch {
+                file_id,
+                data_file_id,
+                ..
+            } => write!(
+                f,
+                "data of file {:#04X} written to file {:#04X}",
+                data_file_id, file_id
+            ),
+        }
+    }
+}
+
+fn is_response_data(action: &Action) -> bool {
+    match action {
+        Action::ReturnFileData(_) | Action::ReturnFileProperties(_) => true,
+        Action::Status(status) => matches!(status.status, Status::Action(_)),
+        _ => false,
+    }
+}
+
+/// Checks how the actions of a command are combined, see [`super::command::Command::validate`]
+pub(crate) fn validate(actions: &[Action]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut tagged = false;
+    // None as long as no chunk was seen, as the command may continue a chunk of an earlier one
+    let mut chunk_open: Option<bool> = None;
+
+    for (index, action) in actions.iter().enumerate() {
+        let rest = &actions[index + 1..];
+
+        if let Some(header) = action.header() {
+            if header.group && rest.is_empty() {
+                diagnostics.push(Diagnostic::DanglingGroup { index });
+            }
+        }
+
+        match action {
+            Action::RequestTag(_) | Action::ResponseTag(_) if tagged => {
+                diagnostics.push(Diagnostic::DuplicateTag { index });
+            }
+            Action::RequestTag(_) => {
+                tagged = true;
+                let first = match index {
+                    0 => true,
+                    1 => matches!(actions[0], Action::Chunk(_)),
+                    _ => false,
+                };
+                if !first {
+                    diagnostics.push(Diagnostic::MisplacedRequestTag { index });
+                }
+            }
+            Action::ResponseTag(_) => {
+                tagged = true;
+                if rest.iter().any(is_response_data) {
+                    diagnostics.push(Diagnostic::ResponseTagBeforeData { index });
+                }
+            }
+            Action::Forward(_) if rest.is_empty() => {
+                diagnostics.push(Diagnostic::NothingToForward { index });
+            }
+            Action::IndirectForward(_) if !rest.is_empty() => {
+                diagnostics.push(Diagnostic::ActionsAfterIndirectForward { index });
+            }
+            Action::ActionQuery(_) => {
+                let applies_to = rest
+                    .iter()
+                    .any(|action| !action.is_query() && !matches!(action, Action::Logic(_)));
+                if !applies_to {
+                    diagnostics.push(Diagnostic::DanglingQuery { index });
+                }
+            }
+            Action::Logic(_) if !rest.first().is_some_and(Action::is_query) => {
+                diagnostics.push(Diagnostic::LogicWithoutQuery { index });
+            }
+            Action::Chunk(chunk) => {
+                let in_sequence = match (&chunk.step, chunk_open) {
+                    (ChunkStep::Start | ChunkStep::StartEnd, open) => open != Some(true),
+                    (ChunkStep::Continue | ChunkStep::End, None) => index == 0,
+                    (ChunkStep::Continue | ChunkStep::End, Some(open)) => open,
+                };
+                if !in_sequence {
+                    diagnostics.push(Diagnostic::ChunkOutOfSequence {
+                        index,
+                        step: chunk.step,
+                    });
+                }
+                chunk_open = Some(matches!(chunk.step, ChunkStep::Start | ChunkStep::Continue));
+            }
+            Action::WriteFileData(file_data)
+            | Action::WriteFileDataFlush(file_data)
+            | Action::ReturnFileData(file_data) => {
+                let file_id = file_data.offset.file_id;
+                match file_data.data().file_id() {
+                    Some(data_file_id) if data_file_id != file_id => {
+                        diagnostics.push(Diagnostic::FileIdMismatch {
+                            index,
+                            file_id,
+                            data_file_id,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{
+        app::{
+            action::OpCode,
+            command::Command,
+            operation::{ActionHeader, FileData, FileOffset, IndirectForward},
+        },
+        file::{File, FirmwareVersion},
+    };
+
+    fn diagnostics(data: &[u8]) -> Vec<Diagnostic> {
+        Command::try_from(data).unwrap().validate()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(diagnostics(&hex!("B4 01 41 00 00 08")), vec![]);
+        assert_eq!(diagnostics(&hex!("20 40 00 01 FF A3 01")), vec![]);
+        assert_eq!(
+            diagnostics(&hex!("08 00 01 40 00   81 40 00 01   04 41 00 01 01")),
+            vec![]
+        );
+        assert_eq!(diagnostics(&hex!("70 B4 01 01 00 00 08")), vec![]);
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(
+            diagnostics(&hex!("01 00 00 08 B4 01")),
+            vec![Diagnostic::MisplacedRequestTag { index: 1 }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("B4 01 A3 02")),
+            vec![Diagnostic::DuplicateTag { index: 1 }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("A3 01 20 40 00 01 FF")),
+            vec![Diagnostic::ResponseTagBeforeData { index: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_forward() {
+        assert_eq!(
+            diagnostics(&hex!("00 32 00")),
+            vec![Diagnostic::NothingToForward { index: 1 }]
+        );
+
+        let command = Command::new(vec![
+            Action::IndirectForward(IndirectForward::new(false, None)),
+            Action::ReadFileData(Default::default()),
+        ]);
+        assert_eq!(
+            command.validate(),
+            vec![Diagnostic::ActionsAfterIndirectForward { index: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(
+            diagnostics(&hex!("80")),
+            vec![Diagnostic::DanglingGroup { index: 0 }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("08 00 01 40 00")),
+            vec![Diagnostic::DanglingQuery { index: 0 }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("B1 00")),
+            vec![Diagnostic::LogicWithoutQuery { index: 0 }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("B1 08 00 01 40 00 09 00 01 41 00 00")),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(diagnostics(&hex!("30 00 B0 00 70 00")), vec![]);
+        assert_eq!(
+            diagnostics(&hex!("70 00 F0 00 30 00")),
+            vec![
+                Diagnostic::ChunkOutOfSequence {
+                    index: 2,
+                    step: ChunkStep::StartEnd
+                },
+                Diagnostic::ChunkOutOfSequence {
+                    index: 4,
+                    step: ChunkStep::Continue
+                }
+            ]
+        );
+        assert_eq!(
+            diagnostics(&hex!("00 B0")),
+            vec![Diagnostic::ChunkOutOfSequence {
+                index: 1,
+                step: ChunkStep::End
+            }]
+        );
+        assert_eq!(
+            diagnostics(&hex!("F0 00 30")),
+            vec![Diagnostic::ChunkOutOfSequence {
+                index: 2,
+                step: ChunkStep::Continue
+            }]
+        );
+    }
+
+    #[test]
+    fn test_file_id_mismatch() {
+        let version = File::FirmwareVersion(FirmwareVersion {
+            major: 1,
+            minor: 2,
+            application_name: "dash7s".into(),
+            git_sha1: "abcdef0".into(),
+        });
+        let command = Command::new(vec![Action::WriteFileData(FileData::new(
+            ActionHeader::default(),
+            FileOffset::no_offset(0x40),
+            version,
+            OpCode::WRITE_FILE_DATA,
+        ))]);
+        let diagnostics = command.validate();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::FileIdMismatch {
+                index: 0,
+                file_id: 0x40,
+                data_file_id: 0x02
+            }]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "action 0: data of file 0x02 written to file 0x40"
+        );
+    }
+}
@@ -0,0 +1,762 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    app::{
+        action::{Action, OpCode},
+        command::Command,
+        execute::{Node, Outcome},
+        operation::{
+            self, ActionHeader, ActionStatus, FileData, FileOffset, PermissionLevel, ResponseTag,
+            ResponseTagHeader, Status, StatusCode,
+        },
+        query::FileAccess,
+    },
+    error::Error,
+    file::{
+        AccessProfile, DllConfig, DllStatus, EngineeringMode, EngineeringModeMethod, File, FileId,
+        FirmwareVersion, PhyStatus, RootKey, SecurityKey, UserKey,
+    },
+    network::Address,
+    physical::{Channel, ChannelHeader},
+    session::QoS,
+};
+
+use super::{FileHeader, FilePermissions, FileProperties, StorageClass, UserPermissions};
+
+#[derive(Debug, Clone, PartialEq)]
+struct StoredFile {
+    header: FileHeader,
+    data: Vec<u8>,
+    /// Contents in permanent storage, used to restore a restorable file
+    backup: Vec<u8>,
+}
+
+/// In-memory D7A filesystem, which executes ALP commands like a device would
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filesystem {
+    files: BTreeMap<u8, StoredFile>,
+}
+
+impl Filesystem {
+    /// Creates a filesystem without any files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a filesystem holding the system files with typed contents
+    ///
+    /// The factory settings are left out, as they hold the calibration of the radio of a device,
+    /// which no default fits. System files without a typed layout are left out as well.
+    pub fn with_system_files() -> Self {
+        let read_only = FilePermissions {
+            user: UserPermissions {
+                read: true,
+                ..Default::default()
+            },
+            guest: UserPermissions {
+                read: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let read_write = FilePermissions {
+            user: UserPermissions {
+                read: true,
+                write: true,
+                executable: false,
+            },
+            ..read_only.clone()
+        };
+
+        let access_profiles = [
+            File::AccessProfile00(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile01(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile02(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile03(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile04(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile05(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile06(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile07(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile08(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile09(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile10(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile11(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile12(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile13(AccessProfile {
+                profile: Default::default(),
+            }),
+            File::AccessProfile14(AccessProfile {
+                profile: Default::default(),
+            }),
+        ]
+        .map(|file| (file, read_write.clone(), StorageClass::Permanent));
+
+        let files = [
+            (
+                File::UId(Address::UId(0)),
+                read_only.clone(),
+                StorageClass::Permanent,
+            ),
+            (
+                File::FirmwareVersion(FirmwareVersion {
+                    major: 0,
+                    minor: 0,
+                    application_name: "dash7s".into(),
+                    git_sha1: "0000000".into(),
+                }),
+                read_only.clone(),
+                StorageClass::Permanent,
+            ),
+            (
+                File::EngineeringMode(EngineeringMode {
+                    mode: EngineeringModeMethod::Off,
+                    flags: 0,
+                    timeout: 0,
+                    channel: Channel::new(ChannelHeader::default(), 0),
+                    eirp: 0,
+                }),
+                read_write.clone(),
+                StorageClass::Volatile,
+            ),
+            (
+                File::VId(Address::VId(0)),
+                read_write.clone(),
+                StorageClass::Permanent,
+            ),
+            (
+                File::PhyStatus(PhyStatus::new(0, 0, 0, 0, vec![])),
+                read_only.clone(),
+                StorageClass::Volatile,
+            ),
+            (
+                File::DllConfig(DllConfig {
+                    ac: 0x01,
+                    lq_filter: 0,
+                    nf_ctrl: 0,
+                    rx_nf_method_parameter: 0,
+                    tx_nf_method_parameter: 0,
+                }),
+                read_write,
+                StorageClass::Restorable,
+            ),
+            (
+                File::DllStatus(DllStatus {
+                    last_rx_packet_level: 0,
+                    last_rx_packet_link_budget: 0,
+                    noise_floor: 0,
+                    channel_header: ChannelHeader::default(),
+                    channel_index: 0,
+                    scan_timeout_ratio: 0,
+                    scan_count: 0,
+                    scan_timeout_count: 0,
+                }),
+                read_only,
+                StorageClass::Volatile,
+            ),
+            (
+                File::NwlSecurityKey(SecurityKey { key: 0 }),
+                FilePermissions::default(),
+                StorageClass::Permanent,
+            ),
+            (
+                File::RootKey(RootKey::default()),
+                FilePermissions::default(),
+                StorageClass::Permanent,
+            ),
+            (
+                File::UserKey(UserKey::default()),
+                FilePermissions::default(),
+                StorageClass::Permanent,
+            ),
+        ];
+
+        let mut filesystem = Self::new();
+        for (file, permissions, storage_class) in files.into_iter().chain(access_profiles) {
+            let header = FileHeader {
+                permissions,
+                properties: FileProperties {
+                    storage_class,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let file_id = file.file_id().expect("system files have a file id");
+            let data = file.to_bytes().expect("system files can be encoded");
+            filesystem.insert(file_id, header, data);
+        }
+        filesystem
+    }
+
+    /// Adds or replaces a file. The file size is set to the length of `data`.
+    pub fn insert(&mut self, file_id: u8, mut header: FileHeader, data: Vec<u8>) {
+        header.file_size = data.len() as u32;
+        header.allocated_size = header.allocated_size.max(header.file_size);
+        self.files.insert(
+            file_id,
+            StoredFile {
+                header,
+                backup: data.clone(),
+                data,
+            },
+        );
+    }
+
+    pub fn contains(&self, file_id: u8) -> bool {
+        self.files.contains_key(&file_id)
+    }
+
+    pub fn header(&self, file_id: u8) -> Option<&FileHeader> {
+        self.files.get(&file_id).map(|file| &file.header)
+    }
+
+    pub fn data(&self, file_id: u8) -> Option<&[u8]> {
+        self.files.get(&file_id).map(|file| file.data.as_slice())
+    }
+
+    /// Decodes the contents of a file into its typed variant
+    pub fn file(&self, file_id: u8) -> Option<Result<File, Error>> {
+        let data = self.data(file_id)?;
+        Some(decode_file(file_id, data))
+    }
+
+    /// Executes a command with user permissions and the default QoS, see [`Filesystem::execute_as`]
+    pub fn execute(&mut self, command: &Command) -> Command {
+        self.execute_as(command, PermissionLevel::User, &QoS::default())
+    }
+
+    /// Executes the actions of a command and returns the response. Groups and queries are
+    /// honoured, see [`Command::execute`]. Forwarding is left to the caller.
+    ///
+    /// Read actions return data or properties. A status is returned for every action that fails,
+    /// and for executed actions without data that ask for a response. A tagged command gets a
+    /// response tag.
+    pub fn execute_as(&mut self, command: &Command, level: PermissionLevel, qos: &QoS) -> Command {
+        let mut session = Session {
+            filesystem: self,
+            level,
+            responses: BTreeMap::new(),
+        };
+        let outcomes = command.execute(&mut session, qos);
+        let mut responses = session.responses;
+
+        let mut actions = vec![];
+        let mut error = false;
+        for (index, (action, outcome)) in command.actions.iter().zip(outcomes).enumerate() {
+            if let Some(response) = responses.remove(&index) {
+                actions.push(response);
+                continue;
+            }
+            let status = match outcome {
+                Outcome::Executed(status) if status.is_err() => {
+                    error = true;
+                    status
+                }
+                Outcome::Executed(status) if action.header().is_some_and(|h| h.response) => status,
+                _ => continue,
+            };
+            actions.push(Action::Status(
+                Status::Action(ActionStatus {
+                    action_id: index as u8,
+                    status,
+                })
+                .into(),
+            ));
+        }
+
+        if let Some(id) = command.request_id() {
+            actions.push(Action::ResponseTag(ResponseTag {
+                header: ResponseTagHeader {
+                    end_of_packet: true,
+                    error,
+                },
+                opcode: OpCode::RESPONSE_TAG,
+                id,
+            }));
+        }
+
+        Command::new(actions)
+    }
+
+    fn execute_action(
+        &mut self,
+        action: &Action,
+        level: &PermissionLevel,
+    ) -> Result<Option<Action>, StatusCode> {
+        match action {
+            Action::Nop(_)
+            | Action::RequestTag(_)
+            | Action::Forward(_)
+            | Action::IndirectForward(_) => Ok(None),
+            Action::ReadFileData(read) => {
+                let data = self.read_data(&read.offset, read.length.into(), level)?;
+                Ok(Some(Action::ReturnFileData(data)))
+            }
+            Action::ReadFileProperties(read) => {
+                let file = self.stored(read.file_id)?;
+                Ok(Some(Action::ReturnFileProperties(
+                    operation::FileProperties {
+                        header: ActionHeader::default(),
+                        opcode: OpCode::RETURN_FILE_PROPERTIES,
+                        file_id: read.file_id,
+                        file_header: file.header.clone(),
+                    },
+                )))
+            }
+            Action::WriteFileData(operation) => self.write(operation, level).map(|_| None),
+            Action::WriteFileDataFlush(operation) => {
+                self.write(operation, level)?;
+                self.flush(operation.offset.file_id).map(|_| None)
+            }
+            Action::WriteFileProperties(operation) => self
+                .write_properties(operation.file_id, &operation.file_header, level)
+                .map(|_| None),
+            Action::CreateNewFile(operation) => self
+                .create(operation.file_id, &operation.file_header)
+                .map(|_| None),
+            Action::ExistFile(operation) => self.stored(operation.file_id).map(|_| None),
+            Action::DeleteFile(operation) => {
+                self.writable(operation.file_id, level)?;
+                self.files.remove(&operation.file_id);
+                Ok(None)
+            }
+            Action::RestoreFile(operation) => {
+                let file = self.writable(operation.file_id, level)?;
+                if file.header.properties.storage_class != StorageClass::Restorable {
+                    return Err(StatusCode::FILE_IS_NOT_RESTORABLE);
+                }
+                file.data = file.backup.clone();
+                file.header.file_size = file.data.len() as u32;
+                Ok(None)
+            }
+            Action::FlushFile(operation) => {
+                self.writable(operation.file_id, level)?;
+                self.flush(operation.file_id).map(|_| None)
+            }
+            Action::CopyFile(operation) => {
+                let data = self.readable(operation.src_file_id, level)?.data.clone();
+                let file = self.writable(operation.dst_file_id, level)?;
+                if data.len() > file.header.allocated_size as usize {
+                    return Err(StatusCode::WRITE_DATA_OVERFLOW);
+                }
+                file.header.file_size = data.len() as u32;
+                file.data = data;
+                Ok(None)
+            }
+            _ => Err(StatusCode::UNKNOWN_OPERATION),
+        }
+    }
+
+    fn stored(&self, file_id: u8) -> Result<&StoredFile, StatusCode> {
+        self.files.get(&file_id).ok_or(StatusCode::FILE_ID_MISSING)
+    }
+
+    fn readable(&self, file_id: u8, level: &PermissionLevel) -> Result<&StoredFile, StatusCode> {
+        let file = self.stored(file_id)?;
+        match level {
+            PermissionLevel::User if !file.header.permissions.user.read => {
+                Err(StatusCode::INSUFFICIENT_PERMISSION)
+            }
+            _ => Ok(file),
+        }
+    }
+
+    fn writable(
+        &mut self,
+        file_id: u8,
+        level: &PermissionLevel,
+    ) -> Result<&mut StoredFile, StatusCode> {
+        let file = self
+            .files
+            .get_mut(&file_id)
+            .ok_or(StatusCode::FILE_ID_MISSING)?;
+        match level {
+            PermissionLevel::User if !file.header.permissions.user.write => {
+                Err(StatusCode::INSUFFICIENT_PERMISSION)
+            }
+            _ => Ok(file),
+        }
+    }
+
+    fn read_bytes(
+        &self,
+        offset: &FileOffset,
+        length: usize,
+        level: &PermissionLevel,
+    ) -> Result<&[u8], StatusCode> {
+        let file = self.readable(offset.file_id, level)?;
+        let start = usize::from(offset.offset).min(file.data.len());
+        let end = start.saturating_add(length).min(file.data.len());
+        Ok(&file.data[start..end])
+    }
+
+    fn read_data(
+        &self,
+        offset: &FileOffset,
+        length: usize,
+        level: &PermissionLevel,
+    ) -> Result<FileData, StatusCode> {
+        let data = self.read_bytes(offset, length, level)?;
+        let whole = usize::from(offset.offset) == 0
+            && self.data(offset.file_id).map(<[u8]>::len) == Some(data.len());
+
+        // a whole file is returned typed, so it can be decoded by the receiver the same way
+        let data = match whole {
+            true => decode_file(offset.file_id, data).unwrap_or_else(|_| File::Other(data.into())),
+            false => File::Other(data.into()),
+        };
+        Ok(FileData::new(
+            ActionHeader::default(),
+            offset.clone(),
+            data,
+            OpCode::RETURN_FILE_DATA,
+        ))
+    }
+
+    fn write(&mut self, operation: &FileData, level: &PermissionLevel) -> Result<(), StatusCode> {
+        let data = operation
+            .data()
+            .to_bytes()
+            .map_err(|_| StatusCode::OPERATION_WRONG_FORMAT)?;
+        let file = self.writable(operation.offset.file_id, level)?;
+
+        let start = usize::from(operation.offset.offset);
+        if start > file.data.len() {
+            return Err(StatusCode::WRITE_OFFSET_OVERFLOW);
+        }
+        let end = start + data.len();
+        if end > file.header.allocated_size as usize {
+            return Err(StatusCode::WRITE_DATA_OVERFLOW);
+        }
+
+        if end > file.data.len() {
+            file.data.resize(end, 0);
+            file.header.file_size = end as u32;
+        }
+        file.data[start..end].copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn write_properties(
+        &mut self,
+        file_id: u8,
+        header: &FileHeader,
+        level: &PermissionLevel,
+    ) -> Result<(), StatusCode> {
+        let file = self.writable(file_id, level)?;
+        if header.file_size > header.allocated_size {
+            return Err(StatusCode::WRITE_DATA_OVERFLOW);
+        }
+        file.data.resize(header.file_size as usize, 0);
+        file.header = header.clone();
+        Ok(())
+    }
+
+    fn create(&mut self, file_id: u8, header: &FileHeader) -> Result<(), StatusCode> {
+        if self.contains(file_id) {
+            return Err(StatusCode::CREATE_FILE_ID_ALREADY_EXIST);
+        }
+        if header.file_size > header.allocated_size {
+            return Err(StatusCode::CREATE_FILE_LENGTH_OVERFLOW);
+        }
+        let data = vec![0; header.file_size as usize];
+        self.files.insert(
+            file_id,
+            StoredFile {
+                header: header.clone(),
+                backup: data.clone(),
+                data,
+            },
+        );
+        Ok(())
+    }
+
+    /// Writes a restorable file to permanent storage, other files are always stored
+    fn flush(&mut self, file_id: u8) -> Result<(), StatusCode> {
+        let file = self
+            .files
+            .get_mut(&file_id)
+            .ok_or(StatusCode::FILE_ID_MISSING)?;
+        file.backup = file.data.clone();
+        Ok(())
+    }
+}
+
+/// Queries read files with user permissions
+impl FileAccess for Filesystem {
+    fn read(&self, file: &FileOffset, length: usize) -> Result<Vec<u8>, StatusCode> {
+        self.read_bytes(file, length, &PermissionLevel::User)
+            .map(Vec::from)
+    }
+}
+
+/// Executes actions on a filesystem, keeping their responses by action index
+struct Session<'a> {
+    filesystem: &'a mut Filesystem,
+    level: PermissionLevel,
+    responses: BTreeMap<usize, Action>,
+}
+
+impl FileAccess for Session<'_> {
+    fn read(&self, file: &FileOffset, length: usize) -> Result<Vec<u8>, StatusCode> {
+        self.filesystem
+            .read_bytes(file, length, &self.level)
+            .map(Vec::from)
+    }
+}
+
+impl Node for Session<'_> {
+    fn perform(&mut self, index: usize, action: &Action) -> Result<(), StatusCode> {
+        if let Some(response) = self.filesystem.execute_action(action, &self.level)? {
+            self.responses.insert(index, response);
+        }
+        Ok(())
+    }
+}
+
+fn decode_file(file_id: u8, data: &[u8]) -> Result<File, Error> {
+    let typed_id = FileId::try_from(file_id).unwrap_or(FileId::Other);
+    File::from_bytes((data, 0), typed_id, data.len() as u32).map(|(_, file)| file)
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::app::query::{NonVoid, Query};
+
+    fn header(file_size: u32, allocated_size: u32, storage_class: StorageClass) -> FileHeader {
+        FileHeader {
+            permissions: FilePermissions {
+                user: UserPermissions {
+                    read: true,
+                    write: true,
+                    executable: false,
+                },
+                ..Default::default()
+            },
+            properties: FileProperties {
+                storage_class,
+                ..Default::default()
+            },
+            file_size,
+            allocated_size,
+            ..Default::default()
+        }
+    }
+
+    fn status(index: u8, status: StatusCode) -> Action {
+        Action::Status(
+            Status::Action(ActionStatus {
+                action_id: index,
+                status,
+            })
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_read_write() {
+        let mut filesystem = Filesystem::new();
+        filesystem.insert(0x40, header(0, 8, StorageClass::Volatile), vec![1, 2, 3]);
+
+        let command = Command::builder()
+            .request_tag(7)
+            .write_file_data(0x40, 2, [0xAA, 0xBB])
+            .read_file(0x40, 1, 8)
+            .build()
+            .unwrap();
+        let response = filesystem.execute(&command);
+
+        assert_eq!(filesystem.data(0x40), Some(hex!("01 02 AA BB").as_slice()));
+        assert_eq!(filesystem.header(0x40).unwrap().file_size, 4);
+        assert_eq!(
+            response.to_bytes().unwrap(),
+            hex!("20 40 01 03 02 AA BB   A3 07")
+        );
+        assert_eq!(response.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_system_files() {
+        let mut filesystem = Filesystem::with_system_files();
+        assert_eq!(
+            filesystem.file(0x00).unwrap(),
+            Ok(File::UId(Address::UId(0)))
+        );
+
+        let response =
+            filesystem.execute(&Command::builder().read_file(0x02, 0, 17).build().unwrap());
+        let Action::ReturnFileData(data) = &response.actions[0] else {
+            panic!("expected data, got {:?}", response.actions);
+        };
+        assert!(matches!(data.data(), File::FirmwareVersion(_)));
+
+        // the security key can not be read by a user
+        let command = Command::builder().read_file(0x0E, 0, 16).build().unwrap();
+        assert_eq!(
+            filesystem.execute(&command).actions,
+            vec![status(0, StatusCode::INSUFFICIENT_PERMISSION)]
+        );
+        assert!(matches!(
+            filesystem
+                .execute_as(&command, PermissionLevel::Root, &QoS::default())
+                .actions[0],
+            Action::ReturnFileData(_)
+        ));
+
+        assert!(matches!(
+            filesystem.file(0x2E).unwrap(),
+            Ok(File::AccessProfile14(_))
+        ));
+        assert!(matches!(
+            filesystem.file(0x0B).unwrap(),
+            Ok(File::DllStatus(_))
+        ));
+        assert!(matches!(
+            filesystem.file(0x18).unwrap(),
+            Ok(File::RootKey(_))
+        ));
+        // no default fits the factory settings
+        assert!(filesystem.file(0x01).is_none());
+    }
+
+    #[test]
+    fn test_properties() {
+        let mut filesystem = Filesystem::new();
+        let create = |file_size| {
+            Action::CreateNewFile(operation::FileProperties {
+                header: ActionHeader::new(false, true),
+                opcode: OpCode::CREATE_NEW_FILE,
+                file_id: 0x41,
+                file_header: header(file_size, 4, StorageClass::Permanent),
+            })
+        };
+
+        let command = Command::new(vec![create(2), create(2)]);
+        let response = filesystem.execute(&command);
+        assert_eq!(
+            response.actions,
+            vec![
+                status(0, StatusCode::OK),
+                status(1, StatusCode::CREATE_FILE_ID_ALREADY_EXIST)
+            ]
+        );
+        assert_eq!(filesystem.data(0x41), Some([0, 0].as_slice()));
+
+        let response = filesystem.execute(
+            &Command::builder()
+                .read_file_properties(0x41)
+                .delete_file(0x41)
+                .exist_file(0x41)
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(
+            response.actions,
+            vec![
+                Action::ReturnFileProperties(operation::FileProperties {
+                    header: ActionHeader::default(),
+                    opcode: OpCode::RETURN_FILE_PROPERTIES,
+                    file_id: 0x41,
+                    file_header: header(2, 4, StorageClass::Permanent),
+                }),
+                status(2, StatusCode::FILE_ID_MISSING)
+            ]
+        );
+
+        let response = Filesystem::new().execute(&Command::new(vec![create(8)]));
+        assert_eq!(
+            response.actions,
+            vec![status(0, StatusCode::CREATE_FILE_LENGTH_OVERFLOW)]
+        );
+    }
+
+    #[test]
+    fn test_query() {
+        let mut filesystem = Filesystem::new();
+        filesystem.insert(0x40, header(0, 4, StorageClass::Volatile), vec![1, 2]);
+
+        let query = |length: u32| {
+            Query::NonVoid(NonVoid {
+                length: length.into(),
+                file: FileOffset::no_offset(0x40),
+            })
+        };
+        assert_eq!(query(2).evaluate(&filesystem), Ok(true));
+        assert_eq!(query(3).evaluate(&filesystem), Ok(false));
+
+        // the write only happens when the query matches
+        for (length, expected) in [(3, [1, 2]), (2, [9, 2])] {
+            let command = Command::builder()
+                .query(query(length))
+                .write_file_data(0x40, 0, [9])
+                .build()
+                .unwrap();
+            assert_eq!(filesystem.execute(&command).actions, vec![]);
+            assert_eq!(filesystem.data(0x40), Some(expected.as_slice()));
+        }
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut filesystem = Filesystem::new();
+        filesystem.insert(0x40, header(0, 4, StorageClass::Restorable), vec![1]);
+        filesystem.insert(0x41, header(0, 4, StorageClass::Volatile), vec![]);
+
+        let command = Command::builder()
+            .request_tag(1)
+            .write_file_data(0x40, 0, [2])
+            .copy_file(0x40, 0x41)
+            .restore_file(0x40)
+            .restore_file(0x41)
+            .write_file_data(0x41, 2, [3])
+            .build()
+            .unwrap();
+        let response = filesystem.execute(&command);
+
+        assert_eq!(filesystem.data(0x40), Some([1].as_slice()));
+        assert_eq!(filesystem.data(0x41), Some([2].as_slice()));
+        assert_eq!(
+            response.actions,
+            vec![
+                status(4, StatusCode::FILE_IS_NOT_RESTORABLE),
+                status(5, StatusCode::WRITE_OFFSET_OVERFLOW),
+                Action::ResponseTag(ResponseTag {
+                    header: ResponseTagHeader {
+                        end_of_packet: true,
+                        error: true,
+                    },
+                    opcode: OpCode::RESPONSE_TAG,
+                    id: 1,
+                })
+            ]
+        );
+    }
+}
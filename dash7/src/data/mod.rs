@@ -1,7 +1,13 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
+mod filesystem;
+pub use filesystem::Filesystem;
+
 /// File access type event that will trigger an ALP action.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, endian = "big", id_type = "u8")]
 pub enum ActionCondition {
     #[default]
@@ -16,6 +22,7 @@ pub enum ActionCondition {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum StorageClass {
     /// The content is not kept in memory. It cannot be read back.
@@ -38,6 +45,7 @@ pub enum StorageClass {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UserPermissions {
     #[deku(bits = 1)]
     pub read: bool,
@@ -48,6 +56,7 @@ pub struct UserPermissions {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FilePermissions {
     #[deku(bits = 1)]
     pub encrypted: bool,
@@ -58,6 +67,7 @@ pub struct FilePermissions {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileProperties {
     /// Enables the D7AActP (ALP action to trigger upon some type of access to this file)
     #[deku(bits = 1)]
@@ -72,6 +82,7 @@ pub struct FileProperties {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileHeader {
     pub permissions: FilePermissions,
     pub properties: FileProperties,
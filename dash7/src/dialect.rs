@@ -0,0 +1,66 @@
+/// Flavour of the protocol spoken by a device
+///
+/// Vendors deviate from the DASH7 spec in a couple of places: field sizes in the addressee,
+/// optional fields in interface configurations and statuses, extra actions and the serial
+/// framing. The dialect is passed as context while encoding and decoding, so a single build can
+/// talk to all of them.
+///
+/// The default dialect is selected with the `spec_v1_2`, `subiot_v0_0`, `subiot_v0_1` or
+/// `wizzilab_v5_3` cargo features, and falls back to the spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dialect {
+    /// DASH7 Alliance Protocol v1.2
+    SpecV1_2,
+    /// Sub-IoT v0.0
+    SubIoTV0_0,
+    /// Sub-IoT v0.1
+    SubIoTV0_1,
+    /// Wizzilab v5.3
+    WizzilabV5_3,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        if cfg!(feature = "wizzilab_v5_3") {
+            Self::WizzilabV5_3
+        } else if cfg!(feature = "subiot_v0_0") {
+            Self::SubIoTV0_0
+        } else if cfg!(feature = "subiot_v0_1") {
+            Self::SubIoTV0_1
+        } else {
+            Self::SpecV1_2
+        }
+    }
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [
+        Self::SpecV1_2,
+        Self::SubIoTV0_0,
+        Self::SubIoTV0_1,
+        Self::WizzilabV5_3,
+    ];
+
+    pub fn is_spec(&self) -> bool {
+        matches!(self, Self::SpecV1_2)
+    }
+
+    pub fn is_subiot(&self) -> bool {
+        matches!(self, Self::SubIoTV0_0 | Self::SubIoTV0_1)
+    }
+
+    pub fn is_wizzilab(&self) -> bool {
+        matches!(self, Self::WizzilabV5_3)
+    }
+
+    /// Sub-IoT leaves the execution delay timeout out of the D7 interface configuration
+    pub fn has_execution_delay_timeout(&self) -> bool {
+        !self.is_subiot()
+    }
+
+    /// Sub-IoT v0.0 does not length prefix interface statuses
+    pub fn has_interface_status_length(&self) -> bool {
+        *self != Self::SubIoTV0_0
+    }
+}
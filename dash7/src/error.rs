@@ -0,0 +1,220 @@
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt;
+
+use deku::DekuError;
+
+/// Part of the protocol that was being decoded when an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Layer {
+    /// Serial modem frame, by its frame counter
+    SerialFrame { counter: u8 },
+
+    /// ALP action, by its index in the command
+    Action { index: usize, opcode: u8 },
+
+    /// File, by its file id
+    File(u8),
+
+    /// Interface, by its interface type
+    Interface(u8),
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SerialFrame { counter } => write!(f, "serial frame {}", counter),
+            Self::Action { index, opcode } => {
+                write!(f, "action {} (opcode {:#04X})", index, opcode)
+            }
+            Self::File(file_id) => write!(f, "file {:#04X}", file_id),
+            Self::Interface(interface_type) => write!(f, "interface {:#04X}", interface_type),
+        }
+    }
+}
+
+/// Position in the input at which an error occurred
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Offset in bits, from the start of the input
+    pub bit_offset: usize,
+
+    /// Layers that were being decoded, outermost first
+    pub layers: Vec<Layer>,
+}
+
+impl Location {
+    pub fn new(bit_offset: usize) -> Self {
+        Self {
+            bit_offset,
+            layers: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {}", self.bit_offset / 8)?;
+        if !self.bit_offset.is_multiple_of(8) {
+            write!(f, ", bit {}", self.bit_offset % 8)?;
+        }
+        for (idx, layer) in self.layers.iter().enumerate() {
+            let separator = if idx == 0 { " in" } else { "," };
+            write!(f, "{} {}", separator, layer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when decoding fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input ended before decoding was complete
+    Incomplete {
+        location: Location,
+        /// Number of bits that were needed for the next field
+        needed: usize,
+    },
+
+    /// A field holds a value that is not valid at this position
+    InvalidValue {
+        location: Location,
+        field: &'static str,
+        expected: Cow<'static, str>,
+        found: u64,
+    },
+
+    /// Decoding completed before the end of the input
+    TooMuchData {
+        location: Location,
+        /// Number of bytes left over
+        remaining: usize,
+    },
+
+    /// Any other error reported while decoding
+    Deku {
+        location: Location,
+        source: DekuError,
+    },
+}
+
+impl Error {
+    /// Wraps an error reported by deku after reading `bit_offset` bits
+    pub(crate) fn from_deku(source: DekuError, bit_offset: usize) -> Self {
+        let location = Location::new(bit_offset);
+        match source {
+            DekuError::Incomplete(size) => Self::Incomplete {
+                location,
+                needed: size.bit_size(),
+            },
+            source => Self::Deku { location, source },
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        match self {
+            Self::Incomplete { location, .. }
+            | Self::InvalidValue { location, .. }
+            | Self::TooMuchData { location, .. }
+            | Self::Deku { location, .. } => location,
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut Location {
+        match self {
+            Self::Incomplete { location, .. }
+            | Self::InvalidValue { location, .. }
+            | Self::TooMuchData { location, .. }
+            | Self::Deku { location, .. } => location,
+        }
+    }
+
+    /// Offset in bits, from the start of the input, at which the error occurred
+    pub fn bit_offset(&self) -> usize {
+        self.location().bit_offset
+    }
+
+    /// Layers that were being decoded, outermost first
+    pub fn layers(&self) -> &[Layer] {
+        &self.location().layers
+    }
+
+    /// Places an error of a nested structure, which starts at `bit_offset`, inside the given layers
+    pub(crate) fn nested(mut self, bit_offset: usize, layers: &[Layer]) -> Self {
+        let location = self.location_mut();
+        location.bit_offset += bit_offset;
+        location.layers.splice(0..0, layers.iter().copied());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete { location, needed } => {
+                write!(
+                    f,
+                    "not enough data at {}, need {} more bits",
+                    location, needed
+                )
+            }
+            Self::InvalidValue {
+                location,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "invalid {} at {}: expected {}, found {:#X}",
+                field, location, expected, found
+            ),
+            Self::TooMuchData {
+                location,
+                remaining,
+            } => write!(f, "too much data at {}, {} bytes left", location, remaining),
+            Self::Deku { location, source } => write!(f, "{} at {}", source, location),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deku { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_display() {
+        let error = Error::from_deku(DekuError::Parse("bad".into()), 20).nested(
+            8,
+            &[
+                Layer::Action {
+                    index: 1,
+                    opcode: 0x04,
+                },
+                Layer::File(0x40),
+            ],
+        );
+        assert_eq!(error.bit_offset(), 28);
+        assert_eq!(
+            error.to_string(),
+            "Parse error: bad at byte 3, bit 4 in action 1 (opcode 0x04), file 0x40"
+        );
+
+        let error = Error::from_deku(DekuError::Incomplete(deku::error::NeedSize::new(8)), 16);
+        assert_eq!(
+            error.to_string(),
+            "not enough data at byte 2, need 8 more bits"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use crate::link;
@@ -5,6 +7,7 @@ use crate::link;
 use super::SystemFile;
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccessProfile<const S: u8> {
     pub profile: link::AccessProfile,
 }
@@ -0,0 +1,31 @@
+use alloc::vec::Vec;
+
+use deku::prelude::*;
+
+use super::SystemFile;
+
+/// Key authenticating root access to the ALP interface
+#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RootKey {
+    #[deku(endian = "big")]
+    pub key: u128,
+}
+
+impl SystemFile for RootKey {
+    const ID: u8 = 0x18;
+    const SIZE: u32 = 16;
+}
+
+/// Key authenticating user access to the ALP interface
+#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserKey {
+    #[deku(endian = "big")]
+    pub key: u128,
+}
+
+impl SystemFile for UserKey {
+    const ID: u8 = 0x19;
+    const SIZE: u32 = 16;
+}
@@ -1,8 +1,11 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use crate::physical::ChannelHeader;
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DllStatus {
     pub last_rx_packet_level: u8,
     pub last_rx_packet_link_budget: u8,
@@ -1,8 +1,11 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use crate::physical::Channel;
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(id_type = "u8")]
 pub enum EngineeringModeMethod {
     #[deku(id = "0")]
@@ -18,6 +21,7 @@ pub enum EngineeringModeMethod {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EngineeringMode {
     pub mode: EngineeringModeMethod,
     pub flags: u8,
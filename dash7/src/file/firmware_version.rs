@@ -1,3 +1,5 @@
+use alloc::{string::String, vec::Vec};
+
 use deku::prelude::*;
 
 use crate::utils::{read_string, write_string};
@@ -6,6 +8,7 @@ use super::SystemFile;
 
 // TODO: actual fixed length strings would be better here
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FirmwareVersion {
     pub major: u8,
     pub minor: u8,
@@ -1,6 +1,9 @@
-use deku::prelude::*;
+use alloc::vec::Vec;
+
+use deku::{no_std_io, prelude::*};
 
 mod access_profile;
+mod authentication_key;
 mod dll_config;
 mod dll_status;
 mod engineering_mode;
@@ -8,9 +11,11 @@ mod factory_settings;
 mod firmware_version;
 mod interface_configuration;
 mod phy_status;
+mod registry;
 mod security_key;
 
 pub use access_profile::AccessProfile;
+pub use authentication_key::{RootKey, UserKey};
 pub use dll_config::DllConfig;
 pub use dll_status::DllStatus;
 pub use engineering_mode::{EngineeringMode, EngineeringModeMethod};
@@ -18,14 +23,19 @@ pub use factory_settings::FactorySettings;
 pub use firmware_version::FirmwareVersion;
 pub use interface_configuration::InterfaceConfiguration;
 pub use phy_status::PhyStatus;
+#[cfg(feature = "serde")]
+pub use registry::Serializable;
+pub use registry::{Custom, CustomFile, FileDecoder, FileRegistry};
 pub use security_key::SecurityKey;
 
 use crate::{
+    error::{Error, Layer},
     network::{Address, AddressType},
-    utils::from_bytes,
+    utils::decode,
 };
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(id_type = "u8", bits = "8")]
 pub enum FileId {
     #[deku(id = "0x00")]
@@ -118,13 +128,13 @@ impl TryFrom<u8> for FileId {
     type Error = DekuError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        Ok(Self::from_bytes((&vec![value], 0))?.1)
+        Ok(Self::from_bytes((&[value], 0))?.1)
     }
 }
 
-impl Into<u8> for FileId {
-    fn into(self) -> u8 {
-        self.deku_id().unwrap()
+impl From<FileId> for u8 {
+    fn from(value: FileId) -> Self {
+        value.deku_id().unwrap()
     }
 }
 
@@ -137,6 +147,7 @@ pub trait SystemFile {
 /// File IDs 0x18-0x1F Reserved for D7AALP.
 /// File IDs 0x20+I with I in [0, 14] are reserved for Access Profiles.
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(
     ctx = "file_id: FileId, length: u32",
     id = "file_id",
@@ -201,8 +212,18 @@ pub enum File {
     #[deku(id = "FileId::NetworkSecurityKey")]
     NwlSecurityKey(SecurityKey),
 
+    #[deku(id = "FileId::RootKey")]
+    RootKey(RootKey),
+
+    #[deku(id = "FileId::UserKey")]
+    UserKey(UserKey),
+
     #[deku(id_pat = "_")]
     Other(#[deku(count = "length")] Vec<u8>),
+
+    /// Application file decoded by a [`FileRegistry`]. Never decoded by itself.
+    #[deku(id = "FileId::Other")]
+    Custom(Custom),
 }
 
 impl Default for File {
@@ -212,22 +233,113 @@ impl Default for File {
 }
 
 impl File {
-    pub fn from_bytes<'a>(
-        input: (&'a [u8], usize),
+    /// Id of the file this variant is stored in. `None` for files without a known type.
+    pub fn file_id(&self) -> Option<u8> {
+        if let Self::Custom(custom) = self {
+            return Some(custom.file_id());
+        }
+        self.deku_id().ok()?.deku_id().ok()
+    }
+
+    pub fn from_bytes(
+        input: (&[u8], usize),
         file_id: FileId,
         length: u32,
-    ) -> Result<((&'a [u8], usize), Self), DekuError> {
-        from_bytes(input, (file_id, length))
+    ) -> Result<((&[u8], usize), Self), Error> {
+        let layer = file_id.deku_id().ok().map(Layer::File);
+        decode(input, (file_id, length)).map_err(|err| err.nested(0, layer.as_slice()))
     }
 
-    // fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
-    //     let output = self.to_bits()?;
-    //     Ok(output.into_vec())
-    // }
+    /// Decodes like [`Self::from_bytes`], but application files with a decoder in `registry` are
+    /// decoded into [`File::Custom`]
+    pub fn from_bytes_with_registry<'a>(
+        input: (&'a [u8], usize),
+        file_id: u8,
+        length: u32,
+        registry: &FileRegistry,
+    ) -> Result<((&'a [u8], usize), Self), Error> {
+        let start = input.1;
+        let id = FileId::try_from(file_id).map_err(|err| Error::from_deku(err, start))?;
+        let (rest, file) = Self::from_bytes(input, id, length)?;
+        let file = match file {
+            Self::Other(data) => match registry.decode(file_id, &data) {
+                Some(custom) => Self::Custom(custom.map_err(|err| {
+                    Error::from_deku(err, start).nested(0, &[Layer::File(file_id)])
+                })?),
+                None => Self::Other(data),
+            },
+            file => file,
+        };
+        Ok((rest, file))
+    }
 
-    // fn to_bits(&self) -> Result<BitVec<u8, Msb0>, DekuError> {
-    //     let mut output: BitVec<u8, Msb0> = BitVec::new();
-    //     self.write(&mut output, u32::MAX)?;
-    //     Ok(output)
-    // }
+    /// Encodes the contents of the file, without a length prefix
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
+        let file_id = self.deku_id().unwrap_or(FileId::Other);
+        let mut out_buf = Vec::new();
+        let mut cursor = no_std_io::Cursor::new(&mut out_buf);
+        let mut writer = Writer::new(&mut cursor);
+        self.to_writer(&mut writer, (file_id, 0))?;
+        writer.finalize()?;
+        Ok(out_buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[track_caller]
+    fn test_file<T: SystemFile>(file: File, data: &[u8]) {
+        assert_eq!(file.file_id(), Some(T::ID));
+        assert_eq!(file.to_bytes().unwrap(), data);
+
+        let file_id = FileId::try_from(T::ID).unwrap();
+        let ((rest, _), decoded) = File::from_bytes((data, 0), file_id, data.len() as u32).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(decoded, file);
+    }
+
+    #[test]
+    fn test_undefined_files() {
+        // no layout is defined for these files, so they are kept raw
+        for file_id in [
+            FileId::DeviceCapacity,
+            FileId::DeviceStatus,
+            FileId::PhyConfig,
+            FileId::NetworkSecurity,
+            FileId::NetworkRouting,
+            FileId::NetworkSsr,
+            FileId::NetworkStatus,
+            FileId::TrlStatus,
+            FileId::SelConfig,
+            FileId::FofStatus,
+            FileId::LocationData,
+            FileId::SensorDescription,
+            FileId::Rtc,
+        ] {
+            let data = hex!("01 02 03 04");
+            let ((rest, _), file) = File::from_bytes((&data, 0), file_id, 4).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(file, File::Other(data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_authentication_keys() {
+        test_file::<RootKey>(
+            File::RootKey(RootKey {
+                key: 0x000102030405060708090A0B0C0D0E0F,
+            }),
+            &hex!("000102030405060708090A0B0C0D0E0F"),
+        );
+        test_file::<UserKey>(
+            File::UserKey(UserKey {
+                key: 0x0F0E0D0C0B0A09080706050403020100,
+            }),
+            &hex!("0F0E0D0C0B0A09080706050403020100"),
+        );
+    }
 }
@@ -1,8 +1,11 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 use crate::physical::ChannelStatus;
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PhyStatus {
     #[deku(endian = "big")]
     pub up_time: u32,
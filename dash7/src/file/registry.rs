@@ -0,0 +1,481 @@
+use alloc::{borrow::Cow, boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{any::Any, fmt, marker::PhantomData};
+
+use deku::{
+    no_std_io::{Read, Seek, Write},
+    prelude::*,
+};
+
+use super::File;
+
+/// A file with a layout defined by the application, see [`FileRegistry`]
+///
+/// Implemented for every type that encodes on its own, like a struct deriving `DekuWrite`.
+pub trait CustomFile: fmt::Debug + Any + Send + Sync {
+    /// Encodes the contents of the file
+    fn encode(&self) -> Result<Vec<u8>, DekuError>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    /// The decoded contents, used when serializing the file. Only the encoded contents are
+    /// serialized without it, see [`Serializable`].
+    #[cfg(feature = "serde")]
+    fn as_serialize(&self) -> Option<&dyn erased_serde::Serialize> {
+        None
+    }
+}
+
+impl<T> CustomFile for T
+where
+    T: DekuContainerWrite + fmt::Debug + Send + Sync + 'static,
+{
+    fn encode(&self) -> Result<Vec<u8>, DekuError> {
+        self.to_bytes()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A custom file that is serialized with its decoded contents
+///
+/// [`Custom::downcast_ref`] gives the wrapped `T`. See [`FileRegistry::register_serializable`].
+#[cfg(feature = "serde")]
+#[derive(Clone, PartialEq)]
+pub struct Serializable<T>(pub T);
+
+/// Formats like the wrapped `T`
+#[cfg(feature = "serde")]
+impl<T: fmt::Debug> fmt::Debug for Serializable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> CustomFile for Serializable<T>
+where
+    T: DekuContainerWrite + serde::Serialize + fmt::Debug + Send + Sync + 'static,
+{
+    fn encode(&self) -> Result<Vec<u8>, DekuError> {
+        self.0.to_bytes()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
+
+    fn as_serialize(&self) -> Option<&dyn erased_serde::Serialize> {
+        Some(&self.0)
+    }
+}
+
+/// Contents of a custom file that were not decoded, eg. after deserializing
+#[derive(DekuWrite, Debug, Clone, PartialEq)]
+struct Raw(Vec<u8>);
+
+/// A typed application file, stored in `file_id`
+#[derive(Clone)]
+pub struct Custom {
+    file_id: u8,
+    value: Arc<dyn CustomFile>,
+}
+
+impl Custom {
+    pub fn new(file_id: u8, value: impl CustomFile) -> Self {
+        Self {
+            file_id,
+            value: Arc::new(value),
+        }
+    }
+
+    pub fn file_id(&self) -> u8 {
+        self.file_id
+    }
+
+    pub fn value(&self) -> &dyn CustomFile {
+        self.value.as_ref()
+    }
+
+    /// The decoded file, if it is a `T`
+    pub fn downcast_ref<T: CustomFile>(&self) -> Option<&T> {
+        self.value.as_any().downcast_ref()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
+        self.value.encode()
+    }
+}
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Custom")
+            .field("file_id", &self.file_id)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// Files are equal when they encode to the same bytes
+impl PartialEq for Custom {
+    fn eq(&self, other: &Self) -> bool {
+        self.file_id == other.file_id
+            && matches!(
+                (self.to_bytes(), other.to_bytes()),
+                (Ok(lhs), Ok(rhs)) if lhs == rhs
+            )
+    }
+}
+
+impl DekuReader<'_> for Custom {
+    fn from_reader_with_ctx<R: Read + Seek>(_: &mut Reader<R>, _: ()) -> Result<Self, DekuError> {
+        Err(DekuError::Parse(Cow::from(
+            "custom files are decoded through a file registry",
+        )))
+    }
+}
+
+impl DekuWriter for Custom {
+    fn to_writer<W: Write + Seek>(&self, writer: &mut Writer<W>, _: ()) -> Result<(), DekuError> {
+        self.to_bytes()?.to_writer(writer, ())
+    }
+}
+
+/// Serialized as its file id, decoded value, if it can be serialized, and encoded contents. Only
+/// the contents are deserialized, as the value can only be decoded by a registry.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SerializedCustom<'a> {
+    file_id: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a dyn erased_serde::Serialize>,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DeserializedCustom {
+    file_id: u8,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Custom {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self.to_bytes().map_err(serde::ser::Error::custom)?;
+        SerializedCustom {
+            file_id: self.file_id,
+            value: self.value.as_serialize(),
+            data,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Custom {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let custom = DeserializedCustom::deserialize(deserializer)?;
+        Ok(Self::new(custom.file_id, Raw(custom.data)))
+    }
+}
+
+/// Decodes the contents of an application file
+pub trait FileDecoder: Send + Sync {
+    /// Decodes all of `data`, which is the whole file
+    fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomFile>, DekuError>;
+}
+
+/// Decodes all of `data` as a `T`
+fn decode_whole<T>(data: &[u8]) -> Result<T, DekuError>
+where
+    T: for<'a> DekuContainerRead<'a>,
+{
+    let ((rest, _), value) = T::from_bytes((data, 0))?;
+    if !rest.is_empty() {
+        return Err(DekuError::Parse(Cow::from(format!(
+            "{} bytes left after decoding",
+            rest.len()
+        ))));
+    }
+    Ok(value)
+}
+
+/// Decodes files as a `T`
+struct Typed<T>(PhantomData<fn() -> T>);
+
+impl<T> FileDecoder for Typed<T>
+where
+    T: for<'a> DekuContainerRead<'a> + CustomFile,
+{
+    fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomFile>, DekuError> {
+        Ok(Box::new(decode_whole::<T>(data)?))
+    }
+}
+
+/// Decodes files as a [`Serializable`] `T`
+#[cfg(feature = "serde")]
+struct TypedSerializable<T>(PhantomData<fn() -> T>);
+
+#[cfg(feature = "serde")]
+impl<T> FileDecoder for TypedSerializable<T>
+where
+    T: for<'a> DekuContainerRead<'a> + DekuContainerWrite + serde::Serialize,
+    T: fmt::Debug + Send + Sync + 'static,
+{
+    fn decode(&self, data: &[u8]) -> Result<Box<dyn CustomFile>, DekuError> {
+        Ok(Box::new(Serializable(decode_whole::<T>(data)?)))
+    }
+}
+
+/// Decoders for application files, keyed on their file id
+///
+/// Files decode as [`File::Other`] by themselves. The registry turns them into [`File::Custom`],
+/// see [`File::from_bytes_with_registry`] and [`crate::app::command::Command::resolve_files`].
+#[derive(Clone, Default)]
+pub struct FileRegistry {
+    decoders: BTreeMap<u8, Arc<dyn FileDecoder>>,
+}
+
+impl FileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes `file_id` as a `T`
+    pub fn register<T>(&mut self, file_id: u8) -> &mut Self
+    where
+        T: for<'a> DekuContainerRead<'a> + CustomFile,
+    {
+        self.register_decoder(file_id, Typed::<T>(PhantomData))
+    }
+
+    /// Like [`Self::register`], but files are serialized with their decoded contents
+    #[cfg(feature = "serde")]
+    pub fn register_serializable<T>(&mut self, file_id: u8) -> &mut Self
+    where
+        T: for<'a> DekuContainerRead<'a> + DekuContainerWrite + serde::Serialize,
+        T: fmt::Debug + Send + Sync + 'static,
+    {
+        self.register_decoder(file_id, TypedSerializable::<T>(PhantomData))
+    }
+
+    pub fn register_decoder(
+        &mut self,
+        file_id: u8,
+        decoder: impl FileDecoder + 'static,
+    ) -> &mut Self {
+        self.decoders.insert(file_id, Arc::new(decoder));
+        self
+    }
+
+    pub fn contains(&self, file_id: u8) -> bool {
+        self.decoders.contains_key(&file_id)
+    }
+
+    /// Ids of the files with a decoder
+    pub fn file_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.decoders.keys().copied()
+    }
+
+    /// Decodes the whole contents of `file_id`. `None` when there is no decoder for it.
+    pub fn decode(&self, file_id: u8, data: &[u8]) -> Option<Result<Custom, DekuError>> {
+        let decoder = self.decoders.get(&file_id)?;
+        Some(decoder.decode(data).map(|value| Custom {
+            file_id,
+            value: Arc::from(value),
+        }))
+    }
+
+    /// Decodes raw file contents in place, when there is a decoder for `file_id`
+    ///
+    /// Like [`File::from_bytes_with_registry`], contents the decoder rejects are an error. The
+    /// file is left as it was then.
+    pub fn resolve(&self, file_id: u8, file: &mut File) -> Result<(), DekuError> {
+        if let File::Other(data) = file {
+            if let Some(custom) = self.decode(file_id, data) {
+                *file = File::Custom(custom?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FileRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileRegistry")
+            .field("file_ids", &self.decoders.keys())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{
+        app::{
+            action::{Action, OpCode},
+            command::Command,
+            operation::{ActionHeader, FileData, FileOffset},
+        },
+        dialect::Dialect,
+        error::Layer,
+    };
+
+    #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[deku(endian = "big")]
+    struct Sensor {
+        temperature: i16,
+        humidity: u8,
+    }
+
+    fn registry() -> FileRegistry {
+        let mut registry = FileRegistry::new();
+        registry.register::<Sensor>(0x40);
+        registry
+    }
+
+    #[test]
+    fn test_decode() {
+        let registry = registry();
+        let ((rest, _), file) =
+            File::from_bytes_with_registry((&hex!("00FA 32"), 0), 0x40, 3, &registry).unwrap();
+        assert!(rest.is_empty());
+        let File::Custom(custom) = &file else {
+            panic!("expected a custom file, got {:?}", file);
+        };
+        assert_eq!(custom.file_id(), 0x40);
+        assert_eq!(
+            custom.downcast_ref::<Sensor>(),
+            Some(&Sensor {
+                temperature: 250,
+                humidity: 50
+            })
+        );
+        assert_eq!(file.file_id(), Some(0x40));
+        assert_eq!(file.to_bytes().unwrap(), hex!("00FA 32"));
+
+        // files without a decoder stay raw, system files are not affected
+        let (_, file) =
+            File::from_bytes_with_registry((&hex!("00FA 32"), 0), 0x41, 3, &registry).unwrap();
+        assert_eq!(file, File::Other(hex!("00FA 32").to_vec()));
+
+        let err = File::from_bytes_with_registry((&hex!("00FA"), 0), 0x40, 2, &registry);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_command() {
+        let registry = registry();
+        let data = hex!("20 40 00 03 00FA 32   20 40 01 02 FA 32");
+        let mut command = Command::try_from(&data[..]).unwrap();
+        command.resolve_files(&registry).unwrap();
+
+        let Action::ReturnFileData(file_data) = &command.actions[0] else {
+            panic!("expected file data");
+        };
+        assert_eq!(
+            file_data.data(),
+            &File::Custom(Custom::new(
+                0x40,
+                Sensor {
+                    temperature: 250,
+                    humidity: 50
+                }
+            ))
+        );
+        // partial data is not decoded
+        let Action::ReturnFileData(file_data) = &command.actions[1] else {
+            panic!("expected file data");
+        };
+        assert_eq!(file_data.data(), &File::Other(hex!("FA 32").to_vec()));
+
+        assert_eq!(command.to_bytes().unwrap(), data);
+        let (_, decoded) =
+            Command::from_bytes_with_registry((&data, 0), Dialect::default(), &registry).unwrap();
+        assert_eq!(decoded, command);
+
+        // the whole file, which does not decode
+        let data = hex!("01 00 00 08   20 40 00 02 FA 32");
+        let err = Command::from_bytes_with_registry((&data, 0), Dialect::default(), &registry)
+            .unwrap_err();
+        assert_eq!(err.bit_offset(), 4 * 8);
+        assert_eq!(
+            err.layers(),
+            &[
+                Layer::Action {
+                    index: 1,
+                    opcode: 0x20
+                },
+                Layer::File(0x40)
+            ]
+        );
+        let mut command = Command::try_from(&data[..]).unwrap();
+        assert!(command.resolve_files(&registry).is_err());
+        let Action::ReturnFileData(file_data) = &command.actions[1] else {
+            panic!("expected file data");
+        };
+        assert_eq!(file_data.data(), &File::Other(hex!("FA 32").to_vec()));
+    }
+
+    #[test]
+    fn test_write() {
+        let sensor = Sensor {
+            temperature: -5,
+            humidity: 80,
+        };
+        let command = Command::new(vec![Action::WriteFileData(FileData::new(
+            ActionHeader::default(),
+            FileOffset::no_offset(0x40),
+            File::Custom(Custom::new(0x40, sensor.clone())),
+            OpCode::WRITE_FILE_DATA,
+        ))]);
+        assert!(command.validate().is_empty());
+
+        let data = command.to_bytes().unwrap();
+        assert_eq!(data, hex!("04 40 00 03 FFFB 50"));
+
+        let mut decoded = Command::try_from(&data[..]).unwrap();
+        decoded.resolve_files(&registry()).unwrap();
+        assert_eq!(decoded, command);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let sensor = Sensor {
+            temperature: 250,
+            humidity: 50,
+        };
+        let file = File::Custom(Custom::new(0x40, Serializable(sensor.clone())));
+        let json = serde_json::to_value(&file).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"Custom": {
+                "file_id": 64,
+                "value": {"temperature": 250, "humidity": 50},
+                "data": [0, 250, 50]
+            }})
+        );
+        assert_eq!(serde_json::from_value::<File>(json).unwrap(), file);
+
+        let mut registry = FileRegistry::new();
+        registry.register_serializable::<Sensor>(0x40);
+        let custom = registry.decode(0x40, &hex!("00FA 32")).unwrap().unwrap();
+        assert_eq!(custom.downcast_ref::<Sensor>(), Some(&sensor));
+        assert!(custom.value().as_serialize().is_some());
+
+        // only the contents of files that are not serializable
+        let file = File::Custom(Custom::new(0x40, sensor));
+        let json = serde_json::to_value(&file).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"Custom": {"file_id": 64, "data": [0, 250, 50]}})
+        );
+        assert_eq!(serde_json::from_value::<File>(json).unwrap(), file);
+    }
+}
@@ -1,6 +1,9 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SecurityKey {
     // TODO: not sure if u128 is available on all archs
     pub key: u128,
@@ -23,41 +23,70 @@
 // //! are composed between them (OR, XOR, NOR, NAND). Without any Logical action, the
 // //! queries are AND'ed.
 
-use mutually_exclusive_features::{exactly_one_of, none_or_one_of};
-exactly_one_of!("_spec", "_subiot", "_wizzilab");
+#![cfg_attr(not(feature = "std"), no_std)]
 
-none_or_one_of!("subiot_v0_0", "subiot_v0_1");
-// none_or_one_of!("wizzilab_v5_3");
+#[cfg(feature = "alloc")]
+#[macro_use]
+extern crate alloc;
+
+#[cfg(test)]
+extern crate std;
+
+use mutually_exclusive_features::none_or_one_of;
+none_or_one_of!("spec_v1_2", "subiot_v0_0", "subiot_v0_1", "wizzilab_v5_3");
+
+/// Protocol dialects
+pub mod dialect;
 
 /// Application layer (ALP)
 pub mod app;
 
 /// Transport layer
+#[cfg(feature = "alloc")]
 pub mod transport;
 
 /// Network layer
+#[cfg(feature = "alloc")]
 pub mod network;
 
 /// Session layer
+#[cfg(feature = "alloc")]
 pub mod session;
 
 /// The Physical Layer (PHY 5) defines the spectrum, modulation and channel coding characteristics.
+#[cfg(feature = "alloc")]
 pub mod physical;
 
 /// Data/Filesystem layer
+#[cfg(feature = "alloc")]
 pub mod data;
 
 /// Datalink layer
+#[cfg(feature = "alloc")]
 pub mod link;
 
+/// Decoding errors
+#[cfg(feature = "alloc")]
+pub mod error;
+
+#[cfg(feature = "alloc")]
+pub use error::Error;
+
 /// Utility functions
+#[cfg(feature = "alloc")]
 pub(crate) mod utils;
 
 /// Reusable types
+#[cfg(feature = "alloc")]
 pub mod types;
 
 /// System files
+#[cfg(feature = "alloc")]
 pub mod file;
 
-#[cfg(test)]
+/// Serial modem interface framing
+#[cfg(feature = "alloc")]
+pub mod serial;
+
+#[cfg(all(test, feature = "alloc"))]
 mod test_tools;
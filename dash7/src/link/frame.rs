@@ -1,11 +1,77 @@
-use deku::prelude::*;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use deku::{ctx::Endian, no_std_io, prelude::*};
 
 use crate::{
-    app::operation::Length,
+    dialect::Dialect,
+    error::{Error, Location},
+    file::FileRegistry,
     network::{self, Address, AddressType},
+    utils::decode,
 };
 
+/// CRC-16 of link layer frames: polynomial 0x8005, initial value 0xFFFF
+/// SPEC: 9.2.2
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+/// Checks `crc` against the CRC of `data`, which precedes it at the start of the frame
+fn check_crc(data: &[u8], crc: u16) -> Result<(), Error> {
+    let expected = crc16(data);
+    if crc != expected {
+        return Err(Error::InvalidValue {
+            location: Location::new(data.len() * 8),
+            field: "crc16",
+            expected: format!("{:#06X}", expected).into(),
+            found: crc as u64,
+        });
+    }
+    Ok(())
+}
+
+/// The CRC covers whole bytes, so a frame checked against it has to start on a byte boundary
+fn check_aligned(input: (&[u8], usize)) -> Result<(), Error> {
+    if input.1 != 0 {
+        return Err(Error::InvalidValue {
+            location: Location::new(input.1),
+            field: "bit offset",
+            expected: "0".into(),
+            found: input.1 as u64,
+        });
+    }
+    Ok(())
+}
+
+/// Writes `body` followed by its CRC
+fn write_with_crc<W>(writer: &mut Writer<W>, body: &[u8]) -> Result<(), DekuError>
+where
+    W: no_std_io::Write + no_std_io::Seek,
+{
+    body.to_writer(writer, ())?;
+    crc16(body).to_writer(writer, Endian::Big)
+}
+
+fn encode<F>(write: F) -> Result<Vec<u8>, DekuError>
+where
+    F: FnOnce(&mut Writer<&mut no_std_io::Cursor<&mut Vec<u8>>>) -> Result<(), DekuError>,
+{
+    let mut data = Vec::new();
+    let mut cursor = no_std_io::Cursor::new(&mut data);
+    let mut writer = Writer::new(&mut cursor);
+    write(&mut writer)?;
+    writer.finalize()?;
+    Ok(data)
+}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BackgroundFrameControl {
     address_type: AddressType,
 
@@ -13,15 +79,173 @@ pub struct BackgroundFrameControl {
     tag_id: u8,
 }
 
-#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+impl BackgroundFrameControl {
+    pub fn new(address_type: AddressType, tag_id: u8) -> Self {
+        Self {
+            address_type,
+            tag_id,
+        }
+    }
+
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub fn tag_id(&self) -> u8 {
+        self.tag_id
+    }
+}
+
+/// Payload of a background frame, by the tag id in its control
+/// SPEC: 9.2.3
+#[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "tag_id: u8", id = "tag_id")]
+pub enum BackgroundPayload {
+    /// Ad-hoc synchronization, which advertises a foreground frame
+    #[deku(id = "BackgroundPayload::AD_HOC_SYNC")]
+    AdHocSync {
+        /// Estimated time of arrival of the foreground frame, in ticks of 1/1024 s
+        #[deku(endian = "big")]
+        eta: u16,
+    },
+
+    /// Payload of a tag without a definition
+    #[deku(id_pat = "_")]
+    Other {
+        #[deku(skip, default = "tag_id")]
+        tag_id: u8,
+        #[deku(endian = "big")]
+        data: u16,
+    },
+}
+
+impl Default for BackgroundPayload {
+    fn default() -> Self {
+        Self::AdHocSync { eta: 0 }
+    }
+}
+
+impl BackgroundPayload {
+    pub const AD_HOC_SYNC: u8 = 0x00;
+
+    /// Number of ticks in a second
+    pub const TICKS_PER_SECOND: u64 = 1024;
+
+    pub fn tag_id(&self) -> u8 {
+        match self {
+            Self::AdHocSync { .. } => Self::AD_HOC_SYNC,
+            Self::Other { tag_id, .. } => *tag_id,
+        }
+    }
+
+    /// Time until the advertised foreground frame, for ad-hoc synchronization
+    pub fn eta(&self) -> Option<Duration> {
+        match self {
+            Self::AdHocSync { eta } => Some(Duration::from_nanos(
+                *eta as u64 * 1_000_000_000 / Self::TICKS_PER_SECOND,
+            )),
+            Self::Other { .. } => None,
+        }
+    }
+}
+
+/// The tag id and CRC are derived when encoding
+#[derive(DekuRead, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BackgroundFrame {
     subnet: u8,
     control: BackgroundFrameControl,
-    payload: u16,
+    #[deku(ctx = "control.tag_id")]
+    payload: BackgroundPayload,
+    #[deku(endian = "big")]
     crc16: u16,
 }
 
+impl BackgroundFrame {
+    const SIZE: usize = 6;
+
+    pub fn new(subnet: u8, address_type: AddressType, payload: BackgroundPayload) -> Self {
+        let mut frame = Self {
+            subnet,
+            control: BackgroundFrameControl::new(address_type, payload.tag_id()),
+            payload,
+            crc16: 0,
+        };
+        frame.crc16 = frame.body().map_or(0, |body| crc16(&body));
+        frame
+    }
+
+    /// Ad-hoc synchronization frames advertising a foreground frame `eta` ticks from the first
+    /// one, sent every `period` ticks. The last one is sent less than `period` ticks before the
+    /// foreground frame.
+    pub fn train(
+        subnet: u8,
+        address_type: AddressType,
+        eta: u16,
+        period: u16,
+    ) -> impl Iterator<Item = Self> {
+        let period = period.max(1);
+        (0..=eta / period).map(move |index| {
+            let eta = eta - index * period;
+            Self::new(subnet, address_type, BackgroundPayload::AdHocSync { eta })
+        })
+    }
+
+    /// Like [`DekuContainerRead::from_bytes`], but rejects frames of which the CRC does not match
+    pub fn from_bytes_strict(input: (&'_ [u8], usize)) -> Result<((&'_ [u8], usize), Self), Error> {
+        check_aligned(input)?;
+        let (rest, frame) = decode::<Self, _>(input, ())?;
+        check_crc(&input.0[..Self::SIZE - 2], frame.crc16)?;
+        Ok((rest, frame))
+    }
+
+    pub fn subnet(&self) -> u8 {
+        self.subnet
+    }
+
+    pub fn control(&self) -> &BackgroundFrameControl {
+        &self.control
+    }
+
+    pub fn payload(&self) -> &BackgroundPayload {
+        &self.payload
+    }
+
+    /// The CRC the frame was decoded or constructed with
+    pub fn crc16(&self) -> u16 {
+        self.crc16
+    }
+
+    fn body(&self) -> Result<Vec<u8>, DekuError> {
+        let control = BackgroundFrameControl::new(self.control.address_type, self.payload.tag_id());
+        encode(|writer| {
+            self.subnet.to_writer(writer, ())?;
+            control.to_writer(writer, ())?;
+            self.payload.to_writer(writer, control.tag_id)
+        })
+    }
+
+    /// Checks the CRC the frame was decoded with against its contents
+    pub fn verify(&self) -> Result<(), Error> {
+        let body = self.body().map_err(|err| Error::from_deku(err, 0))?;
+        check_crc(&body, self.crc16)
+    }
+}
+
+impl DekuWriter for BackgroundFrame {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, _: ()) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        write_with_crc(writer, &self.body()?)
+    }
+}
+
+impl DekuContainerWrite for BackgroundFrame {}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForegroundFrameControl {
     address_type: AddressType,
 
@@ -29,16 +253,440 @@ pub struct ForegroundFrameControl {
     eirp_index: u8,
 }
 
-#[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+impl ForegroundFrameControl {
+    pub fn new(address_type: AddressType, eirp_index: u8) -> Self {
+        Self {
+            address_type,
+            eirp_index,
+        }
+    }
+
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub fn eirp_index(&self) -> u8 {
+        self.eirp_index
+    }
+}
+
+/// Length, address type and CRC are derived when encoding
+#[derive(DekuRead, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "dialect: Dialect, is_request: Option<bool>",
+    ctx_default = "Dialect::default(), None"
+)]
 pub struct ForegroundFrame {
-    length: Length,
+    /// Number of bytes after the length, CRC included
+    length: u8,
     subnet: u8,
     control: ForegroundFrameControl,
 
     #[deku(ctx = "control.address_type")]
     target_address: Address,
 
-    #[deku(ctx = "Into::<u32>::into(*length)")]
+    /// Ends before the CRC, `length - 1` bytes into the frame
+    #[deku(ctx = "(*length as u32).saturating_sub(1), dialect, is_request")]
     frame: network::Frame,
+    #[deku(endian = "big")]
     crc16: u16,
 }
+
+impl ForegroundFrame {
+    /// A frame with the length and CRC it is encoded with in the default dialect, see
+    /// [`Self::update`] for other dialects
+    pub fn new(subnet: u8, eirp_index: u8, target_address: Address, frame: network::Frame) -> Self {
+        let mut frame = Self {
+            length: 0,
+            subnet,
+            control: ForegroundFrameControl::new(target_address.deku_id().unwrap(), eirp_index),
+            target_address,
+            frame,
+            crc16: 0,
+        };
+        // encoding reports the same error again
+        let _ = frame.update(Dialect::default());
+        frame
+    }
+
+    pub fn from_bytes_with_dialect(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        decode(input, (dialect, None))
+    }
+
+    /// Decodes a frame of which it is known whether it is a request, see
+    /// [`crate::transport::TransportDecoder`]
+    pub fn from_bytes_with_direction(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+        is_request: bool,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        decode(input, (dialect, Some(is_request)))
+    }
+
+    /// Like [`Self::from_bytes_with_dialect`], but rejects frames of which the CRC does not match
+    pub fn from_bytes_strict(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        check_aligned(input)?;
+        let (rest, frame) = decode::<Self, _>(input, (dialect, None))?;
+        let size = input.0.len() - rest.0.len();
+        check_crc(&input.0[..size - 2], frame.crc16)?;
+        Ok((rest, frame))
+    }
+
+    /// Number of bytes after the length, as decoded or updated
+    pub fn length(&self) -> u8 {
+        self.length
+    }
+
+    pub fn subnet(&self) -> u8 {
+        self.subnet
+    }
+
+    pub fn control(&self) -> &ForegroundFrameControl {
+        &self.control
+    }
+
+    pub fn target_address(&self) -> &Address {
+        &self.target_address
+    }
+
+    pub fn frame(&self) -> &network::Frame {
+        &self.frame
+    }
+
+    /// Decodes the application files in the command, see [`crate::file::FileRegistry`]
+    pub fn resolve_files(&mut self, registry: &FileRegistry) -> Result<(), Error> {
+        self.frame.resolve_files(registry)
+    }
+
+    /// The CRC, as decoded or updated
+    pub fn crc16(&self) -> u16 {
+        self.crc16
+    }
+
+    /// Sets the length and CRC to the ones the frame is encoded with
+    pub fn update(&mut self, dialect: Dialect) -> Result<(), DekuError> {
+        let body = self.body(dialect)?;
+        self.length = body[0];
+        self.crc16 = crc16(&body);
+        Ok(())
+    }
+
+    /// Encodes the frame up to the CRC
+    fn body(&self, dialect: Dialect) -> Result<Vec<u8>, DekuError> {
+        let control =
+            ForegroundFrameControl::new(self.target_address.deku_id()?, self.control.eirp_index);
+        let mut body = encode(|writer| {
+            // placeholder for the length
+            0u8.to_writer(writer, ())?;
+            self.subnet.to_writer(writer, ())?;
+            control.to_writer(writer, ())?;
+            self.target_address
+                .to_writer(writer, control.address_type)?;
+            self.frame.to_writer(writer, (0, dialect, None))
+        })?;
+        // the length covers the CRC, but not itself
+        body[0] = u8::try_from(body.len() + 1).map_err(|_| {
+            DekuError::InvalidParam(
+                format!("a frame of {} bytes is too long", body.len() + 2).into(),
+            )
+        })?;
+        Ok(body)
+    }
+
+    /// Checks the CRC the frame was decoded with against its contents
+    pub fn verify(&self, dialect: Dialect) -> Result<(), Error> {
+        let body = self.body(dialect).map_err(|err| Error::from_deku(err, 0))?;
+        check_crc(&body, self.crc16)
+    }
+
+    pub fn to_bytes_with_dialect(&self, dialect: Dialect) -> Result<Vec<u8>, DekuError> {
+        encode(|writer| self.to_writer(writer, dialect))
+    }
+}
+
+impl DekuWriter<Dialect> for ForegroundFrame {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, dialect: Dialect) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        write_with_crc(writer, &self.body(dialect)?)
+    }
+}
+
+impl DekuWriter for ForegroundFrame {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, _: ()) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        self.to_writer(writer, Dialect::default())
+    }
+}
+
+impl DekuContainerWrite for ForegroundFrame {}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{
+        app::command::Command,
+        link::AccessClass,
+        network::{HoppingControl, NlsState},
+        transport,
+        types::VarInt,
+    };
+
+    const FOREGROUND: [u8; 22] = hex!("15 05 90 0011223344556677 10 01 80 11 22 01000008 B8F3");
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0xAEE7);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn test_foreground() {
+        let dialect = Dialect::default();
+        let ((rest, _), frame) =
+            ForegroundFrame::from_bytes_strict((&FOREGROUND, 0), dialect).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(frame.crc16(), 0xB8F3);
+        assert_eq!(frame.target_address, Address::UId(0x0011223344556677));
+        assert_eq!(frame.verify(dialect), Ok(()));
+        assert_eq!(frame.to_bytes_with_dialect(dialect).unwrap(), FOREGROUND);
+
+        let mut corrupted = FOREGROUND;
+        corrupted[20] = 0x00;
+        let (_, frame) =
+            ForegroundFrame::from_bytes_with_dialect((&corrupted, 0), dialect).unwrap();
+        assert!(frame.verify(dialect).is_err());
+        // the CRC is recomputed when encoding
+        assert_eq!(frame.to_bytes().unwrap(), FOREGROUND);
+
+        assert!(matches!(
+            ForegroundFrame::from_bytes_strict((&FOREGROUND, 4), dialect),
+            Err(Error::InvalidValue {
+                field: "bit offset",
+                ..
+            })
+        ));
+
+        let err = ForegroundFrame::from_bytes_strict((&corrupted, 0), dialect).unwrap_err();
+        assert_eq!(
+            err,
+            Error::InvalidValue {
+                location: Location::new(20 * 8),
+                field: "crc16",
+                expected: "0xB8F3".into(),
+                found: 0x00F3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_construct() {
+        let transport = transport::Frame {
+            control: transport::Control {
+                is_dialog_start: true,
+                ..Default::default()
+            },
+            dialog_id: 0x11,
+            transaction_id: 0x22,
+            command: Command::builder().read_file(0x00, 0, 8).build().unwrap(),
+            ..Default::default()
+        };
+        let network = network::Frame::new(
+            AccessClass::new(0, 1),
+            Address::NoId,
+            NlsState::None,
+            transport,
+        );
+        let frame = ForegroundFrame::new(0x05, 0x10, Address::UId(0x0011223344556677), network);
+        assert_eq!(frame.length(), 0x15);
+        assert_eq!(frame.crc16(), 0xB8F3);
+        assert_eq!(frame.control().address_type(), AddressType::UId);
+        assert_eq!(frame.control().eirp_index(), 0x10);
+        assert_eq!(frame.to_bytes().unwrap(), FOREGROUND);
+        assert_eq!(
+            ForegroundFrame::from_bytes_strict((&FOREGROUND, 0), Dialect::default())
+                .unwrap()
+                .1,
+            frame
+        );
+
+        // hopping, with the origin addressed by its VID
+        let network = network::Frame::new(
+            AccessClass::new(0, 1),
+            Address::VId(0xABCD),
+            NlsState::None,
+            frame.frame().frame().clone(),
+        )
+        .with_hopping(HoppingControl::new(true, AddressType::NoId));
+        assert!(network.control().has_hopping());
+        let frame = ForegroundFrame::new(0x05, 0x10, Address::NoId, network);
+        let data = frame.to_bytes().unwrap();
+        assert_eq!(data[..9], hex!("10 05 50 70 50 01 ABCD 80"));
+        assert_eq!(data.len(), 0x11);
+        let (_, decoded) =
+            ForegroundFrame::from_bytes_strict((&data, 0), Dialect::default()).unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(
+            decoded.frame().hopping_control(),
+            frame.frame().hopping_control()
+        );
+        assert_eq!(decoded.frame().origin_address(), &Address::VId(0xABCD));
+    }
+
+    #[test]
+    fn test_long_foreground() {
+        // a length of 0x45 has the top bits a compressed length uses for its size
+        let data = hex!(
+            "45 05 90 0011223344556677 10 01 80 11 22"
+            "04 40 00 30 000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F"
+            "202122232425262728292A2B2C2D2E2F B38F"
+        );
+        let ((rest, _), frame) =
+            ForegroundFrame::from_bytes_strict((&data, 0), Dialect::default()).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(frame.length(), 0x45);
+        assert_eq!(frame.to_bytes().unwrap(), data);
+
+        let transport = transport::Frame {
+            control: transport::Control {
+                is_dialog_start: true,
+                ..Default::default()
+            },
+            dialog_id: 0x11,
+            transaction_id: 0x22,
+            command: Command::builder()
+                .write_file_data(0x40, 0, (0..48).collect::<Vec<u8>>())
+                .build()
+                .unwrap(),
+            ..Default::default()
+        };
+        let network = network::Frame::new(
+            AccessClass::new(0, 1),
+            Address::NoId,
+            NlsState::None,
+            transport,
+        );
+        let mut constructed =
+            ForegroundFrame::new(0x05, 0x10, Address::UId(0x0011223344556677), network);
+        assert_eq!(constructed, frame);
+        constructed.update(Dialect::default()).unwrap();
+        assert_eq!(constructed, frame);
+    }
+
+    #[test]
+    fn test_direction() {
+        // second request of the dialog, which has a Tc without being a dialog start
+        let transport = transport::Frame {
+            control: transport::Control {
+                is_ack_requested: true,
+                ..Default::default()
+            },
+            dialog_id: 0x11,
+            transaction_id: 0x23,
+            congestion_timeout: Some(VarInt::new(5, false).unwrap()),
+            command: Command::builder().read_file(0x00, 0, 8).build().unwrap(),
+            ..Default::default()
+        };
+        let network = network::Frame::new(
+            AccessClass::new(0, 1),
+            Address::NoId,
+            NlsState::None,
+            transport,
+        );
+        let frame = ForegroundFrame::new(0x05, 0x10, Address::NoId, network);
+        let data = frame.to_bytes().unwrap();
+
+        let (_, decoded) =
+            ForegroundFrame::from_bytes_with_direction((&data, 0), Dialect::default(), true)
+                .unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(decoded.verify(Dialect::default()), Ok(()));
+
+        // without knowing, the Tc is read as part of the command
+        assert!(ForegroundFrame::from_bytes_with_dialect((&data, 0), Dialect::default()).is_err());
+    }
+
+    #[test]
+    fn test_background() {
+        let data = hex!("05 01 0010 4450");
+        let constructed = BackgroundFrame::new(
+            0x05,
+            AddressType::NbId,
+            BackgroundPayload::Other {
+                tag_id: 1,
+                data: 0x0010,
+            },
+        );
+        assert_eq!(constructed.crc16(), 0x4450);
+        assert_eq!(constructed.to_bytes().unwrap(), data);
+        let ((rest, _), frame) = BackgroundFrame::from_bytes_strict((&data, 0)).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(frame.verify(), Ok(()));
+        assert_eq!(frame.to_bytes().unwrap(), data);
+
+        let corrupted = hex!("05 01 0011 4450");
+        let (_, frame) = BackgroundFrame::from_bytes((&corrupted, 0)).unwrap();
+        assert!(frame.verify().is_err());
+        assert!(BackgroundFrame::from_bytes_strict((&corrupted, 0)).is_err());
+        assert_eq!(frame.to_bytes().unwrap()[..4], corrupted[..4]);
+
+        // the frame shifted by 4 bits
+        let shifted = hex!("00 50 10 01 04 45 00");
+        assert_eq!(
+            BackgroundFrame::from_bytes((&shifted, 4)).unwrap().1,
+            constructed
+        );
+        assert_eq!(
+            BackgroundFrame::from_bytes_strict((&shifted, 4)).unwrap_err(),
+            Error::InvalidValue {
+                location: Location::new(4),
+                field: "bit offset",
+                expected: "0".into(),
+                found: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_ad_hoc_sync() {
+        let (_, frame) = BackgroundFrame::from_bytes((&hex!("05 40 0400 0000"), 0)).unwrap();
+        assert_eq!(frame.control().address_type(), AddressType::NoId);
+        assert_eq!(
+            frame.payload(),
+            &BackgroundPayload::AdHocSync { eta: 0x0400 }
+        );
+        assert_eq!(frame.payload().eta(), Some(Duration::from_secs(1)));
+
+        let train: Vec<_> = BackgroundFrame::train(0x05, AddressType::NoId, 1000, 300).collect();
+        let etas: Vec<_> = train
+            .iter()
+            .map(|frame| frame.payload().eta().unwrap().as_millis())
+            .collect();
+        // 1000, 700, 400 and 100 ticks
+        assert_eq!(etas, vec![976, 683, 390, 97]);
+
+        for frame in train {
+            let data = frame.to_bytes().unwrap();
+            let (_, decoded) = BackgroundFrame::from_bytes_strict((&data, 0)).unwrap();
+            assert_eq!(decoded, frame);
+        }
+        assert_eq!(
+            BackgroundFrame::train(0x05, AddressType::NoId, 100, 300)
+                .map(|frame| frame.payload().clone())
+                .collect::<Vec<_>>(),
+            vec![BackgroundPayload::AdHocSync { eta: 100 }]
+        );
+    }
+}
@@ -1,13 +1,19 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
-use crate::physical::{ChannelHeader, SubBand};
+use crate::physical::{ChannelError, ChannelHeader, SubBand};
 use crate::types::VarInt;
 use crate::utils::{read_array, write_array};
 
 mod frame;
-pub use frame::{BackgroundFrame, BackgroundFrameControl, ForegroundFrame, ForegroundFrameControl};
+pub use frame::{
+    crc16, BackgroundFrame, BackgroundFrameControl, BackgroundPayload, ForegroundFrame,
+    ForegroundFrameControl,
+};
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SubProfile {
     pub subband_bitmap: u8,
     pub scan_automation_period: VarInt,
@@ -20,6 +26,7 @@ pub struct SubProfile {
 /// selected. As a result, only subprofiles performing scan automation (6.7) are
 /// selectable.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccessClass {
     #[deku(bits = 4)]
     pub specifier: u8,
@@ -41,6 +48,7 @@ impl AccessClass {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccessProfile {
     pub channel_header: ChannelHeader,
 
@@ -56,3 +64,12 @@ pub struct AccessProfile {
     )]
     pub sub_bands: [SubBand; 8],
 }
+
+impl AccessProfile {
+    /// Checks that every sub-band only holds channels of the channel header
+    pub fn validate(&self) -> Result<(), ChannelError> {
+        self.sub_bands
+            .iter()
+            .try_for_each(|sub_band| sub_band.validate(&self.channel_header))
+    }
+}
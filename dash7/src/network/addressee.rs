@@ -1,28 +1,30 @@
-use deku::prelude::*;
+use alloc::vec::Vec;
 
-use crate::link::AccessClass;
+use deku::prelude::*;
 
-#[cfg(feature = "_wizzilab")]
-use crate::transport::GroupCondition;
+use crate::{dialect::Dialect, link::AccessClass, transport::GroupCondition};
 
 use super::{Address, AddressType, NlsMethod, NlsState};
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::default()")]
 pub struct Addressee {
-    /// Group condition
-    #[cfg(feature = "_wizzilab")]
+    /// Group condition. Wizzilab only
+    #[deku(skip, cond = "!dialect.is_wizzilab()", default = "GroupCondition::Any")]
     pub group_condition: GroupCondition,
 
-    #[cfg_attr(not(feature = "_wizzilab"), deku(pad_bits_before = "2"))]
-    #[deku(update = "self.address.deku_id().unwrap()")]
+    #[deku(
+        pad_bits_before = "if dialect.is_wizzilab() { 0 } else { 2 }",
+        update = "self.address.deku_id().unwrap()"
+    )]
     address_type: AddressType,
 
-    /// Use VID instead of UID when possible
-    #[cfg(feature = "_wizzilab")]
-    #[deku(bits = 1)]
+    /// Use VID instead of UID when possible. Wizzilab only
+    #[deku(bits = 1, skip, cond = "!dialect.is_wizzilab()", default = "false")]
     pub use_vid: bool,
 
-    #[deku(update = "self.nls_state.deku_id().unwrap()")]
+    #[deku(ctx = "dialect", update = "self.nls_state.deku_id().unwrap()")]
     nls_method: NlsMethod,
 
     pub access_class: AccessClass,
@@ -35,29 +37,11 @@ pub struct Addressee {
 }
 
 impl Addressee {
-    #[cfg(not(feature = "_wizzilab"))]
     pub fn new(address: Address, nls_state: NlsState, access_class: AccessClass) -> Self {
         Self {
+            group_condition: GroupCondition::Any,
             address_type: address.deku_id().unwrap(),
-            nls_method: nls_state.deku_id().unwrap(),
-            access_class,
-            address,
-            nls_state,
-        }
-    }
-
-    #[cfg(feature = "_wizzilab")]
-    pub fn new(
-        use_vid: bool,
-        group_condition: GroupCondition,
-        address: Address,
-        nls_state: NlsState,
-        access_class: AccessClass,
-    ) -> Self {
-        Self {
-            use_vid,
-            group_condition,
-            address_type: address.deku_id().unwrap(),
+            use_vid: false,
             nls_method: nls_state.deku_id().unwrap(),
             access_class,
             address,
@@ -1,19 +1,32 @@
-use deku::prelude::*;
+use alloc::vec::Vec;
+
+use deku::{
+    ctx::{BitSize, Endian},
+    no_std_io,
+    prelude::*,
+};
 
 mod addressee;
+#[allow(clippy::module_inception)]
 mod network;
 
 pub use addressee::Addressee;
 pub use network::{Control, Frame, HoppingControl};
 
-use crate::types::VarInt;
+/// Network layer security (NLS)
+#[cfg(feature = "security")]
+pub mod security;
+
+use crate::{dialect::Dialect, types::VarInt};
 
 /// Network Layer Security
 /// SPEC: 7.4
+///
+/// The spec encodes the method on 4 bits, of which the upper one is RFU. Wizzilab drops that RFU
+/// bit. Without a dialect as context, only the lower 3 bits are read.
 #[derive(DekuRead, DekuWrite, Default, Debug, Copy, Clone, PartialEq)]
-#[cfg_attr(not(feature = "_wizzilab"), deku(bits = 4))]
-#[cfg_attr(feature = "_wizzilab", deku(bits = 3))]
-#[deku(id_type = "u8")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(bits = 3, id_type = "u8")]
 pub enum NlsMethod {
     /// No security
     #[default]
@@ -49,8 +62,36 @@ pub enum NlsMethod {
     AesCcm32,
 }
 
+impl<'a> DekuReader<'a, Dialect> for NlsMethod {
+    fn from_reader_with_ctx<R>(reader: &mut Reader<R>, dialect: Dialect) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        if !dialect.is_wizzilab() {
+            let rfu = u8::from_reader_with_ctx(reader, (Endian::Big, BitSize(1)))?;
+            if rfu != 0 {
+                return Err(DekuError::Parse("NlsMethod: RFU bit set".into()));
+            }
+        }
+        Self::from_reader_with_ctx(reader, ())
+    }
+}
+
+impl DekuWriter<Dialect> for NlsMethod {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, dialect: Dialect) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        if !dialect.is_wizzilab() {
+            0u8.to_writer(writer, (Endian::Big, BitSize(1)))?;
+        }
+        self.to_writer(writer, ())
+    }
+}
+
 /// Encryption algorithm for over-the-air packets
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "nls_method: NlsMethod", id = "nls_method")]
 pub enum NlsState {
     #[default]
@@ -73,6 +114,7 @@ pub enum NlsState {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum AddressType {
     /// Broadcast to an estimated number of receivers, encoded in compressed format on a byte.
@@ -91,6 +133,7 @@ pub enum AddressType {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "address_type: AddressType", id = "address_type")]
 pub enum Address {
     /// Broadcast to an estimated number of receivers, encoded in compressed format on a byte.
@@ -102,10 +145,18 @@ pub enum Address {
     NoId,
     /// Unicast to target via its UID (Unique Dash7 ID)
     #[deku(id = "AddressType::UId")]
-    UId(#[deku(endian = "big")] u64),
+    UId(
+        #[deku(endian = "big")]
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex"))]
+        u64,
+    ),
     /// Unicast to target via its VID (Virtual ID)
     #[deku(id = "AddressType::VId")]
-    VId(#[deku(endian = "big")] u16),
+    VId(
+        #[deku(endian = "big")]
+        #[cfg_attr(feature = "serde", serde(with = "crate::utils::hex"))]
+        u16,
+    ),
 }
 
 #[cfg(test)]
@@ -113,17 +164,15 @@ mod tests {
     use super::*;
     use hex_literal::hex;
 
-    #[cfg(feature = "_wizzilab")]
-    use crate::transport::GroupCondition;
-    use crate::{link::AccessClass, test_tools::test_item};
+    use crate::{
+        link::AccessClass,
+        test_tools::{test_item, test_item_ctx},
+        transport::GroupCondition,
+    };
     #[test]
     fn test_vid_aesccm32() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::VId(0xABCD),
                 NlsState::AesCcm32(hex!("00 11 22 33 44")),
                 AccessClass::new(0x0F, 0x0F),
@@ -135,15 +184,7 @@ mod tests {
     #[test]
     fn test_noid_none() {
         test_item(
-            Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
-                Address::NoId,
-                NlsState::None,
-                AccessClass::default(),
-            ),
+            Addressee::new(Address::NoId, NlsState::None, AccessClass::default()),
             &[0b0010000, 0],
         );
     }
@@ -152,10 +193,6 @@ mod tests {
     fn test_nbid_none() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::NbId(VarInt::new(0, false).unwrap()),
                 NlsState::None,
                 AccessClass::default(),
@@ -167,15 +204,7 @@ mod tests {
     #[test]
     fn test_uid_none() {
         test_item(
-            Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
-                Address::UId(0),
-                NlsState::None,
-                AccessClass::default(),
-            ),
+            Addressee::new(Address::UId(0), NlsState::None, AccessClass::default()),
             &[0b00100000, 0, 0, 0, 0, 0, 0, 0, 0, 0],
         );
     }
@@ -184,10 +213,6 @@ mod tests {
     fn test_nbid_aesctr() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::NbId(VarInt::new(1, false).unwrap()),
                 NlsState::AesCtr([0, 1, 2, 3, 4]),
                 AccessClass::default(),
@@ -199,15 +224,7 @@ mod tests {
     #[test]
     fn test_vid_none() {
         test_item(
-            Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
-                Address::VId(0x1234),
-                NlsState::None,
-                AccessClass::new(0, 5),
-            ),
+            Addressee::new(Address::VId(0x1234), NlsState::None, AccessClass::new(0, 5)),
             &[0b00110000, 5, 0b00010010, 0b00110100],
         );
     }
@@ -216,10 +233,6 @@ mod tests {
     fn test_uid_none2() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::UId(0x1234567890123456),
                 NlsState::None,
                 AccessClass::new(0x06, 0x09),
@@ -235,10 +248,6 @@ mod tests {
     fn test_noid_aescbcmac128() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::NoId,
                 NlsState::AesCbcMac128([10, 20, 30, 40, 50]),
                 AccessClass::new(0x0B, 0x0E),
@@ -251,10 +260,6 @@ mod tests {
     fn test_nbid_none2() {
         test_item(
             Addressee::new(
-                #[cfg(feature = "_wizzilab")]
-                false,
-                #[cfg(feature = "_wizzilab")]
-                GroupCondition::Any,
                 Address::NbId(VarInt::new(100, false).unwrap()),
                 NlsState::None,
                 AccessClass::new(0, 0),
@@ -262,4 +267,61 @@ mod tests {
             &[0, 0, 0x39],
         );
     }
+
+    #[test]
+    fn test_vid_aesccm32_wizzilab() {
+        let mut addressee = Addressee::new(
+            Address::VId(0xABCD),
+            NlsState::AesCcm32(hex!("00 11 22 33 44")),
+            AccessClass::new(0x0F, 0x0F),
+        );
+        addressee.group_condition = GroupCondition::Equal;
+        addressee.use_vid = true;
+
+        test_item_ctx(
+            addressee,
+            &hex!("BF FF ABCD 0011223344"),
+            Dialect::WizzilabV5_3,
+        )
+    }
+
+    #[test]
+    fn test_use_vid_dialect() {
+        let data = [0b00011000, 0];
+
+        let mut cursor = no_std_io::Cursor::new(&data);
+        let mut reader = Reader::new(&mut cursor);
+        assert!(Addressee::from_reader_with_ctx(&mut reader, Dialect::SpecV1_2).is_err());
+
+        let mut cursor = no_std_io::Cursor::new(&data);
+        let mut reader = Reader::new(&mut cursor);
+        let addressee =
+            Addressee::from_reader_with_ctx(&mut reader, Dialect::WizzilabV5_3).unwrap();
+        assert!(addressee.use_vid);
+        assert_eq!(addressee.address, Address::NoId);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_address() {
+        let uid = Address::UId(0x3938_3432_0046_002E);
+        assert_eq!(
+            serde_json::to_string(&uid).unwrap(),
+            r#"{"UId":"393834320046002E"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Address>(r#"{"UId":"393834320046002E"}"#).unwrap(),
+            uid
+        );
+
+        let vid = Address::VId(0x0A);
+        assert_eq!(serde_json::to_string(&vid).unwrap(), r#"{"VId":"000A"}"#);
+        assert_eq!(
+            serde_json::from_str::<Address>(r#"{"VId":"0x000A"}"#).unwrap(),
+            vid
+        );
+        assert!(serde_json::from_str::<Address>(r#"{"VId":"10000"}"#).is_err());
+
+        assert_eq!(serde_json::to_string(&Address::NoId).unwrap(), r#""NoId""#);
+    }
 }
@@ -1,23 +1,59 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
-use crate::{link::AccessClass, transport};
+use crate::{dialect::Dialect, error::Error, file::FileRegistry, link::AccessClass, transport};
 
-use super::{Address, AddressType, NlsMethod};
+use super::{Address, AddressType, NlsMethod, NlsState};
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Control {
     #[deku(bits = 1)]
-    has_no_origin_access_id: bool,
+    pub(super) has_no_origin_access_id: bool,
     #[deku(bits = 1)]
-    has_hopping: bool,
+    pub(super) has_hopping: bool,
 
-    origin_address_type: AddressType,
+    pub(super) origin_address_type: AddressType,
 
     #[deku(pad_bits_before = "1")]
-    nls_method: NlsMethod,
+    pub(super) nls_method: NlsMethod,
+}
+
+impl Control {
+    pub fn new(
+        has_no_origin_access_id: bool,
+        has_hopping: bool,
+        origin_address_type: AddressType,
+        nls_method: NlsMethod,
+    ) -> Self {
+        Self {
+            has_no_origin_access_id,
+            has_hopping,
+            origin_address_type,
+            nls_method,
+        }
+    }
+
+    pub fn has_no_origin_access_id(&self) -> bool {
+        self.has_no_origin_access_id
+    }
+
+    pub fn has_hopping(&self) -> bool {
+        self.has_hopping
+    }
+
+    pub fn origin_address_type(&self) -> AddressType {
+        self.origin_address_type
+    }
+
+    pub fn nls_method(&self) -> NlsMethod {
+        self.nls_method
+    }
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HoppingControl {
     /// Hopping counter for no-hop and one-hop routing.
     #[deku(bits = 1, pad_bits_before = "1")]
@@ -27,19 +63,110 @@ pub struct HoppingControl {
     destination_address_type: AddressType,
 }
 
+impl HoppingControl {
+    pub fn new(hop_counter: bool, destination_address_type: AddressType) -> Self {
+        Self {
+            hop_counter,
+            destination_address_type,
+        }
+    }
+
+    pub fn hop_counter(&self) -> bool {
+        self.hop_counter
+    }
+
+    pub fn destination_address_type(&self) -> AddressType {
+        self.destination_address_type
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(ctx = "command_length: u32", ctx_default = "0")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "command_length: u32, dialect: Dialect, is_request: Option<bool>",
+    ctx_default = "0, Dialect::default(), None"
+)]
 pub struct Frame {
-    control: Control,
+    pub(super) control: Control,
 
     #[deku(cond = "control.has_hopping")]
-    hopping_control: Option<HoppingControl>,
+    pub(super) hopping_control: Option<HoppingControl>,
 
-    origin_access_class: AccessClass,
+    pub(super) origin_access_class: AccessClass,
 
     #[deku(ctx = "control.origin_address_type")]
-    origin_access_adress: Address,
+    pub(super) origin_access_adress: Address,
+
+    /// Security header, present when the frame is secured
+    /// SPEC: 7.4
+    #[deku(ctx = "control.nls_method")]
+    pub(super) nls_state: NlsState,
+
+    /// Encrypted and followed by a MIC when the frame is secured, see [`super::security`]
+    #[deku(ctx = "command_length, dialect, is_request")]
+    pub(super) frame: transport::Frame,
+}
+
+impl Frame {
+    /// A frame without hopping, with the control derived from the origin and security state
+    pub fn new(
+        origin_access_class: AccessClass,
+        origin_address: Address,
+        nls_state: NlsState,
+        frame: transport::Frame,
+    ) -> Self {
+        Self {
+            control: Control::new(
+                false,
+                false,
+                origin_address.deku_id().unwrap(),
+                nls_state.deku_id().unwrap(),
+            ),
+            hopping_control: None,
+            origin_access_class,
+            origin_access_adress: origin_address,
+            nls_state,
+            frame,
+        }
+    }
+
+    pub fn with_hopping(mut self, hopping_control: HoppingControl) -> Self {
+        self.control.has_hopping = true;
+        self.hopping_control = Some(hopping_control);
+        self
+    }
+
+    pub fn control(&self) -> &Control {
+        &self.control
+    }
+
+    pub fn hopping_control(&self) -> Option<&HoppingControl> {
+        self.hopping_control.as_ref()
+    }
+
+    pub fn origin_access_class(&self) -> &AccessClass {
+        &self.origin_access_class
+    }
+
+    pub fn origin_address(&self) -> &Address {
+        &self.origin_access_adress
+    }
+
+    pub fn nls_state(&self) -> &NlsState {
+        &self.nls_state
+    }
+
+    /// The transport layer frame
+    pub fn frame(&self) -> &transport::Frame {
+        &self.frame
+    }
+
+    pub fn into_frame(self) -> transport::Frame {
+        self.frame
+    }
 
-    #[deku(ctx = "command_length")]
-    frame: transport::Frame,
+    /// Decodes the application files in the command, see [`crate::file::FileRegistry`]
+    pub fn resolve_files(&mut self, registry: &FileRegistry) -> Result<(), Error> {
+        self.frame.command.resolve_files(registry)
+    }
 }
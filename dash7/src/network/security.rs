@@ -0,0 +1,481 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit},
+    Aes128,
+};
+use deku::{no_std_io::Cursor, prelude::*};
+
+use crate::{
+    dialect::Dialect,
+    link::AccessClass,
+    transport,
+    utils::{decode, position},
+    Error,
+};
+
+use super::{Address, Control, Frame, HoppingControl, NlsMethod, NlsState};
+
+const BLOCK_SIZE: usize = 16;
+
+/// Size of the payload length in the CCM blocks
+const LENGTH_SIZE: usize = 2;
+
+/// Problems securing or unsecuring a frame
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SecurityError {
+    /// The frame is not secured
+    Unsecured,
+
+    /// The origin of the frame is not addressed by its UID, which is part of the nonce
+    NoUid,
+
+    /// The payload is too long to be secured
+    TooLong { length: usize },
+
+    /// The frame is too short to hold its MIC
+    TooShort { length: usize },
+
+    /// The MIC does not match the frame, it was altered or secured with another key
+    Mic,
+
+    /// The frame could not be encoded
+    Encode(DekuError),
+
+    /// The frame could not be decoded
+    Decode(Error),
+}
+
+impl fmt::Display for SecurityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsecured => write!(f, "the frame is not secured"),
+            Self::NoUid => write!(f, "the origin of the frame has no UID"),
+            Self::TooLong { length } => write!(f, "a payload of {} bytes is too long", length),
+            Self::TooShort { length } => {
+                write!(f, "a frame of {} bytes is too short for its MIC", length)
+            }
+            Self::Mic => write!(f, "the MIC does not match"),
+            Self::Encode(err) => write!(f, "could not encode the frame: {}", err),
+            Self::Decode(err) => write!(f, "could not decode the frame: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SecurityError {}
+
+impl From<DekuError> for SecurityError {
+    fn from(err: DekuError) -> Self {
+        Self::Encode(err)
+    }
+}
+
+impl From<Error> for SecurityError {
+    fn from(err: Error) -> Self {
+        Self::Decode(err)
+    }
+}
+
+impl NlsState {
+    pub fn method(&self) -> NlsMethod {
+        self.deku_id().unwrap()
+    }
+
+    /// Key counter and security frame counter
+    pub fn header(&self) -> Option<&[u8; 5]> {
+        match self {
+            Self::None => None,
+            Self::AesCtr(header)
+            | Self::AesCbcMac128(header)
+            | Self::AesCbcMac64(header)
+            | Self::AesCbcMac32(header)
+            | Self::AesCcm128(header)
+            | Self::AesCcm64(header)
+            | Self::AesCcm32(header) => Some(header),
+        }
+    }
+}
+
+impl NlsMethod {
+    /// Size of the MIC in bytes
+    pub fn mic_size(&self) -> usize {
+        match self {
+            Self::None | Self::AesCtr => 0,
+            Self::AesCbcMac128 | Self::AesCcm128 => 16,
+            Self::AesCbcMac64 | Self::AesCcm64 => 8,
+            Self::AesCbcMac32 | Self::AesCcm32 => 4,
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(
+            self,
+            Self::AesCtr | Self::AesCcm128 | Self::AesCcm64 | Self::AesCcm32
+        )
+    }
+}
+
+/// CCM nonce: security frame counter, key counter and the UID of the origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nonce(pub [u8; 13]);
+
+impl Nonce {
+    /// `None` when the frame is not secured
+    pub fn new(origin: u64, state: &NlsState) -> Option<Self> {
+        let [key_counter, frame_counter @ ..] = state.header()?;
+        let mut nonce = [0; 13];
+        nonce[..4].copy_from_slice(frame_counter);
+        nonce[4] = *key_counter;
+        nonce[5..].copy_from_slice(&origin.to_be_bytes());
+        Some(Self(nonce))
+    }
+}
+
+/// AES-128 key used to secure frames
+///
+/// Encryption is done in counter mode, authentication with CBC-MAC, both as in AES-CCM
+/// (RFC 3610) with 2 length bytes. The network header is authenticated as additional data.
+#[derive(Clone)]
+pub struct Key {
+    cipher: Aes128,
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key").finish_non_exhaustive()
+    }
+}
+
+impl Key {
+    pub fn new(key: u128) -> Self {
+        Self {
+            cipher: Aes128::new(&key.to_be_bytes().into()),
+        }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        self.cipher
+            .encrypt_block(GenericArray::from_mut_slice(block));
+    }
+
+    /// Counter block `A_i`
+    fn counter_block(&self, nonce: &Nonce, counter: u16) -> [u8; BLOCK_SIZE] {
+        let mut block = [0; BLOCK_SIZE];
+        block[0] = (LENGTH_SIZE - 1) as u8;
+        block[1..14].copy_from_slice(&nonce.0);
+        block[14..].copy_from_slice(&counter.to_be_bytes());
+        self.encrypt_block(&mut block);
+        block
+    }
+
+    /// Encrypts or decrypts `data` in counter mode, starting from `A_1`
+    fn ctr(&self, nonce: &Nonce, data: &mut [u8]) {
+        for (chunk, counter) in data.chunks_mut(BLOCK_SIZE).zip(1..) {
+            let stream = self.counter_block(nonce, counter);
+            chunk.iter_mut().zip(stream).for_each(|(b, s)| *b ^= s);
+        }
+    }
+
+    /// CBC-MAC over `aad` and `payload`, encrypted with `A_0`
+    fn cbc_mac(&self, mic_size: usize, nonce: &Nonce, aad: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut x = [0; BLOCK_SIZE];
+        x[0] = (u8::from(!aad.is_empty()) << 6)
+            | ((((mic_size - 2) / 2) as u8) << 3)
+            | (LENGTH_SIZE - 1) as u8;
+        x[1..14].copy_from_slice(&nonce.0);
+        x[14..].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+        self.encrypt_block(&mut x);
+
+        let mut mac = |data: &[u8]| {
+            for chunk in data.chunks(BLOCK_SIZE) {
+                x.iter_mut().zip(chunk).for_each(|(x, b)| *x ^= b);
+                self.encrypt_block(&mut x);
+            }
+        };
+        if !aad.is_empty() {
+            // additional data shorter than 0xFF00 bytes is prefixed with its length
+            let mut prefixed = Vec::with_capacity(aad.len() + 2);
+            prefixed.extend_from_slice(&(aad.len() as u16).to_be_bytes());
+            prefixed.extend_from_slice(aad);
+            mac(&prefixed);
+        }
+        mac(payload);
+
+        let stream = self.counter_block(nonce, 0);
+        x.iter()
+            .zip(stream)
+            .take(mic_size)
+            .map(|(x, s)| x ^ s)
+            .collect()
+    }
+
+    /// Computes the MIC of `payload`. Empty for methods without authentication.
+    pub fn mic(&self, method: NlsMethod, nonce: &Nonce, aad: &[u8], payload: &[u8]) -> Vec<u8> {
+        match method.mic_size() {
+            0 => Vec::new(),
+            size => self.cbc_mac(size, nonce, aad, payload),
+        }
+    }
+
+    /// Secures `payload` in place: encrypts it and appends its MIC, as required by `method`
+    pub fn encrypt(
+        &self,
+        method: NlsMethod,
+        nonce: &Nonce,
+        aad: &[u8],
+        payload: &mut Vec<u8>,
+    ) -> Result<(), SecurityError> {
+        if payload.len() > u16::MAX as usize || aad.len() >= 0xFF00 {
+            return Err(SecurityError::TooLong {
+                length: payload.len().max(aad.len()),
+            });
+        }
+        let mic = self.mic(method, nonce, aad, payload);
+        if method.is_encrypted() {
+            self.ctr(nonce, payload);
+        }
+        payload.extend(mic);
+        Ok(())
+    }
+
+    /// Reverts [`Self::encrypt`] in place: verifies and removes the MIC, and decrypts
+    pub fn decrypt(
+        &self,
+        method: NlsMethod,
+        nonce: &Nonce,
+        aad: &[u8],
+        data: &mut Vec<u8>,
+    ) -> Result<(), SecurityError> {
+        let length = data.len();
+        let Some(payload_length) = length.checked_sub(method.mic_size()) else {
+            return Err(SecurityError::TooShort { length });
+        };
+        let mic = data.split_off(payload_length);
+        if method.is_encrypted() {
+            self.ctr(nonce, data);
+        }
+        if self.mic(method, nonce, aad, data) != mic {
+            return Err(SecurityError::Mic);
+        }
+        Ok(())
+    }
+
+    /// Encodes `frame`, with its transport payload secured according to its [`NlsState`]
+    pub fn encrypt_frame(&self, frame: &Frame, dialect: Dialect) -> Result<Vec<u8>, SecurityError> {
+        let nonce = nonce(frame)?;
+        let mut data = encode(frame, (0, dialect, None))?;
+        let payload_length = encode(&frame.frame, (0, dialect, None))?.len();
+        let mut payload = data.split_off(data.len() - payload_length);
+        self.encrypt(frame.nls_state.method(), &nonce, &data, &mut payload)?;
+        data.extend(payload);
+        Ok(data)
+    }
+
+    /// Decodes a network frame of which the transport payload is secured
+    pub fn decrypt_frame(&self, data: &[u8], dialect: Dialect) -> Result<Frame, SecurityError> {
+        let mut cursor = Cursor::new(data);
+        let mut reader = Reader::new(&mut cursor);
+        let mut read = || -> Result<Frame, DekuError> {
+            let control = Control::from_reader_with_ctx(&mut reader, ())?;
+            let hopping_control = match control.has_hopping {
+                true => Some(HoppingControl::from_reader_with_ctx(&mut reader, ())?),
+                false => None,
+            };
+            let origin_access_class = AccessClass::from_reader_with_ctx(&mut reader, ())?;
+            let origin_access_adress =
+                Address::from_reader_with_ctx(&mut reader, control.origin_address_type)?;
+            let nls_state = NlsState::from_reader_with_ctx(&mut reader, control.nls_method)?;
+            Ok(Frame {
+                control,
+                hopping_control,
+                origin_access_class,
+                origin_access_adress,
+                nls_state,
+                frame: Default::default(),
+            })
+        };
+        let mut frame = read().map_err(|err| Error::from_deku(err, reader.bits_read))?;
+        let header_length = position(&mut reader).map_err(|err| Error::from_deku(err, 0))? / 8;
+
+        let nonce = nonce(&frame)?;
+        let (header, payload) = data.split_at(header_length);
+        let mut payload = payload.to_vec();
+        self.decrypt(frame.nls_state.method(), &nonce, header, &mut payload)?;
+
+        let (_, transport) = decode::<transport::Frame, _>((&payload, 0), (0, dialect, None))
+            .map_err(|err| err.nested(header_length * 8, &[]))?;
+        frame.frame = transport;
+        Ok(frame)
+    }
+}
+
+fn nonce(frame: &Frame) -> Result<Nonce, SecurityError> {
+    let Address::UId(uid) = frame.origin_access_adress else {
+        return Err(SecurityError::NoUid);
+    };
+    Nonce::new(uid, &frame.nls_state).ok_or(SecurityError::Unsecured)
+}
+
+fn encode<T, Ctx>(value: &T, ctx: Ctx) -> Result<Vec<u8>, DekuError>
+where
+    T: DekuWriter<Ctx>,
+{
+    let mut data = Vec::new();
+    let mut writer = Writer::new(Cursor::new(&mut data));
+    value.to_writer(&mut writer, ctx)?;
+    writer.finalize()?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::app::command::Command;
+
+    #[test]
+    fn test_ccm() {
+        // RFC 3610, packet vector #1
+        let key = Key::new(0xC0C1C2C3C4C5C6C7C8C9CACBCCCDCECF);
+        let nonce = Nonce(hex!("00000003 02 01 00A0A1A2A3A4A5"));
+        let aad = hex!("0001020304050607");
+        let mut data = hex!("08090A0B0C0D0E0F101112131415161718191A1B1C1D1E").to_vec();
+
+        key.encrypt(NlsMethod::AesCcm64, &nonce, &aad, &mut data)
+            .unwrap();
+        assert_eq!(
+            data,
+            hex!("588C979A61C663D2F066D0C2C0F989806D5F6B61DAC38417E8D12CFDF926E0")
+        );
+
+        let mut decrypted = data.clone();
+        key.decrypt(NlsMethod::AesCcm64, &nonce, &aad, &mut decrypted)
+            .unwrap();
+        assert_eq!(
+            decrypted,
+            hex!("08090A0B0C0D0E0F101112131415161718191A1B1C1D1E")
+        );
+
+        data[0] ^= 1;
+        assert!(matches!(
+            key.decrypt(NlsMethod::AesCcm64, &nonce, &aad, &mut data),
+            Err(SecurityError::Mic)
+        ));
+        assert!(matches!(
+            key.decrypt(NlsMethod::AesCcm128, &nonce, &aad, &mut vec![0; 8]),
+            Err(SecurityError::TooShort { length: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_methods() {
+        let key = Key::new(0x000102030405060708090A0B0C0D0E0F);
+        let nonce = Nonce([0; 13]);
+        let payload = hex!("0102030405060708090A0B0C0D0E0F1011").to_vec();
+
+        for (method, encrypted, mic_size) in [
+            (NlsMethod::None, false, 0),
+            (NlsMethod::AesCtr, true, 0),
+            (NlsMethod::AesCbcMac32, false, 4),
+            (NlsMethod::AesCbcMac128, false, 16),
+            (NlsMethod::AesCcm32, true, 4),
+        ] {
+            let mut data = payload.clone();
+            key.encrypt(method, &nonce, &[], &mut data).unwrap();
+            assert_eq!(data.len(), payload.len() + mic_size);
+            assert_eq!(data[..payload.len()] != payload[..], encrypted);
+
+            key.decrypt(method, &nonce, &[], &mut data).unwrap();
+            assert_eq!(data, payload);
+        }
+    }
+
+    #[test]
+    fn test_secured_frame() {
+        // AES-CCM-32 frame, secured with OpenSSL's AES-CCM: the clear header, the encrypted
+        // transport frame (dialog start 0x11, transaction 0x01, read 8 bytes of file 0x00) and
+        // the MIC
+        let data = hex!("27 12 0011223344556677 0100000002  1E1F17A430F38115  15015E");
+        let key = Key::new(0x000102030405060708090A0B0C0D0E0F);
+
+        let frame = key.decrypt_frame(&data, Dialect::default()).unwrap();
+        assert_eq!(frame.origin_address(), &Address::UId(0x0011223344556677));
+        assert_eq!(frame.nls_state(), &NlsState::AesCcm32(hex!("01 00000002")));
+        assert!(frame.frame().control.is_dialog_start);
+        assert_eq!(frame.frame().dialog_id, 0x11);
+        assert_eq!(frame.frame().transaction_id, 0x01);
+        assert_eq!(
+            frame.frame().command,
+            Command::builder().read_file(0x00, 0, 8).build().unwrap()
+        );
+        assert_eq!(key.encrypt_frame(&frame, Dialect::default()).unwrap(), data);
+
+        let mut altered = data;
+        altered[20] ^= 0x01;
+        assert!(matches!(
+            key.decrypt_frame(&altered, Dialect::default()),
+            Err(SecurityError::Mic)
+        ));
+    }
+
+    fn frame(origin_address: Address, nls_state: NlsState) -> Frame {
+        let transport = transport::Frame {
+            command: Command::builder().read_file(0x00, 0, 8).build().unwrap(),
+            ..Default::default()
+        };
+        Frame::new(
+            AccessClass::new(0x01, 0x02),
+            origin_address,
+            nls_state,
+            transport,
+        )
+    }
+
+    #[test]
+    fn test_frame() {
+        let key = Key::new(0x000102030405060708090A0B0C0D0E0F);
+        let state = NlsState::AesCcm32(hex!("01 00000002"));
+        let frame = frame(Address::UId(0x0011223344556677), state.clone());
+        assert_eq!(
+            Nonce::new(0x0011223344556677, &state),
+            Some(Nonce(hex!("00000002 01 0011223344556677")))
+        );
+
+        let data = key.encrypt_frame(&frame, Dialect::default()).unwrap();
+        let plain = encode(&frame, (0, Dialect::default(), None)).unwrap();
+        // the header is sent in the clear
+        assert_eq!(data[..15], hex!("27 12 0011223344556677 0100000002"));
+        assert_eq!(data[..15], plain[..15]);
+        assert_ne!(data[15..plain.len()], plain[15..]);
+        assert_eq!(data.len(), plain.len() + 4);
+
+        assert_eq!(key.decrypt_frame(&data, Dialect::default()).unwrap(), frame);
+
+        // the header is authenticated
+        let mut altered = data.clone();
+        altered[1] = 0x13;
+        assert!(matches!(
+            key.decrypt_frame(&altered, Dialect::default()),
+            Err(SecurityError::Mic)
+        ));
+        assert!(matches!(
+            Key::new(0).decrypt_frame(&data, Dialect::default()),
+            Err(SecurityError::Mic)
+        ));
+
+        let frame = self::frame(Address::VId(0x1234), state);
+        assert!(matches!(
+            key.encrypt_frame(&frame, Dialect::default()),
+            Err(SecurityError::NoUid)
+        ));
+        let frame = self::frame(Address::UId(0), NlsState::None);
+        assert!(matches!(
+            key.encrypt_frame(&frame, Dialect::default()),
+            Err(SecurityError::Unsecured)
+        ));
+    }
+}
@@ -1,6 +1,52 @@
+use alloc::vec::Vec;
+use core::fmt;
+
 use deku::prelude::*;
 
+/// Spacing between consecutive channel indexes, in Hz
+pub const CHANNEL_SPACING: u32 = 25_000;
+
+/// Problems with channels that are not valid for their band and class
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChannelError {
+    /// The band is reserved for future use
+    RfuBand,
+
+    /// The channel class is not defined by the spec
+    UnsupportedClass,
+
+    /// The index is out of the band, or not a channel of the class
+    InvalidIndex { index: u16 },
+
+    /// The sub-band ends before it starts
+    InvalidRange { start: u16, end: u16 },
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RfuBand => write!(f, "the channel band is reserved"),
+            Self::UnsupportedClass => write!(f, "the channel class is not supported"),
+            Self::InvalidIndex { index } => {
+                write!(
+                    f,
+                    "channel index {} is not valid for the band and class",
+                    index
+                )
+            }
+            Self::InvalidRange { start, end } => {
+                write!(f, "sub-band from {} to {} is empty", start, end)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChannelError {}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, endian = "big", id_type = "u8")]
 pub enum Bandwidth {
     #[default]
@@ -10,6 +56,7 @@ pub enum Bandwidth {
 
 /// D7A channel bands indexes
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, endian = "big", id_type = "u8")]
 pub enum ChannelBand {
     #[default]
@@ -20,7 +67,30 @@ pub enum ChannelBand {
     Band915 = 0x04,
 }
 
+impl ChannelBand {
+    /// Frequency of channel index 0, in Hz
+    pub fn start_frequency(&self) -> Option<u32> {
+        match self {
+            Self::Rfu0 | Self::Rfu1 => None,
+            Self::Band433 => Some(433_056_000),
+            Self::Band868 => Some(863_000_000),
+            Self::Band915 => Some(902_000_000),
+        }
+    }
+
+    /// Number of channel indexes in the band, `0` for reserved bands
+    pub fn channel_count(&self) -> u16 {
+        match self {
+            Self::Rfu0 | Self::Rfu1 => 0,
+            Self::Band433 => 69,
+            Self::Band868 => 280,
+            Self::Band915 => 1040,
+        }
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, endian = "big", id_type = "u8")]
 pub enum ChannelClass {
     #[default]
@@ -30,7 +100,47 @@ pub enum ChannelClass {
     HiRate = 3,
 }
 
+impl ChannelClass {
+    /// Symbol rate, in symbols per second
+    pub fn symbol_rate(&self) -> Option<u32> {
+        match self {
+            Self::LoRate => Some(9_600),
+            Self::NormalRate => Some(55_555),
+            Self::HiRate => Some(166_667),
+            Self::Lora => None,
+        }
+    }
+
+    /// Frequency deviation, in Hz
+    pub fn deviation(&self) -> Option<u32> {
+        match self {
+            Self::LoRate => Some(4_800),
+            Self::NormalRate => Some(50_000),
+            Self::HiRate => Some(41_667),
+            Self::Lora => None,
+        }
+    }
+
+    /// Channel bandwidth, in Hz
+    pub fn bandwidth(&self) -> Option<u32> {
+        match self {
+            Self::LoRate => Some(25_000),
+            Self::NormalRate | Self::HiRate => Some(200_000),
+            Self::Lora => None,
+        }
+    }
+
+    /// Channels of wide classes only start on every 8th index, so they do not overlap
+    fn index_step(&self) -> u16 {
+        match self {
+            Self::NormalRate | Self::HiRate => 8,
+            Self::LoRate | Self::Lora => 1,
+        }
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, endian = "big", id_type = "u8")]
 pub enum ChannelCoding {
     #[default]
@@ -41,6 +151,7 @@ pub enum ChannelCoding {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 4, id_type = "u8")]
 pub enum CsmaCaMode {
     #[default]
@@ -55,6 +166,7 @@ pub enum CsmaCaMode {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 4, id_type = "u8")]
 pub enum NoiseFloor {
     /// Noise floor (in dBm). Use the default channel CCA threshold (5.4).
@@ -77,6 +189,7 @@ pub enum NoiseFloor {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelHeader {
     #[deku(pad_bits_before = "1")]
     pub channel_band: ChannelBand,
@@ -96,16 +209,67 @@ impl ChannelHeader {
             channel_coding,
         }
     }
+
+    /// Checks that `index` is a channel of this band and class
+    pub fn validate(&self, index: u16) -> Result<(), ChannelError> {
+        if self.channel_band.start_frequency().is_none() {
+            return Err(ChannelError::RfuBand);
+        }
+        if self.channel_class.bandwidth().is_none() {
+            return Err(ChannelError::UnsupportedClass);
+        }
+        if index >= self.channel_band.channel_count()
+            || !index.is_multiple_of(self.channel_class.index_step())
+        {
+            return Err(ChannelError::InvalidIndex { index });
+        }
+        Ok(())
+    }
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel {
     pub header: ChannelHeader,
     #[deku(endian = "big")]
     pub index: u16,
 }
 
+impl Channel {
+    pub fn new(header: ChannelHeader, index: u16) -> Self {
+        Self { header, index }
+    }
+
+    pub fn validate(&self) -> Result<(), ChannelError> {
+        self.header.validate(self.index)
+    }
+
+    /// Center frequency, in Hz
+    pub fn frequency(&self) -> Result<u32, ChannelError> {
+        self.validate()?;
+        let start = self.header.channel_band.start_frequency();
+        let bandwidth = self.header.channel_class.bandwidth();
+        // The index gives the lower edge of the channel
+        Ok(start.unwrap_or_default()
+            + self.index as u32 * CHANNEL_SPACING
+            + bandwidth.unwrap_or_default() / 2)
+    }
+
+    /// Symbol rate, in symbols per second
+    pub fn symbol_rate(&self) -> Result<u32, ChannelError> {
+        self.validate()?;
+        Ok(self.header.channel_class.symbol_rate().unwrap_or_default())
+    }
+
+    /// Frequency deviation, in Hz
+    pub fn deviation(&self) -> Result<u32, ChannelError> {
+        self.validate()?;
+        Ok(self.header.channel_class.deviation().unwrap_or_default())
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelStatusIdentifier {
     // TODO update to D7AP v1.1
     pub channel_band: ChannelBand,
@@ -116,6 +280,7 @@ pub struct ChannelStatusIdentifier {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "big")]
 pub struct SubBand {
     pub channel_index_start: u16,
@@ -125,8 +290,137 @@ pub struct SubBand {
     pub duty: u8,
 }
 
+impl SubBand {
+    /// Checks that the sub-band only holds channels of the band and class in `header`, and starts
+    /// at one of them
+    pub fn validate(&self, header: &ChannelHeader) -> Result<(), ChannelError> {
+        if self.channel_index_start > self.channel_index_end {
+            return Err(ChannelError::InvalidRange {
+                start: self.channel_index_start,
+                end: self.channel_index_end,
+            });
+        }
+        header.validate(self.channel_index_start)?;
+        if self.channel_index_end >= header.channel_band.channel_count() {
+            return Err(ChannelError::InvalidIndex {
+                index: self.channel_index_end,
+            });
+        }
+        Ok(())
+    }
+
+    /// Valid channels of the band and class in `header`, from the start to the end of the sub-band
+    pub fn channels<'a>(&self, header: &'a ChannelHeader) -> impl Iterator<Item = Channel> + 'a {
+        (self.channel_index_start..=self.channel_index_end)
+            .filter(|index| header.validate(*index).is_ok())
+            .map(|index| Channel::new(header.clone(), index))
+    }
+}
+
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelStatus {
     pub identifier: ChannelStatusIdentifier,
     pub noise_floor: u8,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(channel_band: ChannelBand, channel_class: ChannelClass) -> ChannelHeader {
+        ChannelHeader::new(channel_band, channel_class, ChannelCoding::Pn9)
+    }
+
+    #[test]
+    fn test_channel() {
+        let channel = Channel::new(header(ChannelBand::Band868, ChannelClass::LoRate), 270);
+        assert_eq!(channel.frequency(), Ok(869_762_500));
+        assert_eq!(channel.symbol_rate(), Ok(9_600));
+        assert_eq!(channel.deviation(), Ok(4_800));
+
+        let channel = Channel::new(header(ChannelBand::Band433, ChannelClass::NormalRate), 16);
+        assert_eq!(channel.frequency(), Ok(433_556_000));
+        assert_eq!(channel.symbol_rate(), Ok(55_555));
+        assert_eq!(channel.deviation(), Ok(50_000));
+
+        let channel = Channel::new(header(ChannelBand::Band915, ChannelClass::HiRate), 1032);
+        assert_eq!(channel.frequency(), Ok(927_900_000));
+
+        // wide channels start on every 8th index
+        let channel = Channel::new(header(ChannelBand::Band868, ChannelClass::HiRate), 9);
+        assert_eq!(
+            channel.frequency(),
+            Err(ChannelError::InvalidIndex { index: 9 })
+        );
+        let channel = Channel::new(header(ChannelBand::Band433, ChannelClass::LoRate), 69);
+        assert_eq!(
+            channel.validate(),
+            Err(ChannelError::InvalidIndex { index: 69 })
+        );
+        let channel = Channel::new(header(ChannelBand::Rfu1, ChannelClass::LoRate), 0);
+        assert_eq!(channel.validate(), Err(ChannelError::RfuBand));
+        let channel = Channel::new(header(ChannelBand::Band868, ChannelClass::Lora), 0);
+        assert_eq!(channel.validate(), Err(ChannelError::UnsupportedClass));
+    }
+
+    #[test]
+    fn test_sub_band() {
+        let header = header(ChannelBand::Band868, ChannelClass::NormalRate);
+        let sub_band = SubBand {
+            channel_index_start: 0,
+            channel_index_end: 20,
+            eirp: 14,
+            clear_channel_assessment: 0,
+            duty: 0,
+        };
+        assert_eq!(sub_band.validate(&header), Ok(()));
+        let frequencies: Vec<_> = sub_band
+            .channels(&header)
+            .map(|channel| channel.frequency().unwrap())
+            .collect();
+        assert_eq!(frequencies, vec![863_100_000, 863_300_000, 863_500_000]);
+
+        let sub_band = SubBand {
+            channel_index_start: 272,
+            channel_index_end: 290,
+            ..sub_band
+        };
+        assert_eq!(
+            sub_band.validate(&header),
+            Err(ChannelError::InvalidIndex { index: 290 })
+        );
+        assert_eq!(sub_band.channels(&header).count(), 1);
+
+        // the start is not a channel of the class
+        let sub_band = SubBand {
+            channel_index_start: 4,
+            channel_index_end: 20,
+            ..sub_band
+        };
+        assert_eq!(
+            sub_band.validate(&header),
+            Err(ChannelError::InvalidIndex { index: 4 })
+        );
+        let sub_band = SubBand {
+            channel_index_start: 1,
+            channel_index_end: 7,
+            ..sub_band
+        };
+        assert_eq!(
+            sub_band.validate(&header),
+            Err(ChannelError::InvalidIndex { index: 1 })
+        );
+        assert_eq!(sub_band.channels(&header).count(), 0);
+
+        let sub_band = SubBand {
+            channel_index_start: 8,
+            channel_index_end: 0,
+            ..sub_band
+        };
+        assert_eq!(
+            sub_band.validate(&header),
+            Err(ChannelError::InvalidRange { start: 8, end: 0 })
+        );
+    }
+}
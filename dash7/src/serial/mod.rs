@@ -0,0 +1,707 @@
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use crc16::{State, CCITT_FALSE};
+use deku::{
+    ctx::Limit,
+    no_std_io::{self, Cursor},
+    prelude::*,
+};
+
+use crate::{
+    app::command::Command,
+    dialect::Dialect,
+    error::{Error, Layer, Location},
+    utils::{decode, from_reader},
+};
+
+// ===============================================================================
+// Message types
+// ===============================================================================
+
+/// Type of the message carried by a serial frame.
+///
+/// Sub-IoT calls this the message type, Wizzilab calls it the flow id. The values differ between
+/// both, use the dialect aware constructors to get the right one.
+#[derive(DekuRead, DekuWrite, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageType(pub u8);
+
+/// Sub-IoT message types
+impl MessageType {
+    pub const ALP_DATA: MessageType = MessageType(0x01);
+    pub const PING_REQUEST: MessageType = MessageType(0x02);
+    pub const PING_RESPONSE: MessageType = MessageType(0x03);
+    pub const LOGGING: MessageType = MessageType(0x04);
+    pub const REBOOTED: MessageType = MessageType(0x05);
+}
+
+/// Wizzilab flow ids
+impl MessageType {
+    pub const PRINTF: MessageType = MessageType(0x10);
+    pub const ALP_COMMAND: MessageType = MessageType(0x20);
+    pub const ALP_RESPONSE: MessageType = MessageType(0x21);
+    pub const ALP_UNSOLICITED: MessageType = MessageType(0x22);
+    pub const ALP_ERROR: MessageType = MessageType(0x23);
+    pub const SYS_RESET: MessageType = MessageType(0x30);
+    pub const SYS_PING: MessageType = MessageType(0x34);
+    pub const SYS_PONG: MessageType = MessageType(0x35);
+}
+
+/// The spec does not define a serial interface. Anything but Wizzilab uses the Sub-IoT framing.
+impl MessageType {
+    pub fn alp(dialect: Dialect) -> Self {
+        if dialect.is_wizzilab() {
+            Self::ALP_COMMAND
+        } else {
+            Self::ALP_DATA
+        }
+    }
+
+    pub fn ping_request(dialect: Dialect) -> Self {
+        if dialect.is_wizzilab() {
+            Self::SYS_PING
+        } else {
+            Self::PING_REQUEST
+        }
+    }
+
+    pub fn ping_response(dialect: Dialect) -> Self {
+        if dialect.is_wizzilab() {
+            Self::SYS_PONG
+        } else {
+            Self::PING_RESPONSE
+        }
+    }
+
+    pub fn logging(dialect: Dialect) -> Self {
+        if dialect.is_wizzilab() {
+            Self::PRINTF
+        } else {
+            Self::LOGGING
+        }
+    }
+
+    pub fn is_alp(&self, dialect: Dialect) -> bool {
+        if dialect.is_wizzilab() {
+            (Self::ALP_COMMAND..=Self::ALP_ERROR).contains(self)
+        } else {
+            *self == Self::ALP_DATA
+        }
+    }
+}
+
+// ===============================================================================
+// Messages
+// ===============================================================================
+
+/// Payload of a serial frame, interpreted according to its message type
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SerialMessage {
+    /// An ALP command, sent to or received from the modem
+    Alp(Command),
+
+    /// Ping request. The payload is echoed back in the ping response.
+    PingRequest(Vec<u8>),
+
+    /// Response to a ping request
+    PingResponse(Vec<u8>),
+
+    /// Log output of the modem
+    Logging(String),
+
+    /// Any message type we do not know how to interpret
+    Other(Vec<u8>),
+}
+
+impl Default for SerialMessage {
+    fn default() -> Self {
+        Self::Other(vec![])
+    }
+}
+
+impl SerialMessage {
+    /// Default message type used to transmit this message
+    pub fn message_type(&self, dialect: Dialect) -> Option<MessageType> {
+        match self {
+            Self::Alp(_) => Some(MessageType::alp(dialect)),
+            Self::PingRequest(_) => Some(MessageType::ping_request(dialect)),
+            Self::PingResponse(_) => Some(MessageType::ping_response(dialect)),
+            Self::Logging(_) => Some(MessageType::logging(dialect)),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl<'a> DekuReader<'a, (MessageType, u32, Dialect)> for SerialMessage {
+    fn from_reader_with_ctx<R>(
+        reader: &mut Reader<R>,
+        (message_type, length, dialect): (MessageType, u32, Dialect),
+    ) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        if message_type.is_alp(dialect) {
+            return Command::from_reader_with_ctx(reader, (length, dialect)).map(Self::Alp);
+        }
+
+        let data = Vec::<u8>::from_reader_with_ctx(reader, Limit::new_count(length as usize))?;
+
+        Ok(if message_type == MessageType::ping_request(dialect) {
+            Self::PingRequest(data)
+        } else if message_type == MessageType::ping_response(dialect) {
+            Self::PingResponse(data)
+        } else if message_type == MessageType::logging(dialect) {
+            Self::Logging(String::from_utf8_lossy(&data).into_owned())
+        } else {
+            Self::Other(data)
+        })
+    }
+}
+
+impl DekuWriter<Dialect> for SerialMessage {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, dialect: Dialect) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        match self {
+            Self::Alp(command) => command.to_writer(writer, (0, dialect)),
+            Self::PingRequest(data) | Self::PingResponse(data) | Self::Other(data) => {
+                data.to_writer(writer, ())
+            }
+            Self::Logging(message) => message.as_bytes().to_writer(writer, ()),
+        }
+    }
+}
+
+// ===============================================================================
+// Frames
+// ===============================================================================
+
+/// Frame used by modems to exchange ALP commands and auxiliary messages over a serial line
+///
+/// Sub-IoT layout:
+/// `SYNC (0xC0) | VERSION (0x00) | COUNTER | MESSAGE TYPE | LENGTH | CRC16 (2 bytes) | PAYLOAD`
+///
+/// Wizzilab layout:
+/// `SYNC (0x01 0x1F) | LENGTH | COUNTER | FLOW ID | PAYLOAD`
+///
+/// The CRC is the CRC-16/CCITT-FALSE of the payload.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SerialFrame {
+    /// Rolling frame counter, used to detect missed frames
+    pub counter: u8,
+
+    /// Type of the message. Determines how the payload is interpreted.
+    pub message_type: MessageType,
+
+    pub message: SerialMessage,
+}
+
+impl SerialFrame {
+    pub const SUBIOT_SYNC: [u8; 2] = [0xC0, 0x00];
+    pub const WIZZILAB_SYNC: [u8; 2] = [0x01, 0x1F];
+
+    pub const SUBIOT_HEADER_SIZE: usize = 7;
+    pub const WIZZILAB_HEADER_SIZE: usize = 5;
+
+    /// Maximum size of the payload of a single frame
+    pub const MAX_PAYLOAD_SIZE: usize = u8::MAX as usize;
+
+    pub fn new(counter: u8, message_type: MessageType, message: SerialMessage) -> Self {
+        Self {
+            counter,
+            message_type,
+            message,
+        }
+    }
+
+    /// Wraps an ALP command in a frame
+    pub fn alp(counter: u8, command: Command, dialect: Dialect) -> Self {
+        Self::new(
+            counter,
+            MessageType::alp(dialect),
+            SerialMessage::Alp(command),
+        )
+    }
+
+    pub fn sync(dialect: Dialect) -> [u8; 2] {
+        if dialect.is_wizzilab() {
+            Self::WIZZILAB_SYNC
+        } else {
+            Self::SUBIOT_SYNC
+        }
+    }
+
+    pub fn header_size(dialect: Dialect) -> usize {
+        if dialect.is_wizzilab() {
+            Self::WIZZILAB_HEADER_SIZE
+        } else {
+            Self::SUBIOT_HEADER_SIZE
+        }
+    }
+
+    pub fn command(&self) -> Option<&Command> {
+        match &self.message {
+            SerialMessage::Alp(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    /// Total size of the frame starting at `data`, as announced by its header.
+    ///
+    /// Returns `None` when `data` does not hold a complete header yet.
+    pub fn frame_size(data: &[u8], dialect: Dialect) -> Option<usize> {
+        let length_index = if dialect.is_wizzilab() { 2 } else { 4 };
+        let header_size = Self::header_size(dialect);
+
+        if data.len() < header_size {
+            return None;
+        }
+        Some(header_size + data[length_index] as usize)
+    }
+
+    pub fn crc(payload: &[u8]) -> u16 {
+        State::<CCITT_FALSE>::calculate(payload)
+    }
+
+    pub fn from_reader<R>(input: (&mut R, usize)) -> Result<(usize, Self), DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        from_reader(input, ())
+    }
+
+    pub fn from_bytes(input: (&'_ [u8], usize)) -> Result<((&'_ [u8], usize), Self), Error> {
+        Self::from_bytes_with_dialect(input, Dialect::default())
+    }
+
+    pub fn from_bytes_with_dialect(
+        input: (&'_ [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'_ [u8], usize), Self), Error> {
+        decode(input, dialect).map_err(|err| match input.1 {
+            0 => Self::locate_error(err, input.0, dialect),
+            _ => err,
+        })
+    }
+
+    /// Looks up which part of the frame at the start of `data` made decoding fail
+    fn locate_error(err: Error, data: &[u8], dialect: Dialect) -> Error {
+        let sync = Self::sync(dialect);
+        if data.len() >= sync.len() && data[..sync.len()] != sync {
+            return Error::InvalidValue {
+                location: Location::new(0),
+                field: "sync",
+                expected: format!("{:#06X}", u16::from_be_bytes(sync)).into(),
+                found: u16::from_be_bytes([data[0], data[1]]) as u64,
+            };
+        }
+
+        let Some(size) = Self::frame_size(data, dialect).filter(|size| *size <= data.len()) else {
+            return err;
+        };
+        let header_size = Self::header_size(dialect);
+        let payload = &data[header_size..size];
+
+        let (counter, message_type) = if dialect.is_wizzilab() {
+            (data[3], MessageType(data[4]))
+        } else {
+            let crc = u16::from_be_bytes([data[5], data[6]]);
+            let expected = Self::crc(payload);
+            if crc != expected {
+                return Error::InvalidValue {
+                    location: Location::new(5 * 8),
+                    field: "crc",
+                    expected: format!("{:#06X}", expected).into(),
+                    found: crc as u64,
+                };
+            }
+            (data[2], MessageType(data[3]))
+        };
+
+        if !message_type.is_alp(dialect) {
+            return err;
+        }
+
+        let command_err = match Command::from_bytes_with_dialect((payload, 0), dialect) {
+            Err(command_err) => command_err,
+            Ok(((rest, _), _)) if !rest.is_empty() => Error::TooMuchData {
+                location: Location::new((payload.len() - rest.len()) * 8),
+                remaining: rest.len(),
+            },
+            Ok(_) => return err,
+        };
+        command_err.nested(header_size * 8, &[Layer::SerialFrame { counter }])
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DekuError> {
+        self.to_bytes_with_dialect(Dialect::default())
+    }
+
+    pub fn to_bytes_with_dialect(&self, dialect: Dialect) -> Result<Vec<u8>, DekuError> {
+        let mut out_buf = Vec::new();
+        let mut cursor = Cursor::new(&mut out_buf);
+        let mut writer = Writer::new(&mut cursor);
+        DekuWriter::to_writer(self, &mut writer, dialect)?;
+        writer.finalize()?;
+        Ok(out_buf)
+    }
+}
+
+impl<'a> DekuReader<'a, ()> for SerialFrame {
+    fn from_reader_with_ctx<R>(reader: &mut Reader<R>, _: ()) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        Self::from_reader_with_ctx(reader, Dialect::default())
+    }
+}
+
+impl<'a> DekuReader<'a, Dialect> for SerialFrame {
+    fn from_reader_with_ctx<R>(reader: &mut Reader<R>, dialect: Dialect) -> Result<Self, DekuError>
+    where
+        R: no_std_io::Read + no_std_io::Seek,
+    {
+        let sync = <[u8; 2]>::from_reader_with_ctx(reader, ())?;
+        if sync != Self::sync(dialect) {
+            return Err(DekuError::Parse(Cow::Owned(format!(
+                "Serial frame: invalid sync bytes {:02X?}",
+                sync
+            ))));
+        }
+
+        let (counter, message_type, length, crc) = if dialect.is_wizzilab() {
+            let length = u8::from_reader_with_ctx(reader, ())?;
+            let counter = u8::from_reader_with_ctx(reader, ())?;
+            let message_type = MessageType::from_reader_with_ctx(reader, ())?;
+            (counter, message_type, length, None)
+        } else {
+            (
+                u8::from_reader_with_ctx(reader, ())?,
+                MessageType::from_reader_with_ctx(reader, ())?,
+                u8::from_reader_with_ctx(reader, ())?,
+                Some(u16::from_reader_with_ctx(reader, deku::ctx::Endian::Big)?),
+            )
+        };
+
+        let payload = Vec::<u8>::from_reader_with_ctx(reader, Limit::new_count(length as usize))?;
+
+        if let Some(crc) = crc {
+            let expected = Self::crc(&payload);
+            if crc != expected {
+                return Err(DekuError::Parse(Cow::Owned(format!(
+                    "Serial frame: CRC mismatch, got {:#06X}, expected {:#06X}",
+                    crc, expected
+                ))));
+            }
+        }
+
+        let mut cursor = Cursor::new(payload.as_slice());
+        let mut payload_reader = Reader::new(&mut cursor);
+        let message = SerialMessage::from_reader_with_ctx(
+            &mut payload_reader,
+            (message_type, length as u32, dialect),
+        )?;
+
+        if !payload_reader.end() {
+            return Err(DekuError::Parse(Cow::Borrowed(
+                "Serial frame: payload not fully consumed",
+            )));
+        }
+
+        Ok(Self {
+            counter,
+            message_type,
+            message,
+        })
+    }
+}
+
+impl DekuWriter<()> for SerialFrame {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, _: ()) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        self.to_writer(writer, Dialect::default())
+    }
+}
+
+impl DekuWriter<Dialect> for SerialFrame {
+    fn to_writer<W>(&self, writer: &mut Writer<W>, dialect: Dialect) -> Result<(), DekuError>
+    where
+        W: no_std_io::Write + no_std_io::Seek,
+    {
+        // first write the whole message into a byte buffer
+        let mut out_buf = Vec::new();
+        let mut out_buf_cur = Cursor::new(&mut out_buf);
+        let mut tmp_writer = Writer::new(&mut out_buf_cur);
+        self.message.to_writer(&mut tmp_writer, dialect)?;
+        tmp_writer.finalize()?;
+        let payload = out_buf_cur.into_inner();
+
+        let length: u8 = payload.len().try_into().map_err(|_| {
+            DekuError::InvalidParam(Cow::Owned(format!(
+                "Serial frame: payload too large: {}. Max: {}",
+                payload.len(),
+                Self::MAX_PAYLOAD_SIZE
+            )))
+        })?;
+
+        Self::sync(dialect).to_writer(writer, ())?;
+
+        if dialect.is_wizzilab() {
+            length.to_writer(writer, ())?;
+            self.counter.to_writer(writer, ())?;
+            self.message_type.to_writer(writer, ())?;
+        } else {
+            self.counter.to_writer(writer, ())?;
+            self.message_type.to_writer(writer, ())?;
+            length.to_writer(writer, ())?;
+            Self::crc(payload).to_writer(writer, deku::ctx::Endian::Big)?;
+        }
+
+        payload.to_writer(writer, ())
+    }
+}
+
+impl TryFrom<&'_ [u8]> for SerialFrame {
+    type Error = Error;
+    fn try_from(input: &'_ [u8]) -> Result<Self, Self::Error> {
+        let (rest, res) = Self::from_bytes((input, 0))?;
+        if !rest.0.is_empty() {
+            return Err(Error::TooMuchData {
+                location: Location::new((input.len() - rest.0.len()) * 8),
+                remaining: rest.0.len(),
+            });
+        }
+        Ok(res)
+    }
+}
+
+impl TryFrom<SerialFrame> for Vec<u8> {
+    type Error = DekuError;
+    fn try_from(input: SerialFrame) -> Result<Self, Self::Error> {
+        input.to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{
+        app::{
+            action::{Action, OpCode},
+            operation::{ActionHeader, FileOffset, ReadFileData},
+        },
+        test_tools::test_item_ctx,
+    };
+
+    const SUBIOT: Dialect = Dialect::SubIoTV0_1;
+    const WIZZILAB: Dialect = Dialect::WizzilabV5_3;
+
+    fn read_uid_command() -> Command {
+        Command::new(vec![Action::ReadFileData(ReadFileData {
+            header: ActionHeader {
+                group: false,
+                response: false,
+            },
+            offset: FileOffset::no_offset(0),
+            length: 8u32.into(),
+            opcode: OpCode::READ_FILE_DATA,
+        })])
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(SerialFrame::crc(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn test_alp_frame_subiot() {
+        test_item_ctx(
+            SerialFrame::alp(3, read_uid_command(), SUBIOT),
+            &hex!("C0 00 03 01 04 737C 01 00 00 08"),
+            SUBIOT,
+        );
+
+        // the spec does not define a serial interface, Sub-IoT framing is used instead
+        test_item_ctx(
+            SerialFrame::alp(3, read_uid_command(), Dialect::SpecV1_2),
+            &hex!("C0 00 03 01 04 737C 01 00 00 08"),
+            Dialect::SpecV1_2,
+        );
+    }
+
+    #[test]
+    fn test_ping_frames_subiot() {
+        test_item_ctx(
+            SerialFrame::new(
+                0,
+                MessageType::PING_REQUEST,
+                SerialMessage::PingRequest(vec![0x2A]),
+            ),
+            &hex!("C0 00 00 02 01 64D8 2A"),
+            SUBIOT,
+        );
+        test_item_ctx(
+            SerialFrame::new(
+                1,
+                MessageType::PING_RESPONSE,
+                SerialMessage::PingResponse(vec![0x2A]),
+            ),
+            &hex!("C0 00 01 03 01 64D8 2A"),
+            SUBIOT,
+        );
+    }
+
+    #[test]
+    fn test_logging_frame_subiot() {
+        test_item_ctx(
+            SerialFrame::new(
+                7,
+                MessageType::LOGGING,
+                SerialMessage::Logging("hello".to_string()),
+            ),
+            &hex!("C0 00 07 04 05 D26E 68656C6C6F"),
+            SUBIOT,
+        )
+    }
+
+    #[test]
+    fn test_invalid_frames_subiot() {
+        fn parse(data: &[u8]) -> Result<((&[u8], usize), SerialFrame), Error> {
+            SerialFrame::from_bytes_with_dialect((data, 0), SUBIOT)
+        }
+
+        // bad crc
+        assert_eq!(
+            parse(&hex!("C0 00 03 01 04 737D 01 00 00 08")).unwrap_err(),
+            Error::InvalidValue {
+                location: Location::new(40),
+                field: "crc",
+                expected: "0x737C".into(),
+                found: 0x737D,
+            }
+        );
+        // bad sync
+        assert!(matches!(
+            parse(&hex!("C1 00 03 01 04 737C 01 00 00 08")),
+            Err(Error::InvalidValue {
+                field: "sync",
+                found: 0xC100,
+                ..
+            })
+        ));
+        // truncated
+        assert!(matches!(
+            parse(&hex!("C0 00 03 01 04 737C 01 00")),
+            Err(Error::Incomplete { .. })
+        ));
+        // wizzilab framing
+        assert!(parse(&hex!("01 1F 04 03 20 01 00 00 08")).is_err());
+
+        // unknown opcode in the second action of the payload
+        let payload = hex!("01 00 00 08 3E");
+        let mut data = vec![0xC0, 0x00, 0x03, 0x01, payload.len() as u8];
+        data.extend(SerialFrame::crc(&payload).to_be_bytes());
+        data.extend(payload);
+        let err = parse(&data).unwrap_err();
+        assert_eq!(err.bit_offset(), (7 + 4) * 8 + 2);
+        assert_eq!(
+            err.layers(),
+            &[
+                Layer::SerialFrame { counter: 3 },
+                Layer::Action {
+                    index: 1,
+                    opcode: 0x3E
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frame_size() {
+        for dialect in Dialect::ALL {
+            let data = SerialFrame::alp(3, read_uid_command(), dialect)
+                .to_bytes_with_dialect(dialect)
+                .unwrap();
+            assert_eq!(SerialFrame::frame_size(&data, dialect), Some(data.len()));
+            assert_eq!(SerialFrame::frame_size(&data[..2], dialect), None);
+        }
+    }
+
+    #[test]
+    fn test_consecutive_frames() {
+        let data = hex!("C0 00 03 01 04 737C 01 00 00 08 C0 00 00 02 01 64D8 2A");
+
+        let (rest, first) = SerialFrame::from_bytes_with_dialect((&data, 0), SUBIOT).unwrap();
+        assert_eq!(first.command(), Some(&read_uid_command()));
+
+        let (rest, second) = SerialFrame::from_bytes_with_dialect(rest, SUBIOT).unwrap();
+        assert_eq!(second.message, SerialMessage::PingRequest(vec![0x2A]));
+        assert!(rest.0.is_empty());
+    }
+
+    #[test]
+    fn test_alp_frame_wizzilab() {
+        test_item_ctx(
+            SerialFrame::alp(3, read_uid_command(), WIZZILAB),
+            &hex!("01 1F 04 03 20 01 00 00 08"),
+            WIZZILAB,
+        )
+    }
+
+    #[test]
+    fn test_alp_response_frame_wizzilab() {
+        test_item_ctx(
+            SerialFrame::new(
+                4,
+                MessageType::ALP_RESPONSE,
+                SerialMessage::Alp(read_uid_command()),
+            ),
+            &hex!("01 1F 04 04 21 01 00 00 08"),
+            WIZZILAB,
+        )
+    }
+
+    #[test]
+    fn test_ping_frames_wizzilab() {
+        test_item_ctx(
+            SerialFrame::new(
+                0,
+                MessageType::SYS_PING,
+                SerialMessage::PingRequest(vec![0x2A]),
+            ),
+            &hex!("01 1F 01 00 34 2A"),
+            WIZZILAB,
+        );
+        test_item_ctx(
+            SerialFrame::new(
+                1,
+                MessageType::SYS_PONG,
+                SerialMessage::PingResponse(vec![0x2A]),
+            ),
+            &hex!("01 1F 01 01 35 2A"),
+            WIZZILAB,
+        );
+    }
+
+    #[test]
+    fn test_logging_frame_wizzilab() {
+        test_item_ctx(
+            SerialFrame::new(
+                7,
+                MessageType::PRINTF,
+                SerialMessage::Logging("hello".to_string()),
+            ),
+            &hex!("01 1F 05 07 10 68656C6C6F"),
+            WIZZILAB,
+        )
+    }
+}
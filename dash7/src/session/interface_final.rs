@@ -1,8 +1,11 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
-use crate::{network::Addressee, physical::Channel};
+use crate::{dialect::Dialect, network::Addressee, physical::Channel};
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(ctx = "interface_id: u8, length: u32", id = "interface_id")]
 pub enum InterfaceFinalStatus {
     #[deku(id = "0xD7")]
@@ -13,6 +16,7 @@ pub enum InterfaceFinalStatus {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(id_type = "u8")]
 pub enum InterfaceFinalStatusCode {
     /// No error
@@ -64,16 +68,22 @@ pub enum InterfaceFinalStatusCode {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
-#[deku(ctx = "interface_id: u8, length: u32", id = "interface_id")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "interface_id: u8, length: u32, dialect: Dialect",
+    id = "interface_id"
+)]
 pub enum InterfaceTxStatus {
     #[deku(id = "0xD7")]
-    Dash7(Dash7InterfaceTxStatus),
+    Dash7(#[deku(ctx = "dialect")] Dash7InterfaceTxStatus),
 
     #[deku(id_pat = "_")]
     Other(#[deku(count = "length")] Vec<u8>),
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::WizzilabV5_3")]
 pub struct Dash7InterfaceTxStatus {
     /// PHY layer channel header
     pub channel: Channel,
@@ -85,6 +95,7 @@ pub struct Dash7InterfaceTxStatus {
     #[deku(pad_bits_before = "24")]
     pub lts: u32,
     /// Addressee
+    #[deku(ctx = "dialect")]
     pub addressee: Addressee,
 }
 
@@ -97,7 +108,6 @@ mod test {
         network::{Address, Addressee, NlsState},
         physical::{Channel, ChannelBand, ChannelClass, ChannelCoding, ChannelHeader},
         test_tools::test_item,
-        transport::GroupCondition,
     };
 
     use super::*;
@@ -118,8 +128,6 @@ mod test {
                 error: InterfaceFinalStatusCode::Busy,
                 lts: 0x0708_0000,
                 addressee: Addressee::new(
-                    false,
-                    GroupCondition::Any,
                     Address::VId(0x0011),
                     NlsState::AesCcm64([0; 5]),
                     AccessClass::new(0x0F, 0x0F),
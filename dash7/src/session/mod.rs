@@ -1,16 +1,17 @@
-#[cfg(feature = "std")]
-use std::fmt::Display;
+use alloc::{string::ToString, vec::Vec};
+use core::fmt::Display;
 
 use deku::prelude::*;
 
-use crate::{network::Addressee, physical::Channel, types::VarInt};
+use crate::{dialect::Dialect, network::Addressee, physical::Channel, types::VarInt};
 
-#[cfg(feature = "_wizzilab")]
 mod interface_final;
-#[cfg(feature = "_wizzilab")]
-pub use interface_final::{InterfaceFinalStatus, InterfaceFinalStatusCode, InterfaceTxStatus};
+pub use interface_final::{
+    Dash7InterfaceTxStatus, InterfaceFinalStatus, InterfaceFinalStatusCode, InterfaceTxStatus,
+};
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 8, id_type = "u8")]
 pub enum InterfaceType {
     #[default]
@@ -37,18 +38,19 @@ impl TryFrom<u8> for InterfaceType {
     type Error = DekuError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        Ok(Self::from_bytes((&vec![value], 0))?.1)
+        Ok(Self::from_bytes((&[value], 0))?.1)
     }
 }
 
-impl Into<u8> for InterfaceType {
-    fn into(self) -> u8 {
-        self.deku_id().unwrap()
+impl From<InterfaceType> for u8 {
+    fn from(value: InterfaceType) -> Self {
+        value.deku_id().unwrap()
     }
 }
 
 /// The Response Modes define the condition for termination on success of a Request
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, id_type = "u8")]
 pub enum ResponseMode {
     /// A Request is acknowledged if the DLL CSMA-CA routine succeeds. No
@@ -108,6 +110,7 @@ pub enum ResponseMode {
 ///
 /// In other words, what is the retry policy when sending your payload.
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 3, id_type = "u8")]
 pub enum RetryMode {
     #[default]
@@ -131,6 +134,7 @@ pub enum RetryMode {
 
 /// QoS of the request
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct QoS {
     #[deku(bits = 1)]
     pub stop_on_error: bool,
@@ -142,7 +146,11 @@ pub struct QoS {
 }
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
-#[deku(ctx = "interface_id: InterfaceType, length: u32", id = "interface_id")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "interface_id: InterfaceType, length: u32, dialect: Dialect",
+    id = "interface_id"
+)]
 pub enum InterfaceStatus {
     #[default]
     #[deku(id = "InterfaceType::Host")]
@@ -157,15 +165,14 @@ pub enum InterfaceStatus {
     // #[deku(id = "InterfaceType::LoRaWanOTAA")]
     // LoRaWanOTAA(LoRaWANOTAAInterfaceStatus),
     #[deku(id = "InterfaceType::Dash7")]
-    Dash7(Dash7InterfaceStatus),
+    Dash7(#[deku(ctx = "dialect")] Dash7InterfaceStatus),
 
     #[deku(id = "InterfaceType::Unknown")]
     Other(#[deku(count = "length")] Vec<u8>),
 }
 
-#[cfg(feature = "std")]
 impl Display for InterfaceStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Dash7(status) => status.fmt(f),
             Self::Other(status) => f.write_str(&format!("OtherInterfaceStatus{{ {:?} }}", status)),
@@ -180,6 +187,8 @@ impl Display for InterfaceStatus {
 }
 
 #[derive(DekuRead, DekuWrite, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(ctx = "dialect: Dialect", ctx_default = "Dialect::default()")]
 pub struct Dash7InterfaceStatus {
     /// PHY layer channel
     pub channel: Channel,
@@ -209,12 +218,12 @@ pub struct Dash7InterfaceStatus {
     pub response_timeout: VarInt,
 
     /// Address of source
+    #[deku(ctx = "dialect")]
     pub addressee: Addressee,
 }
 
-#[cfg(feature = "std")]
 impl Display for Dash7InterfaceStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_str("Dash7InterfaceStatus { ")?;
         f.write_str(&format!("channel: {:?}, ", self.channel))?;
         f.write_str(&format!("rx_level: {:?}, ", self.rx_level))?;
@@ -1,4 +1,6 @@
+use alloc::vec::Vec;
 use core::fmt::Debug;
+use std::println;
 
 use deku::prelude::*;
 
@@ -42,3 +44,46 @@ where
         &item
     );
 }
+
+#[track_caller]
+pub fn test_item_ctx<T, Ctx>(item: T, data: &[u8], ctx: Ctx)
+where
+    T: Clone + Debug + PartialEq + for<'a> DekuReader<'a, Ctx> + DekuWriter<Ctx>,
+    Ctx: Copy + Debug,
+{
+    use deku::bitvec::{BitVec, Msb0};
+    use deku::no_std_io::Cursor;
+
+    let mut out_buf = vec![];
+    let mut cursor = Cursor::new(&mut out_buf);
+    let mut writer = Writer::new(&mut cursor);
+    item.to_writer(&mut writer, ctx).unwrap();
+    writer.finalize().unwrap();
+
+    println!(
+        "{:?} == {:?}",
+        BitVec::<u8, Msb0>::from_slice(&out_buf),
+        BitVec::<u8, Msb0>::from_slice(data)
+    );
+
+    assert_eq!(
+        out_buf.as_slice(),
+        data,
+        "Serialize {:?} with {:?} == {:?}",
+        &item,
+        ctx,
+        data
+    );
+
+    let mut cursor = Cursor::new(data);
+    let mut reader = Reader::new(&mut cursor);
+    let result = T::from_reader_with_ctx(&mut reader, ctx).expect("should be parsed without error");
+    assert_eq!(
+        result,
+        item.clone(),
+        "Deserialize {:?} with {:?} == {:?}",
+        data,
+        ctx,
+        &item
+    );
+}
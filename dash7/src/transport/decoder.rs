@@ -0,0 +1,345 @@
+use alloc::collections::BTreeMap;
+
+use deku::prelude::*;
+
+use crate::{
+    dialect::Dialect,
+    link::{AccessClass, ForegroundFrame, ForegroundFrameControl},
+    network::{self, Address, HoppingControl, NlsState},
+    utils::{decode, from_bytes},
+    Error,
+};
+
+use super::Frame;
+
+/// Part the decoding party plays in the dialogs it receives frames of
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+    /// Receives responses to its own requests
+    Requester,
+
+    /// Receives requests
+    Responder,
+
+    /// Receives both requests and responses, like a sniffer. Requests are told apart from
+    /// responses by following the transactions of each dialog.
+    ///
+    /// A retransmitted request has the transaction of the request it repeats, so it is taken
+    /// for a response. Use [`TransportDecoder::decode_with_direction`] when the direction is
+    /// known otherwise, eg. from the addresses of the link layer.
+    #[default]
+    Observer,
+}
+
+/// Start of a frame, which tells which dialog and transaction it belongs to
+#[derive(DekuRead, Debug)]
+struct Header {
+    /// Skips the control
+    #[deku(pad_bytes_before = "1")]
+    dialog_id: u8,
+    transaction_id: u8,
+}
+
+/// Start of a network frame, up to the header of its transport frame
+#[derive(DekuRead, Debug)]
+struct NetworkHeader {
+    _control: network::Control,
+    #[deku(cond = "_control.has_hopping()")]
+    _hopping_control: Option<HoppingControl>,
+    _origin_access_class: AccessClass,
+    #[deku(ctx = "_control.origin_address_type()")]
+    _origin_address: Address,
+    #[deku(ctx = "_control.nls_method()")]
+    _nls_state: NlsState,
+    header: Header,
+}
+
+/// Start of a foreground frame, up to the header of its transport frame
+#[derive(DekuRead, Debug)]
+struct ForegroundHeader {
+    /// Skips the length and subnet
+    #[deku(pad_bytes_before = "2")]
+    _control: ForegroundFrameControl,
+    #[deku(ctx = "_control.address_type()")]
+    _target_address: Address,
+    network: NetworkHeader,
+}
+
+/// Decodes consecutive transport frames, knowing which ones are requests
+///
+/// A congestion timeout (Tc) is only present in requests, so it can not be told apart from the
+/// rest of the frame without knowing the dialog. A single [`Frame`] decodes as if only the first
+/// request of a dialog has one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransportDecoder {
+    role: Role,
+    /// Transaction of the last request, per ongoing dialog
+    dialogs: BTreeMap<u8, u8>,
+}
+
+impl TransportDecoder {
+    pub fn new(role: Role) -> Self {
+        Self {
+            role,
+            dialogs: BTreeMap::new(),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Whether a frame of the transaction `transaction_id` of dialog `dialog_id` is a request
+    pub fn is_request(&self, dialog_id: u8, transaction_id: u8) -> bool {
+        match self.role {
+            Role::Requester => false,
+            Role::Responder => true,
+            Role::Observer => self.dialogs.get(&dialog_id) != Some(&transaction_id),
+        }
+    }
+
+    /// Transaction of the last request of dialog `dialog_id`, if it is ongoing
+    pub fn transaction(&self, dialog_id: u8) -> Option<u8> {
+        self.dialogs.get(&dialog_id).copied()
+    }
+
+    /// Decodes the next frame of a dialog. See [`crate::app::command::Command`] for
+    /// `command_length`.
+    pub fn decode<'a>(
+        &mut self,
+        input: (&'a [u8], usize),
+        command_length: u32,
+        dialect: Dialect,
+    ) -> Result<((&'a [u8], usize), Frame), Error> {
+        let (_, header) =
+            from_bytes::<Header, _>(input, ()).map_err(|err| Error::from_deku(err, 0))?;
+        let is_request = self.is_request(header.dialog_id, header.transaction_id);
+        self.decode_with_direction(input, command_length, dialect, is_request)
+    }
+
+    /// Like [`Self::decode`], for a frame of which it is known whether it is a request
+    pub fn decode_with_direction<'a>(
+        &mut self,
+        input: (&'a [u8], usize),
+        command_length: u32,
+        dialect: Dialect,
+        is_request: bool,
+    ) -> Result<((&'a [u8], usize), Frame), Error> {
+        let (rest, frame) = decode::<Frame, _>(input, (command_length, dialect, Some(is_request)))?;
+        self.update(&frame, is_request);
+        Ok((rest, frame))
+    }
+
+    /// Decodes the next network frame of a dialog, finding its transaction after the network
+    /// header. See [`network::Frame`] for `command_length`.
+    ///
+    /// The transport header of a secured frame is encrypted, so those are decoded like any
+    /// other frame.
+    pub fn decode_network<'a>(
+        &mut self,
+        input: (&'a [u8], usize),
+        command_length: u32,
+        dialect: Dialect,
+    ) -> Result<((&'a [u8], usize), network::Frame), Error> {
+        let (_, header) =
+            from_bytes::<NetworkHeader, _>(input, ()).map_err(|err| Error::from_deku(err, 0))?;
+        let is_request = self.is_request(header.header.dialog_id, header.header.transaction_id);
+
+        let (rest, frame) =
+            decode::<network::Frame, _>(input, (command_length, dialect, Some(is_request)))?;
+        self.update(frame.frame(), is_request);
+        Ok((rest, frame))
+    }
+
+    /// Decodes the next foreground frame of a dialog, like [`Self::decode_network`]
+    pub fn decode_foreground<'a>(
+        &mut self,
+        input: (&'a [u8], usize),
+        dialect: Dialect,
+    ) -> Result<((&'a [u8], usize), ForegroundFrame), Error> {
+        let (_, header) =
+            from_bytes::<ForegroundHeader, _>(input, ()).map_err(|err| Error::from_deku(err, 0))?;
+        let header = header.network.header;
+        let is_request = self.is_request(header.dialog_id, header.transaction_id);
+
+        let (rest, frame) = ForegroundFrame::from_bytes_with_direction(input, dialect, is_request)?;
+        self.update(frame.frame().frame(), is_request);
+        Ok((rest, frame))
+    }
+
+    fn update(&mut self, frame: &Frame, is_request: bool) {
+        if frame.control.is_dialog_end {
+            self.dialogs.remove(&frame.dialog_id);
+        } else if is_request {
+            self.dialogs.insert(frame.dialog_id, frame.transaction_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{app::command::Command, transport::Control, types::VarInt};
+
+    fn decode_frame(decoder: &mut TransportDecoder, data: &[u8]) -> Frame {
+        let ((rest, _), frame) = decoder.decode((data, 0), 0, Dialect::default()).unwrap();
+        assert!(rest.is_empty(), "{:?} left", rest);
+        frame
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut decoder = TransportDecoder::default();
+        let read = Command::builder().read_file(0x00, 0, 8).build().unwrap();
+
+        // first request of dialog 0x11, with a Tc
+        let frame = decode_frame(&mut decoder, &hex!("88 11 01 05 01000008"));
+        assert_eq!(
+            frame.congestion_timeout,
+            Some(VarInt::new(5, false).unwrap())
+        );
+        assert_eq!(frame.command, read);
+        assert_eq!(decoder.transaction(0x11), Some(0x01));
+
+        // the response copies the ack request flag, but has no Tc
+        let frame = decode_frame(&mut decoder, &hex!("08 11 01 01000008"));
+        assert_eq!(frame.congestion_timeout, None);
+        assert_eq!(frame.command, read);
+
+        // the next request of the dialog is no dialog start, but does have a Tc
+        let frame = decode_frame(&mut decoder, &hex!("08 11 02 05 01000008"));
+        assert_eq!(
+            frame.congestion_timeout,
+            Some(VarInt::new(5, false).unwrap())
+        );
+        assert_eq!(decoder.transaction(0x11), Some(0x02));
+
+        // a stateless decode reads the Tc as part of the command
+        assert!(Frame::from_bytes((&hex!("08 11 02 05 01000008"), 0)).is_err());
+
+        // the last response ends the dialog
+        let frame = decode_frame(&mut decoder, &hex!("48 11 02 01000008"));
+        assert_eq!(frame.congestion_timeout, None);
+        assert_eq!(decoder.transaction(0x11), None);
+    }
+
+    #[test]
+    fn test_retransmission() {
+        let mut decoder = TransportDecoder::default();
+        let request = hex!("08 11 02 05 01000008");
+        decode_frame(&mut decoder, &request);
+
+        // the same request again is taken for a response of its transaction
+        assert!(!decoder.is_request(0x11, 0x02));
+        assert!(decoder
+            .decode((&request, 0), 0, Dialect::default())
+            .is_err());
+
+        let ((rest, _), frame) = decoder
+            .decode_with_direction((&request, 0), 0, Dialect::default(), true)
+            .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            frame.congestion_timeout,
+            Some(VarInt::new(5, false).unwrap())
+        );
+        assert_eq!(decoder.transaction(0x11), Some(0x02));
+    }
+
+    #[test]
+    fn test_roles() {
+        let mut decoder = TransportDecoder::new(Role::Responder);
+        let frame = decode_frame(&mut decoder, &hex!("08 22 07 05 01000008"));
+        assert_eq!(
+            frame.congestion_timeout,
+            Some(VarInt::new(5, false).unwrap())
+        );
+
+        let mut decoder = TransportDecoder::new(Role::Requester);
+        let frame = decode_frame(&mut decoder, &hex!("88 22 07 01000008"));
+        assert_eq!(frame.congestion_timeout, None);
+        assert_eq!(decoder.transaction(0x22), None);
+    }
+
+    #[test]
+    fn test_foreground() {
+        let read = Command::builder().read_file(0x00, 0, 8).build().unwrap();
+        let transport = |control, transaction_id, congestion_timeout| Frame {
+            control,
+            dialog_id: 0x11,
+            transaction_id,
+            congestion_timeout,
+            command: read.clone(),
+            ..Default::default()
+        };
+        let timeout = Some(VarInt::new(5, false).unwrap());
+        let request = Control {
+            is_ack_requested: true,
+            ..Default::default()
+        };
+        let transports = [
+            transport(
+                Control {
+                    is_dialog_start: true,
+                    ..request.clone()
+                },
+                0x01,
+                timeout,
+            ),
+            transport(request.clone(), 0x01, None),
+            // a request in the middle of the dialog, with a Tc
+            transport(request.clone(), 0x02, timeout),
+            transport(
+                Control {
+                    is_dialog_end: true,
+                    ..request
+                },
+                0x02,
+                None,
+            ),
+        ];
+
+        let frames: Vec<_> = transports
+            .into_iter()
+            .map(|transport| {
+                let network = network::Frame::new(
+                    AccessClass::new(0, 1),
+                    Address::NoId,
+                    NlsState::None,
+                    transport,
+                );
+                ForegroundFrame::new(0x05, 0x10, Address::NoId, network)
+            })
+            .collect();
+        let data: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.to_bytes().unwrap())
+            .collect();
+
+        let mut decoder = TransportDecoder::default();
+        let mut rest = (data.as_slice(), 0);
+        for frame in &frames {
+            let (next, decoded) = decoder.decode_foreground(rest, Dialect::default()).unwrap();
+            assert_eq!(&decoded, frame);
+            rest = next;
+        }
+        assert!(rest.0.is_empty());
+        assert_eq!(decoder.transaction(0x11), None);
+
+        // the network frame of the request in the middle of the dialog
+        let mut decoder = TransportDecoder::default();
+        decoder.dialogs.insert(0x11, 0x01);
+        let data = frames[2].frame().to_bytes().unwrap();
+        let ((rest, _), decoded) = decoder
+            .decode_network((&data, 0), data.len() as u32, Dialect::default())
+            .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(&decoded, frames[2].frame());
+        assert_eq!(decoder.transaction(0x11), Some(0x02));
+    }
+}
@@ -1,8 +1,14 @@
+use alloc::vec::Vec;
+
 use deku::prelude::*;
 
-use crate::{app::command::Command, types::VarInt};
+use crate::{app::command::Command, dialect::Dialect, types::VarInt};
+
+mod decoder;
+pub use decoder::{Role, TransportDecoder};
 
 #[derive(DekuRead, DekuWrite, Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(bits = 2, id_type = "u8")]
 pub enum GroupCondition {
     /// <, =, > (always true)
@@ -21,11 +27,18 @@ pub enum GroupCondition {
 }
 
 // TODO: make these names more readable
+/// D7ATP control. It takes a full byte, the dialog end flag (DLGE) follows the dialog start.
+/// Flags are in the same order, from the most significant bit, as `d7atp_ctrl_t` in Sub-IoT's
+/// `stack/modules/d7ap/d7atp.h`.
+/// SPEC: 8.2.1
 #[derive(DekuRead, DekuWrite, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Control {
     #[deku(bits = 1)]
     pub is_dialog_start: bool,
     #[deku(bits = 1)]
+    pub is_dialog_end: bool,
+    #[deku(bits = 1)]
     pub has_listen_timeout: bool,
     #[deku(bits = 1)]
     pub has_execution_delay_timeout: bool,
@@ -40,13 +53,18 @@ pub struct Control {
 }
 
 #[derive(DekuRead, DekuWrite, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AckTemplate {
     pub transaction_id_start: u8,
     pub transaction_id_stop: u8,
 }
 
 #[derive(DekuRead, DekuWrite, Clone, Debug, PartialEq, Default)]
-#[deku(ctx = "command_length: u32", ctx_default = "0")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[deku(
+    ctx = "command_length: u32, dialect: Dialect, is_request: Option<bool>",
+    ctx_default = "0, Dialect::default(), None"
+)]
 pub struct Frame {
     pub control: Control,
 
@@ -68,18 +86,70 @@ pub struct Frame {
     #[deku(cond = "control.has_execution_delay_timeout")]
     pub execution_delay_timeout: Option<VarInt>,
 
-    // TODO currently we have no way to know if Tc is present or not
     /// Tc is present when control.is_ack_requested AND when we are requester,
     /// while responders copy this flag but do NOT provide a Tc.
-    /// When parsing single frames without knowledge of dialogs we cannot determine this.
-    /// We use control.is_dialog_start for now but this will break when we start supporting multiple transactions per dialog
-    #[deku(cond = "control.is_ack_requested && control.is_dialog_start")]
+    /// Whether the frame is a request follows from the dialog, see [`TransportDecoder`]. Without
+    /// that context, only the first request of a dialog is assumed to carry a Tc.
+    #[deku(cond = "control.is_ack_requested && is_request.unwrap_or(control.is_dialog_start)")]
     pub congestion_timeout: Option<VarInt>,
 
     #[deku(cond = "control.is_ack_not_void")]
     pub ack_template: Option<AckTemplate>,
 
     // TODO: is this really the command length or rather the length of the entire message?
-    #[deku(ctx = "command_length")]
+    #[deku(ctx = "command_length, dialect")]
     pub command: Command,
 }
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::test_tools::test_item_ctx;
+
+    fn read() -> Command {
+        Command::builder().read_file(0x00, 0, 8).build().unwrap()
+    }
+
+    #[test]
+    fn test_dialog_end() {
+        // last response of a dialog. With a 7 bit control, DLGE was read as TL and the
+        // following flags shifted by one, so a listen timeout was expected.
+        test_item_ctx(
+            Frame {
+                control: Control {
+                    is_dialog_end: true,
+                    ..Default::default()
+                },
+                dialog_id: 0x11,
+                transaction_id: 0x01,
+                command: read(),
+                ..Default::default()
+            },
+            &hex!("40 11 01 01000008"),
+            (7, Dialect::default(), None),
+        );
+    }
+
+    #[test]
+    fn test_agc() {
+        // AGC is the last bit of the control byte, which was past the end of a 7 bit control
+        test_item_ctx(
+            Frame {
+                control: Control {
+                    is_dialog_start: true,
+                    has_agc: true,
+                    ..Default::default()
+                },
+                dialog_id: 0x11,
+                transaction_id: 0x01,
+                target_rx_level_i: Some(0x50),
+                command: read(),
+                ..Default::default()
+            },
+            &hex!("81 11 01 50 01000008"),
+            (8, Dialect::default(), None),
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use alloc::{borrow::Cow, vec::Vec};
 
 use deku::{
     ctx::{BitSize, Endian},
@@ -14,9 +14,9 @@ pub enum VarIntError {
     Unknown,
 }
 
-impl Into<DekuError> for VarIntError {
-    fn into(self) -> DekuError {
-        match self {
+impl From<VarIntError> for DekuError {
+    fn from(error: VarIntError) -> Self {
+        match error {
             VarIntError::ValueTooLarge(value) => DekuError::InvalidParam(Cow::Owned(format!(
                 "VarInt: Value too large: {:?}. Max: {:?}",
                 value,
@@ -141,9 +141,9 @@ impl VarInt {
     }
 }
 
-impl Into<u32> for VarInt {
-    fn into(self) -> u32 {
-        self.value as u32
+impl From<VarInt> for u32 {
+    fn from(value: VarInt) -> Self {
+        value.value
     }
 }
 
@@ -153,6 +153,22 @@ impl From<u32> for VarInt {
     }
 }
 
+/// Serialized as its decompressed value
+#[cfg(feature = "serde")]
+impl serde::Serialize for VarInt {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VarInt {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = u32::deserialize(deserializer)?;
+        Self::new(value, false).map_err(|err| serde::de::Error::custom(DekuError::from(err)))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -180,4 +196,13 @@ mod test {
         test_item(VarInt::new_unchecked(32, false), &[0b00101000u8]);
         test_item(VarInt::new_unchecked(507904, false), &[0xFFu8]);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let item = VarInt::new(384, false).unwrap();
+        assert_eq!(serde_json::to_string(&item).unwrap(), "384");
+        assert_eq!(serde_json::from_str::<VarInt>("384").unwrap(), item);
+        assert!(serde_json::from_str::<VarInt>("507905").is_err());
+    }
 }
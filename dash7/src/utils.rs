@@ -1,18 +1,21 @@
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
 use core::cmp;
 use core::{
     mem::{self, MaybeUninit},
-    ptr, slice,
+    ptr,
 };
-use std::borrow::Cow;
-use std::fmt::Debug;
 
 use deku::{
     ctx::{ByteSize, Limit},
-    no_std_io::{Cursor, Read, Seek, Write},
+    no_std_io::{Cursor, Read, Seek, SeekFrom, Write},
     prelude::*,
 };
 
-use crate::app::operation::Length;
+use crate::{app::operation::Length, error::Error};
 
 struct TransientDropper<T> {
     base_ptr: *mut T,
@@ -20,9 +23,9 @@ struct TransientDropper<T> {
 }
 
 impl<T> Drop for TransientDropper<T> {
-    fn drop(self: &'_ mut Self) {
+    fn drop(&mut self) {
         unsafe {
-            ptr::drop_in_place(slice::from_raw_parts_mut(
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
                 self.base_ptr,
                 self.initialized_count,
             ));
@@ -30,12 +33,12 @@ impl<T> Drop for TransientDropper<T> {
     }
 }
 
-pub fn pad_rest<'a>(rest: (&'a [u8], usize), bits_read: usize) -> (&'a [u8], usize) {
+pub fn pad_rest(rest: (&[u8], usize), bits_read: usize) -> (&[u8], usize) {
     if (rest.0.len() * 8 + rest.1) <= bits_read {
         return (&[], bits_read % 8);
     }
 
-    let read_whole_byte = (bits_read % 8) == 0;
+    let read_whole_byte = bits_read.is_multiple_of(8);
     let idx = if read_whole_byte {
         bits_read / 8
     } else {
@@ -65,22 +68,68 @@ pub fn from_bytes<'a, T, Ctx>(
     ctx: Ctx,
 ) -> Result<((&'a [u8], usize), T), DekuError>
 where
-    T: DekuReader<'a, Ctx> + Debug,
+    T: DekuReader<'a, Ctx>,
 {
     let mut cursor = Cursor::new(input.0);
-    let mut reader = &mut Reader::new(&mut cursor);
+    let reader = &mut Reader::new(&mut cursor);
     if input.1 != 0 {
         reader.skip_bits(input.1)?;
     }
-    let value = T::from_reader_with_ctx(&mut reader, ctx)?;
-    println!(
-        "value {:?}, reader.bits_read {:?}  {:?}",
-        value, reader.bits_read, input
-    );
-
+    let value = T::from_reader_with_ctx(reader, ctx)?;
     Ok((pad_rest(input, reader.bits_read), value))
 }
 
+/// Like [`from_bytes`], but reports the offset at which decoding failed
+pub fn decode<'a, T, Ctx>(
+    input: (&'a [u8], usize),
+    ctx: Ctx,
+) -> Result<((&'a [u8], usize), T), Error>
+where
+    T: DekuReader<'a, Ctx>,
+{
+    let mut cursor = Cursor::new(input.0);
+    let reader = &mut Reader::new(&mut cursor);
+    if input.1 != 0 {
+        reader
+            .skip_bits(input.1)
+            .map_err(|err| Error::from_deku(err, 0))?;
+    }
+    let value = T::from_reader_with_ctx(reader, ctx);
+    let bits_read = position(reader).map_err(|err| Error::from_deku(err, reader.bits_read))?;
+    match value {
+        Ok(value) => Ok((pad_rest(input, bits_read), value)),
+        Err(err) => Err(Error::from_deku(err, bits_read)),
+    }
+}
+
+/// Number of bits consumed from the input of `reader`.
+///
+/// Unlike `Reader::bits_read`, this does not count the byte `Reader::end` peeks at.
+pub fn position<R>(reader: &mut Reader<R>) -> Result<usize, DekuError>
+where
+    R: Read + Seek,
+{
+    // no_std_io has no stream_position
+    #[allow(clippy::seek_from_current)]
+    let bytes = reader
+        .as_mut()
+        .seek(SeekFrom::Current(0))
+        .map_err(|err| DekuError::Io(err.kind()))?;
+    Ok(bytes as usize * 8 - reader.rest().len())
+}
+
+/// Byte at an arbitrary bit offset, if the input is long enough
+pub fn peek_byte(data: &[u8], bit_offset: usize) -> Option<u8> {
+    let idx = bit_offset / 8;
+    let shift = bit_offset % 8;
+    let high = *data.get(idx)?;
+    if shift == 0 {
+        return Some(high);
+    }
+    let low = *data.get(idx + 1)?;
+    Some((high << shift) | (low >> (8 - shift)))
+}
+
 pub fn write_length_prefixed<W, T, Ctx>(
     writer: &mut Writer<W>,
     item: &T,
@@ -91,9 +140,10 @@ where
     W: Write + Seek,
 {
     // first write the whole item into a byte buffer
-    let mut out_buf_cur = Cursor::new(Vec::new());
+    let mut out_buf = Vec::new();
+    let mut out_buf_cur = Cursor::new(&mut out_buf);
     let mut tmp_writer = Writer::new(&mut out_buf_cur);
-    let _ = item.to_writer(&mut tmp_writer, ctx)?;
+    item.to_writer(&mut tmp_writer, ctx)?;
     let _ = tmp_writer.finalize();
 
     // get the length of it
@@ -123,7 +173,8 @@ where
     let enum_id = enum_id.try_into()?;
 
     // first write the whole item into a byte buffer
-    let mut out_buf_cur = Cursor::new(Vec::new());
+    let mut out_buf = Vec::new();
+    let mut out_buf_cur = Cursor::new(&mut out_buf);
     let mut tmp_writer = Writer::new(&mut out_buf_cur);
     item.to_writer(&mut tmp_writer, (enum_id, fallback_length))?;
     let _ = tmp_writer.finalize();
@@ -186,9 +237,9 @@ where
         initialized_count: 0,
     };
 
-    for i in 0..N {
+    for elem in data.iter_mut() {
         let value = <T as DekuReader<'_, _>>::from_reader_with_ctx(reader, ())?;
-        data[i].write(value);
+        elem.write(value);
         unsafe { transient_dropper.base_ptr = transient_dropper.base_ptr.add(1) };
         transient_dropper.initialized_count += 1;
     }
@@ -211,3 +262,32 @@ where
     }
     Ok(())
 }
+
+/// Serialize integers as upper case, zero padded hex strings
+#[cfg(feature = "serde")]
+pub(crate) mod hex {
+    use alloc::string::String;
+    use core::fmt::UpperHex;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: UpperHex,
+        S: Serializer,
+    {
+        let width = core::mem::size_of::<T>() * 2;
+        serializer.collect_str(&format_args!("{:0width$X}", value, width = width))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TryFrom<u64>,
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let value =
+            u64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(Error::custom)?;
+        T::try_from(value).map_err(|_| Error::custom("hex value out of range"))
+    }
+}